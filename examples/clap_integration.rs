@@ -0,0 +1,68 @@
+//! Wiring `--no-update-check` and `--check-updates` flags, parsed with clap,
+//! into a checker.
+//!
+//! Run with: `cargo run --example clap_integration -- [--no-update-check] [--check-updates] [--config FILE] [--registry-url URL]`
+//!
+//! The config file stands in for the application's own: a line reading
+//! `update_check = false` turns checks off by default, which
+//! `--check-updates` still overrides. Pass `--registry-url` to point the
+//! check at a local mock registry.
+
+use clap::Parser;
+use std::path::{Path, PathBuf};
+use tiny_update_check::{CliPolicy, UpdateChecker};
+
+#[derive(Parser)]
+struct Args {
+    /// Don't check for a newer version.
+    #[arg(long)]
+    no_update_check: bool,
+
+    /// Check for a newer version now, even if opted out.
+    #[arg(long)]
+    check_updates: bool,
+
+    /// Read the `update_check` default from this file.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Check against this registry instead of crates.io.
+    #[arg(long)]
+    registry_url: Option<String>,
+}
+
+/// The `update_check` setting in the config file at `path`, if any.
+fn configured_update_check(path: &Path) -> Option<bool> {
+    let config = std::fs::read_to_string(path).ok()?;
+    config.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        if key.trim() == "update_check" {
+            value.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+fn main() {
+    let args = Args::parse();
+    let policy = CliPolicy {
+        no_update_check: args.no_update_check,
+        force_check: args.check_updates,
+        config_enabled: args.config.as_deref().and_then(configured_update_check),
+    };
+
+    let mut checker = UpdateChecker::new("serde", "1.0.0");
+    if let Some(url) = args.registry_url {
+        checker = checker.registry_url(url);
+    }
+    // Apply the policy last, so the flags take precedence over both
+    // `DO_NOT_TRACK` and the configured default.
+    let checker = policy.apply(checker);
+
+    match checker.check() {
+        Ok(Some(update)) => eprintln!("Update available: {} -> {}", update.current, update.latest),
+        Ok(None) => eprintln!("No update to report."),
+        Err(e) => eprintln!("Update check failed: {e}"),
+    }
+}
@@ -16,88 +16,1106 @@
 //! # }
 //! ```
 
-use std::path::PathBuf;
-use std::time::Duration;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
 
-#[cfg(feature = "do-not-track")]
-use crate::do_not_track_enabled;
 use crate::{
-    DetailedUpdateInfo, Error, USER_AGENT, UpdateInfo, compare_versions, extract_newest_version,
-    read_cache, truncate_message, validate_crate_name,
+    AuthToken, Backend, CheckEvent, CheckMode, CheckOutcome, CheckStats, CheckerConfig,
+    CompareOptions, Comparison, CustomSource, DEFAULT_MAX_MEMORY, DetailedUpdateInfo,
+    DistManifestSource, Error, ErrorKind, GitHubSource, GitLabSource, GitTagsSource,
+    HomebrewSource, JsonSource, Latest, MESSAGE_READ_LIMIT, ManifestSource, NoUpdateReason,
+    NpmSource, OciSource, Paths, PlannedRequest, PrereleasePolicy, RaceWinner, ReleaseSource,
+    Response, ResponseFormat, Rng, SharedRng, SourceContext, Spki256, SuggestStrategy, TextSource,
+    TlsVersion, USER_AGENT, UnparsableLatestPolicy, UpdateInfo, UpgradeInfo, Verdict,
+    VersionPolicy, VersionSource, WingetSource, after_attempts, app_user_agent, binary_age_notice,
+    body_limit, cache_file, cached_however_old, cached_max_age, cargo_net_offline,
+    check_crate_name, clear_cache, crate_api_url, default_cache_key, git, history, http_error,
+    is_unavailable, jittered, latest_verdict, message_text, note_rate_limit, oci, panic_message,
+    parse_fetched, paths, probe, project, proxy::ProxyUrl, race_lost, rate_limited,
+    read_cached_latest, refused_credentials, registries_unavailable, registry_attempts,
+    request_allowed, retry_delay, seed_cache, skip_reason, stale_cached, status_error,
+    successor_update, tiny_update_check_offline, too_large_error, validate_base_url,
+    validate_cache_key, validate_crate_name, validate_registry_url, version_change, write_cache,
 };
 
+/// The future returned by an [`UpdateChecker::on_event_async`] callback.
+pub type EventFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// How many events may wait for a slow [`UpdateChecker::on_event_async`]
+/// callback before later ones are dropped.
+const EVENT_QUEUE: usize = 32;
+
+/// How long an [`UpdateChecker::on_event_async`] callback's future may run
+/// before it is dropped so the next event's can start.
+const EVENT_CALLBACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A registered async [`CheckEvent`] callback, and the queue of its futures
+/// waiting to run.
+#[derive(Clone)]
+struct AsyncEventHook {
+    callback: Arc<dyn Fn(CheckEvent) -> EventFuture + Send + Sync>,
+    queue: Arc<Mutex<Option<tokio::sync::mpsc::Sender<EventFuture>>>>,
+}
+
+impl AsyncEventHook {
+    fn new(callback: impl Fn(CheckEvent) -> EventFuture + Send + Sync + 'static) -> Self {
+        Self {
+            callback: Arc::new(callback),
+            queue: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Dispatch an event without awaiting the callback.
+    ///
+    /// The returned future is queued for a single task on the current tokio
+    /// runtime, which runs the futures one at a time in the order of their
+    /// events, so a slow callback never delays the check. A future that
+    /// panics, or runs past [`EVENT_CALLBACK_TIMEOUT`], is dropped and the
+    /// next one starts. When [`EVENT_QUEUE`] futures are already waiting, the
+    /// event is dropped. A panic while *creating* the future is caught here.
+    /// Outside a tokio runtime the event is dropped.
+    fn emit(&self, event: CheckEvent) {
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let Ok(future) = std::panic::catch_unwind(AssertUnwindSafe(|| (self.callback)(event)))
+        else {
+            return;
+        };
+        let sender = {
+            let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+            // The task is gone if the runtime it ran on was shut down.
+            match *queue {
+                Some(ref sender) if !sender.is_closed() => sender.clone(),
+                _ => queue.insert(Self::spawn_runner(&handle)).clone(),
+            }
+        };
+        // A full queue drops the event rather than grow without bound.
+        let _ = sender.try_send(future);
+    }
+
+    /// Spawn the task running queued futures onto `handle`, returning its
+    /// queue.
+    fn spawn_runner(handle: &tokio::runtime::Handle) -> tokio::sync::mpsc::Sender<EventFuture> {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<EventFuture>(EVENT_QUEUE);
+        handle.spawn(async move {
+            while let Some(future) = receiver.recv().await {
+                // A panicking or hung callback is its own.
+                let _ = tokio::time::timeout(EVENT_CALLBACK_TIMEOUT, CatchUnwind(Box::pin(future)))
+                    .await;
+            }
+        });
+        sender
+    }
+}
+
+/// A future that resolves to the panic message if polling `F` panics.
+struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let inner = self.0.as_mut();
+        match std::panic::catch_unwind(AssertUnwindSafe(|| inner.poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(panic_message(payload.as_ref()))),
+        }
+    }
+}
+
+impl std::fmt::Debug for AsyncEventHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AsyncEventHook")
+    }
+}
+
 /// An async update checker for crates.io.
 ///
 /// This is the async equivalent of [`crate::UpdateChecker`], using `reqwest`
 /// for HTTP requests instead of `minreq`.
 #[derive(Debug, Clone)]
+// Independent builder switches, not a state machine.
+#[allow(clippy::struct_excessive_bools)]
 pub struct UpdateChecker {
-    crate_name: String,
+    config: CheckerConfig,
     current_version: String,
     cache_duration: Duration,
-    timeout: Duration,
-    cache_dir: Option<PathBuf>,
-    include_prerelease: bool,
-    message_url: Option<String>,
+    cache_jitter: Duration,
+    honor_cache_control: bool,
+    daily_request_budget: Option<u32>,
+    pub(crate) timeout: Duration,
+    max_memory: usize,
+    state_dir: Option<PathBuf>,
+    durable_cache: bool,
+    compare: CompareOptions,
+    successor_crate: Option<String>,
+    fallback_registries: Vec<String>,
+    retries: u32,
+    total_deadline: Option<Duration>,
+    project_config: bool,
+    project_file: Option<String>,
+    race_registry: bool,
+    offline_probe: bool,
+    ignore_cargo_net_offline: bool,
+    offline: bool,
+    stale_on_server_error: bool,
+    mode: CheckMode,
+    build_timestamp: Option<SystemTime>,
+    max_binary_age: Option<Duration>,
+    event_hook: Option<AsyncEventHook>,
+    stats: Option<Arc<CheckStats>>,
+    previous_run: Arc<OnceLock<Option<String>>>,
+    /// The client given with [`with_client`](Self::with_client).
+    client: Option<reqwest::Client>,
+    /// The clients built for this checker and its clones, by configuration.
+    #[cfg(not(feature = "shared-pool"))]
+    clients: Arc<crate::pool::Pool<reqwest::Client>>,
+    rng: SharedRng,
 }
 
-impl UpdateChecker {
-    /// Create a new async update checker for the given crate.
+impl UpdateChecker {
+    /// Create a new async update checker for the given crate.
+    #[must_use]
+    pub fn new(crate_name: impl Into<String>, current_version: impl Into<String>) -> Self {
+        Self::with_paths(crate_name, current_version, &paths::System)
+    }
+
+    /// Like [`new`](Self::new), resolving the default directories from
+    /// `paths` instead of the process environment.
+    pub(crate) fn with_paths(
+        crate_name: impl Into<String>,
+        current_version: impl Into<String>,
+        paths: &impl Paths,
+    ) -> Self {
+        Self {
+            config: CheckerConfig::new(crate_name.into(), paths.cache_dir(paths::Os::CURRENT)),
+            current_version: current_version.into(),
+            cache_duration: Duration::from_secs(24 * 60 * 60),
+            cache_jitter: Duration::ZERO,
+            honor_cache_control: false,
+            daily_request_budget: None,
+            timeout: Duration::from_secs(5),
+            max_memory: DEFAULT_MAX_MEMORY,
+            state_dir: paths.state_dir(paths::Os::CURRENT),
+            durable_cache: false,
+            compare: CompareOptions::default(),
+            successor_crate: None,
+            fallback_registries: Vec::new(),
+            retries: 0,
+            total_deadline: None,
+            project_config: false,
+            project_file: None,
+            race_registry: false,
+            offline_probe: false,
+            ignore_cargo_net_offline: false,
+            offline: false,
+            stale_on_server_error: true,
+            mode: CheckMode::Auto,
+            build_timestamp: None,
+            max_binary_age: None,
+            event_hook: None,
+            stats: None,
+            previous_run: Arc::default(),
+            client: None,
+            #[cfg(not(feature = "shared-pool"))]
+            clients: Arc::default(),
+            rng: SharedRng::default(),
+        }
+    }
+
+    /// Set the cache duration. Defaults to 24 hours.
+    #[must_use]
+    pub const fn cache_duration(mut self, duration: Duration) -> Self {
+        self.cache_duration = duration;
+        self
+    }
+
+    /// Expire cached results up to `jitter` earlier than the cache duration.
+    ///
+    /// See [`crate::UpdateChecker::cache_jitter`]. Defaults to zero.
+    #[must_use]
+    pub const fn cache_jitter(mut self, jitter: Duration) -> Self {
+        self.cache_jitter = jitter;
+        self
+    }
+
+    /// Expire cached results early when the server asked for it.
+    ///
+    /// See [`crate::UpdateChecker::honor_cache_control`]. Defaults to `false`.
+    #[must_use]
+    pub const fn honor_cache_control(mut self, honor: bool) -> Self {
+        self.honor_cache_control = honor;
+        self
+    }
+
+    /// Never contact the registry more than `limit` times in any 24 hours.
+    ///
+    /// See [`crate::UpdateChecker::daily_request_budget`].
+    #[must_use]
+    pub const fn daily_request_budget(mut self, limit: u32) -> Self {
+        self.daily_request_budget = Some(limit);
+        self
+    }
+
+    /// Set the HTTP request timeout. Defaults to 5 seconds.
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Cap the memory a check may use for the registry response, in bytes.
+    ///
+    /// See [`crate::UpdateChecker::max_memory`].
+    #[must_use]
+    pub const fn max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory = bytes;
+        self
+    }
+
+    /// Identify the calling application in the `User-Agent` header.
+    ///
+    /// See [`crate::UpdateChecker::app_user_agent`].
+    #[must_use]
+    pub fn app_user_agent(mut self, name: &str, version: &str, contact: Option<&str>) -> Self {
+        self.config.app_user_agent = Some(app_user_agent(name, version, contact));
+        self
+    }
+
+    /// Send a header with every request.
+    ///
+    /// See [`crate::UpdateChecker::header`].
+    #[must_use]
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config.headers.push(name.into(), value.into());
+        self
+    }
+
+    /// Let a custom header replace the `User-Agent` or `Authorization`
+    /// header this crate sets.
+    ///
+    /// See [`crate::UpdateChecker::allow_header_overrides`].
+    #[must_use]
+    pub const fn allow_header_overrides(mut self, allow: bool) -> Self {
+        self.config.header_overrides = allow;
+        self
+    }
+
+    /// Set a custom cache directory. Defaults to system cache directory.
+    #[must_use]
+    pub fn cache_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.config.cache_dir = dir;
+        self
+    }
+
+    /// Set a custom directory for persistent state.
+    ///
+    /// See [`crate::UpdateChecker::state_dir`].
+    #[must_use]
+    pub fn state_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.state_dir = dir;
+        self
+    }
+
+    /// Name the cache and state files after `key` instead of the crate.
+    ///
+    /// See [`crate::UpdateChecker::cache_key`].
+    #[must_use]
+    pub fn cache_key(mut self, key: impl Into<String>) -> Self {
+        self.config.cache_key = Some(key.into());
+        self
+    }
+
+    /// Flush cache writes to disk before they are considered done.
+    ///
+    /// See [`crate::UpdateChecker::durable_cache`].
+    #[must_use]
+    pub const fn durable_cache(mut self, durable: bool) -> Self {
+        self.durable_cache = durable;
+        self
+    }
+
+    /// Answer from the cache, however old, when the registry responds with a
+    /// `5xx` server error. Defaults to `true`.
+    ///
+    /// See [`crate::UpdateChecker::stale_on_server_error`].
+    #[must_use]
+    pub const fn stale_on_server_error(mut self, enabled: bool) -> Self {
+        self.stale_on_server_error = enabled;
+        self
+    }
+
+    /// Keep the cache and request budget in memory instead of on disk.
+    ///
+    /// See [`crate::UpdateChecker::ephemeral`].
+    #[must_use]
+    pub fn ephemeral(mut self, ephemeral: bool) -> Self {
+        self.config.memory = ephemeral.then(Arc::default);
+        self
+    }
+
+    /// Include pre-release versions in update checks. Defaults to `false`.
+    #[must_use]
+    pub const fn include_prerelease(mut self, include: bool) -> Self {
+        self.compare.prerelease = if include {
+            PrereleasePolicy::Always
+        } else {
+            PrereleasePolicy::Never
+        };
+        self
+    }
+
+    /// Choose which pre-release versions are reported as updates.
+    ///
+    /// See [`crate::UpdateChecker::prerelease_policy`].
+    #[must_use]
+    pub const fn prerelease_policy(mut self, policy: PrereleasePolicy) -> Self {
+        self.compare.prerelease = policy;
+        self
+    }
+
+    /// Choose which of the versions crates.io reports is taken as the
+    /// latest.
+    ///
+    /// See [`crate::UpdateChecker::version_policy`].
+    #[must_use]
+    pub const fn version_policy(mut self, policy: VersionPolicy) -> Self {
+        self.compare.version = Some(policy);
+        self
+    }
+
+    /// Set a URL to fetch an update message from.
+    ///
+    /// When an update is available, the checker will make a separate HTTP request
+    /// to this URL and include the response as [`DetailedUpdateInfo::message`]. The URL
+    /// should serve plain text.
+    ///
+    /// The fetch is best-effort: if it fails, the update check still succeeds
+    /// with `message` set to `None`. The message is trimmed and truncated to 4KB.
+    #[must_use]
+    pub fn message_url(mut self, url: impl Into<String>) -> Self {
+        self.config.message_url = Some(url.into());
+        self
+    }
+
+    /// Also check the crate this tool was renamed to or replaced by.
+    ///
+    /// See [`crate::UpdateChecker::successor_crate`].
+    #[must_use]
+    pub fn successor_crate(mut self, name: impl Into<String>) -> Self {
+        self.successor_crate = Some(name.into());
+        self
+    }
+
+    /// Accept crate names that don't start with a letter.
+    ///
+    /// See [`crate::UpdateChecker::lenient_name_validation`].
+    #[must_use]
+    pub const fn lenient_name_validation(mut self, lenient: bool) -> Self {
+        self.config.lenient_names = lenient;
+        self
+    }
+
+    /// Set the base URL of the registry to query. Defaults to `https://crates.io`,
+    /// or `TINY_UPDATE_CHECK_BASE_URL` if set.
+    ///
+    /// See [`crate::UpdateChecker::registry_url`].
+    #[must_use]
+    pub fn registry_url(mut self, url: impl Into<String>) -> Self {
+        self.config.registry_url = url.into();
+        self
+    }
+
+    /// Authenticate registry requests with this token, sent verbatim as the
+    /// `Authorization` header.
+    ///
+    /// See [`crate::UpdateChecker::auth_token`].
+    #[must_use]
+    pub fn auth_token(mut self, token: impl Into<String>) -> Self {
+        self.config.auth_token = Some(AuthToken(token.into()));
+        self
+    }
+
+    /// See [`crate::UpdateChecker::basic_auth`].
+    #[must_use]
+    pub fn basic_auth(mut self, user: &str, password: &str) -> Self {
+        self.config.auth_token = Some(AuthToken::basic(user, password));
+        self
+    }
+
+    /// See [`crate::UpdateChecker::registry_name`].
+    #[must_use]
+    pub fn registry_name(mut self, name: impl Into<String>) -> Self {
+        self.config.registry_name = Some(name.into());
+        self
+    }
+
+    /// See [`crate::UpdateChecker::use_cargo_credentials`].
+    #[must_use]
+    pub const fn use_cargo_credentials(mut self, enabled: bool) -> Self {
+        self.config.cargo_credentials = enabled;
+        self
+    }
+
+    /// See [`crate::UpdateChecker::use_netrc`].
+    #[must_use]
+    pub const fn use_netrc(mut self, enabled: bool) -> Self {
+        self.config.netrc = enabled;
+        self
+    }
+
+    /// Registries to try, in order, when the registry is unreachable.
+    /// Defaults to none.
+    ///
+    /// See [`crate::UpdateChecker::fallback_registries`].
+    #[must_use]
+    pub fn fallback_registries(mut self, urls: Vec<String>) -> Self {
+        self.fallback_registries = urls;
+        self
+    }
+
+    /// Cap the time spent on all of a lookup's registries together.
+    /// Defaults to the timeout.
+    ///
+    /// See [`crate::UpdateChecker::total_deadline`].
+    #[must_use]
+    pub const fn total_deadline(mut self, deadline: Duration) -> Self {
+        self.total_deadline = Some(deadline);
+        self
+    }
+
+    /// Retry a lookup that failed transiently up to `retries` more times.
+    /// Defaults to `0`.
+    ///
+    /// See [`crate::UpdateChecker::retries`].
+    #[must_use]
+    pub const fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Look up versions in the sparse index instead of the registry's web
+    /// API. Defaults to `false`.
+    ///
+    /// See [`crate::UpdateChecker::use_sparse_index`].
+    #[must_use]
+    pub const fn use_sparse_index(mut self, enabled: bool) -> Self {
+        self.config.sparse_index = enabled;
+        self
+    }
+
+    /// Set the base URL of the sparse index queried with
+    /// [`use_sparse_index`](Self::use_sparse_index). Defaults to
+    /// `https://index.crates.io`.
+    #[must_use]
+    pub fn sparse_index_url(mut self, url: impl Into<String>) -> Self {
+        self.config.index_url = url.into();
+        self
+    }
+
+    /// Race the registry against the configured release source, taking
+    /// whichever answers successfully first. Defaults to `false`.
+    ///
+    /// See [`crate::UpdateChecker::race_registry`]. Both lookups are polled
+    /// on the calling task, and the slower one is dropped.
+    #[must_use]
+    pub const fn race_registry(mut self, enabled: bool) -> Self {
+        self.race_registry = enabled;
+        self
+    }
+
+    /// Follow Cargo's source replacement of crates.io. Defaults to `false`.
+    ///
+    /// See [`crate::UpdateChecker::respect_cargo_config`].
+    #[must_use]
+    pub const fn respect_cargo_config(mut self, enabled: bool) -> Self {
+        self.config.cargo_config = enabled;
+        self
+    }
+
+    /// Take the latest version from a GitHub repository's latest release
+    /// instead of the registry.
+    ///
+    /// See [`crate::UpdateChecker::github_releases`].
+    #[must_use]
+    pub fn github_releases(mut self, source: GitHubSource) -> Self {
+        self.config.release_source = Some(ReleaseSource::GitHub(source));
+        self
+    }
+
+    /// Take the latest version from a GitLab project's newest release
+    /// instead of the registry.
+    ///
+    /// See [`crate::UpdateChecker::gitlab_releases`].
+    #[must_use]
+    pub fn gitlab_releases(mut self, source: GitLabSource) -> Self {
+        self.config.release_source = Some(ReleaseSource::GitLab(source));
+        self
+    }
+
+    /// Take the latest version from a repository's tags instead of the
+    /// registry.
+    ///
+    /// See [`crate::UpdateChecker::git_tags`].
+    #[must_use]
+    pub fn git_tags(mut self, source: GitTagsSource) -> Self {
+        self.config.release_source = Some(ReleaseSource::Tags(source));
+        self
+    }
+
+    /// Take the latest version from a URL serving just a version string,
+    /// instead of the registry.
+    ///
+    /// See [`crate::UpdateChecker::text_source`].
+    #[must_use]
+    pub fn text_source(mut self, source: TextSource) -> Self {
+        self.config.release_source = Some(ReleaseSource::Text(source));
+        self
+    }
+
+    /// Take the latest version from a value inside a JSON document,
+    /// instead of the registry.
+    ///
+    /// See [`crate::UpdateChecker::json_source`].
+    #[must_use]
+    pub fn json_source(mut self, source: JsonSource) -> Self {
+        self.config.release_source = Some(ReleaseSource::Json(source));
+        self
+    }
+
+    /// Take the latest version from a remote `Cargo.toml` instead of the
+    /// registry.
+    ///
+    /// See [`crate::UpdateChecker::manifest_source`].
+    #[must_use]
+    pub fn manifest_source(mut self, source: ManifestSource) -> Self {
+        self.config.release_source = Some(ReleaseSource::Manifest(source));
+        self
+    }
+
+    /// Take the latest version from a Homebrew formula's stable version
+    /// instead of the registry.
+    ///
+    /// See [`crate::UpdateChecker::homebrew_formula`].
+    #[must_use]
+    pub fn homebrew_formula(mut self, source: HomebrewSource) -> Self {
+        self.config.release_source = Some(ReleaseSource::Homebrew(source));
+        self
+    }
+
+    /// Take the latest version from a cargo-dist `dist-manifest.json`
+    /// instead of the registry.
+    ///
+    /// See [`crate::UpdateChecker::dist_manifest`].
+    #[must_use]
+    pub fn dist_manifest(mut self, source: DistManifestSource) -> Self {
+        self.config.release_source = Some(ReleaseSource::Dist(source));
+        self
+    }
+
+    /// Take the latest version from an npm package's `latest` dist-tag
+    /// instead of the registry.
+    ///
+    /// See [`crate::UpdateChecker::npm_package`].
+    #[must_use]
+    pub fn npm_package(mut self, source: NpmSource) -> Self {
+        self.config.release_source = Some(ReleaseSource::Npm(source));
+        self
+    }
+
+    /// Take the latest version from a package's manifests in the community
+    /// winget repository instead of the registry.
+    ///
+    /// See [`crate::UpdateChecker::winget_package`].
+    #[must_use]
+    pub fn winget_package(mut self, source: WingetSource) -> Self {
+        self.config.release_source = Some(ReleaseSource::Winget(source));
+        self
+    }
+
+    /// Take the latest version from `source` instead of the registry.
+    ///
+    /// The source's [`latest`](VersionSource::latest) runs on tokio's
+    /// blocking thread pool. See [`crate::UpdateChecker::with_source`].
+    #[must_use]
+    pub fn with_source(mut self, source: impl VersionSource + 'static) -> Self {
+        self.config.release_source = Some(ReleaseSource::Custom(CustomSource(Arc::new(source))));
+        self
+    }
+
+    /// Take the latest version from a container image's tags instead of
+    /// the registry.
+    ///
+    /// See [`crate::UpdateChecker::oci_tags`].
+    #[must_use]
+    pub fn oci_tags(mut self, source: OciSource) -> Self {
+        self.config.release_source = Some(ReleaseSource::Oci(source));
+        self
+    }
+
+    /// Allow a plain `http://` registry URL. Defaults to `false`.
+    ///
+    /// See [`crate::UpdateChecker::allow_insecure_http`].
+    #[must_use]
+    pub const fn allow_insecure_http(mut self, allow: bool) -> Self {
+        self.config.allow_insecure_http = allow;
+        self
+    }
+
+    /// Send requests to loopback hosts through the proxy too. Defaults to `false`.
+    ///
+    /// See [`crate::UpdateChecker::proxy_loopback`].
+    #[must_use]
+    pub const fn proxy_loopback(mut self, proxy: bool) -> Self {
+        self.config.proxy_loopback = proxy;
+        self
+    }
+
+    /// See [`crate::UpdateChecker::use_env_proxy`].
+    #[must_use]
+    pub const fn use_env_proxy(mut self, enabled: bool) -> Self {
+        self.config.env_proxy = enabled;
+        self
+    }
+
+    /// Send requests through this proxy instead of any in the environment.
+    ///
+    /// See [`crate::UpdateChecker::proxy`].
+    #[must_use]
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.config.proxy = Some(ProxyUrl(url.into()));
+        self
+    }
+
+    /// Connect to `addr` for requests to `host`, in place of the addresses
+    /// DNS has for it. May be called for several hosts.
+    ///
+    /// See [`crate::UpdateChecker::resolve`].
+    #[must_use]
+    pub fn resolve(mut self, host: &str, addr: std::net::SocketAddr) -> Self {
+        self.config.hosts.insert(host, addr);
+        self
+    }
+
+    /// Trust `cert`, PEM or DER, as a root certificate in place of the
+    /// bundled or system roots. May be called repeatedly.
+    ///
+    /// See [`crate::UpdateChecker::add_root_certificate`].
+    #[must_use]
+    pub fn add_root_certificate(mut self, cert: impl Into<Vec<u8>>) -> Self {
+        self.config.tls.add_root(cert.into());
+        self
+    }
+
+    /// Refuse to connect with a TLS version older than `version`.
+    ///
+    /// See [`crate::UpdateChecker::min_tls_version`].
+    #[must_use]
+    pub const fn min_tls_version(mut self, version: TlsVersion) -> Self {
+        self.config.tls.set_min_version(version);
+        self
+    }
+
+    /// Only talk to servers whose verified certificate chain has one of the
+    /// keys in `pins`. Needs the `rustls` feature too.
+    ///
+    /// See [`crate::UpdateChecker::pin_certificates`].
+    #[must_use]
+    pub fn pin_certificates(mut self, pins: Vec<Spki256>) -> Self {
+        self.config.tls.set_pins(pins);
+        self
+    }
+
+    /// Accept any server certificate, however invalid. Defaults to `false`.
+    /// **This turns off the protection TLS gives**; see the blocking
+    /// checker's documentation before using it.
+    ///
+    /// See [`crate::UpdateChecker::danger_accept_invalid_certs`].
+    #[cfg(feature = "dangerous-config")]
+    #[must_use]
+    pub const fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.config.tls.set_accept_invalid_certs(accept);
+        self
+    }
+
+    /// Trust the root certificates `SSL_CERT_FILE` and `SSL_CERT_DIR` point
+    /// at. Defaults to `true`.
+    ///
+    /// See [`crate::UpdateChecker::use_env_certificates`].
+    #[must_use]
+    pub const fn use_env_certificates(mut self, enabled: bool) -> Self {
+        self.config.env_certificates = enabled;
+        self
+    }
+
+    /// Send every request with `client` instead of one built by the checker.
+    ///
+    /// Use this to share an application's client, and with it its
+    /// connection pool and configuration such as root certificates, default
+    /// headers or a proxy. The checker's [`proxy`](Self::proxy) settings
+    /// and TLS settings such as
+    /// [root certificates](Self::add_root_certificate) then don't apply, and
+    /// its [`timeout`](Self::timeout) is set on each request instead of on
+    /// the client.
+    ///
+    /// ```no_run
+    /// use tiny_update_check::r#async::UpdateChecker;
+    ///
+    /// # async fn example() {
+    /// let client = reqwest::Client::new();
+    /// let checker = UpdateChecker::new("my-crate", "1.0.0").with_client(client.clone());
+    /// let _ = checker.check().await;
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Look up the registry host before each request, failing fast when it
+    /// can't be resolved. Defaults to `false`.
+    ///
+    /// See [`crate::UpdateChecker::fast_offline_probe`]. The lookup runs on
+    /// tokio's blocking thread pool.
+    #[must_use]
+    pub const fn fast_offline_probe(mut self, probe: bool) -> Self {
+        self.offline_probe = probe;
+        self
+    }
+
+    /// Contact the registry even when `CARGO_NET_OFFLINE` is set. Defaults to
+    /// `false`.
+    ///
+    /// See [`crate::UpdateChecker::ignore_cargo_net_offline`].
+    #[must_use]
+    pub const fn ignore_cargo_net_offline(mut self, ignore: bool) -> Self {
+        self.ignore_cargo_net_offline = ignore;
+        self
+    }
+
+    /// Never contact the registry: compare against whatever is cached,
+    /// however old. Defaults to `false`.
+    ///
+    /// See [`crate::UpdateChecker::offline`].
+    #[must_use]
+    pub const fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Save a registry response that fails to parse, for attaching to a bug
+    /// report. Defaults to `false`.
+    ///
+    /// See [`crate::UpdateChecker::capture_failed_bodies`].
     #[must_use]
-    pub fn new(crate_name: impl Into<String>, current_version: impl Into<String>) -> Self {
-        Self {
-            crate_name: crate_name.into(),
-            current_version: current_version.into(),
-            cache_duration: Duration::from_secs(24 * 60 * 60),
-            timeout: Duration::from_secs(5),
-            cache_dir: crate::cache_dir(),
-            include_prerelease: false,
-            message_url: None,
-        }
+    pub const fn capture_failed_bodies(mut self, capture: bool) -> Self {
+        self.config.capture_failed_bodies = capture;
+        self
     }
 
-    /// Set the cache duration. Defaults to 24 hours.
+    /// Set whether checks run. Defaults to [`CheckMode::Auto`].
+    ///
+    /// See [`crate::UpdateChecker::mode`].
     #[must_use]
-    pub const fn cache_duration(mut self, duration: Duration) -> Self {
-        self.cache_duration = duration;
+    pub const fn mode(mut self, mode: CheckMode) -> Self {
+        self.mode = mode;
         self
     }
 
-    /// Set the HTTP request timeout. Defaults to 5 seconds.
+    /// Apply the policy of a repository-local `.tiny-update-check.toml`.
+    /// Defaults to `false`.
+    ///
+    /// See [`crate::UpdateChecker::project_config`].
     #[must_use]
-    pub const fn timeout(mut self, timeout: Duration) -> Self {
-        self.timeout = timeout;
+    pub const fn project_config(mut self, enabled: bool) -> Self {
+        self.project_config = enabled;
         self
     }
 
-    /// Set a custom cache directory. Defaults to system cache directory.
+    /// Set the file name [`project_config`](Self::project_config) looks for.
+    /// Defaults to `.tiny-update-check.toml`.
     #[must_use]
-    pub fn cache_dir(mut self, dir: Option<PathBuf>) -> Self {
-        self.cache_dir = dir;
+    pub fn project_config_file(mut self, name: impl Into<String>) -> Self {
+        self.project_file = Some(name.into());
         self
     }
 
-    /// Include pre-release versions in update checks. Defaults to `false`.
+    /// Set the directory the [`project_config`](Self::project_config) search
+    /// starts from. Defaults to the current directory.
     #[must_use]
-    pub const fn include_prerelease(mut self, include: bool) -> Self {
-        self.include_prerelease = include;
+    pub fn project_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.project_dir = Some(dir.into());
         self
     }
 
-    /// Set a URL to fetch an update message from.
+    /// Record when the running binary was built.
     ///
-    /// When an update is available, the checker will make a separate HTTP request
-    /// to this URL and include the response as [`DetailedUpdateInfo::message`]. The URL
-    /// should serve plain text.
+    /// See [`crate::UpdateChecker::build_timestamp`].
+    #[must_use]
+    pub const fn build_timestamp(mut self, built: SystemTime) -> Self {
+        self.build_timestamp = Some(built);
+        self
+    }
+
+    /// Report binaries older than `max_age` via [`age_notice`](Self::age_notice).
+    #[must_use]
+    pub const fn max_binary_age(mut self, max_age: Duration) -> Self {
+        self.max_binary_age = Some(max_age);
+        self
+    }
+
+    /// How old the running binary is, if older than the configured maximum.
     ///
-    /// The fetch is best-effort: if it fails, the update check still succeeds
-    /// with `message` set to `None`. The message is trimmed and truncated to 4KB.
+    /// Purely local; see [`crate::UpdateChecker::age_notice`].
     #[must_use]
-    pub fn message_url(mut self, url: impl Into<String>) -> Self {
-        self.message_url = Some(url.into());
+    pub fn age_notice(&self) -> Option<Duration> {
+        binary_age_notice(
+            self.build_timestamp?,
+            self.max_binary_age?,
+            SystemTime::now(),
+        )
+    }
+
+    /// Choose what happens when the registry's latest version cannot be parsed.
+    ///
+    /// Defaults to [`UnparsableLatestPolicy::Error`].
+    #[must_use]
+    pub const fn on_unparsable_latest(mut self, policy: UnparsableLatestPolicy) -> Self {
+        self.compare.on_unparsable_latest = policy;
+        self
+    }
+
+    /// Choose how the current and latest versions are compared.
+    ///
+    /// Defaults to [`Comparison::Semver`].
+    #[must_use]
+    pub const fn comparison(mut self, comparison: Comparison) -> Self {
+        self.compare.comparison = comparison;
+        self
+    }
+
+    /// Report post-releases of the current version as updates. Defaults to `false`.
+    ///
+    /// See [`crate::UpdateChecker::treat_post_releases_as_updates`] for how
+    /// versions are normalized.
+    #[must_use]
+    pub const fn treat_post_releases_as_updates(mut self, treat: bool) -> Self {
+        self.compare.post_releases_are_updates = treat;
+        self
+    }
+
+    /// Set which upgrade target to suggest. Defaults to [`SuggestStrategy::Newest`].
+    ///
+    /// See [`crate::UpdateChecker::suggest`].
+    #[must_use]
+    pub const fn suggest(mut self, strategy: SuggestStrategy) -> Self {
+        self.compare.suggest = strategy;
+        self
+    }
+
+    /// Register an async callback invoked with each [`CheckEvent`] during a check.
+    ///
+    /// Events are dispatched fire-and-forget: each returned future is queued
+    /// for a task on the current tokio runtime and never awaited by the
+    /// check, so a slow or panicking callback cannot delay or break it. The
+    /// futures run one at a time, in the order of their events. One still
+    /// running after 5 seconds is dropped so the next can start, and while 32
+    /// are waiting behind a slow one, further events are dropped. Events are
+    /// also dropped when no tokio runtime is available.
+    ///
+    /// ```no_run
+    /// use tiny_update_check::CheckEvent;
+    /// use tiny_update_check::r#async::UpdateChecker;
+    ///
+    /// let (tx, _rx) = tokio::sync::mpsc::unbounded_channel::<CheckEvent>();
+    /// let checker = UpdateChecker::new("my-crate", "1.0.0").on_event_async(move |event| {
+    ///     let tx = tx.clone();
+    ///     Box::pin(async move {
+    ///         let _ = tx.send(event);
+    ///     })
+    /// });
+    /// ```
+    #[must_use]
+    pub fn on_event_async(
+        mut self,
+        callback: impl Fn(CheckEvent) -> EventFuture + Send + Sync + 'static,
+    ) -> Self {
+        self.event_hook = Some(AsyncEventHook::new(callback));
+        self
+    }
+
+    /// Replace the random source used for jitter.
+    ///
+    /// See [`crate::UpdateChecker::rng`]. Defaults to [`crate::XorShiftRng::from_entropy`].
+    #[must_use]
+    pub fn rng(mut self, rng: impl Rng + Send + 'static) -> Self {
+        self.rng = SharedRng::new(rng);
+        self
+    }
+
+    /// Record check statistics in `stats`, which may be shared with other checkers.
+    #[must_use]
+    pub fn stats(mut self, stats: Arc<CheckStats>) -> Self {
+        self.stats = Some(stats);
         self
     }
 
+    /// Cache `version` as the latest, as if fetched at `as_of`, unless the
+    /// cache already holds something fetched at or after that time.
+    ///
+    /// See [`crate::UpdateChecker::seed_cache`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`crate::UpdateChecker::seed_cache`].
+    pub fn seed_cache(&self, version: &str, as_of: SystemTime) -> Result<(), Error> {
+        validate_crate_name(&self.config.crate_name, self.config.lenient_names)?;
+        seed_cache(
+            self.config.cache_dir.as_deref(),
+            self.config.memory.as_deref(),
+            self.config.cache_key.as_deref(),
+            &self.latest_key(),
+            version,
+            as_of,
+            self.durable_cache,
+        )
+    }
+
+    /// Remove the cached latest version, so the next check asks the registry.
+    ///
+    /// See [`crate::UpdateChecker::clear_cache`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`crate::UpdateChecker::clear_cache`].
+    pub fn clear_cache(&self) -> Result<(), Error> {
+        validate_crate_name(&self.config.crate_name, self.config.lenient_names)?;
+        clear_cache(
+            self.config.cache_dir.as_deref(),
+            self.config.memory.as_deref(),
+            self.config.cache_key.as_deref(),
+            &self.latest_key(),
+        )
+    }
+
+    /// The file the latest version is cached in, for pointing users at it
+    /// when checks misbehave.
+    ///
+    /// See [`crate::UpdateChecker::cache_file_path`].
+    #[must_use]
+    pub fn cache_file_path(&self) -> Option<PathBuf> {
+        if self.config.memory.is_some()
+            || validate_crate_name(&self.config.crate_name, self.config.lenient_names).is_err()
+            || validate_cache_key(self.config.cache_key.as_deref()).is_err()
+        {
+            return None;
+        }
+        Some(cache_file(
+            self.config.cache_dir.as_deref()?,
+            &self.latest_key(),
+        ))
+    }
+
+    /// The version that ran before this one, as recorded by the last check.
+    ///
+    /// See [`crate::UpdateChecker::previous_run_version`].
+    #[must_use]
+    pub fn previous_run_version(&self) -> Option<String> {
+        self.previous_run
+            .get_or_init(|| history::read(&self.last_run_file()?))
+            .clone()
+    }
+
+    /// How the running version differs from the one that ran before it, if
+    /// it does.
+    ///
+    /// See [`crate::UpdateChecker::just_upgraded`].
+    #[must_use]
+    pub fn just_upgraded(&self) -> Option<UpgradeInfo> {
+        version_change(&self.previous_run_version()?, &self.current_version)
+    }
+
+    /// The file the running version is recorded in, if it is recorded.
+    fn last_run_file(&self) -> Option<PathBuf> {
+        if self.config.memory.is_some()
+            || validate_crate_name(&self.config.crate_name, self.config.lenient_names).is_err()
+            || validate_cache_key(self.config.cache_key.as_deref()).is_err()
+        {
+            return None;
+        }
+        Some(history::file(
+            self.state_dir.as_deref()?,
+            &self.storage_key(),
+        ))
+    }
+
+    /// Record the running version for the next run, after reading what the
+    /// last one recorded.
+    fn record_run(&self) {
+        let _ = self.previous_run_version();
+        if let Some(path) = self.last_run_file() {
+            history::record(&path, self.current_version.trim(), self.durable_cache);
+        }
+    }
+
+    /// The key this checker's cache and state files are named after.
+    fn storage_key(&self) -> String {
+        self.config
+            .cache_key
+            .clone()
+            .unwrap_or_else(|| default_cache_key(&self.config.crate_name))
+    }
+
+    /// The key the crate's latest version is cached under.
+    fn latest_key(&self) -> String {
+        ReleaseSource::cache_key(self.config.release_source.as_ref(), self.storage_key())
+    }
+
+    fn emit(&self, event: CheckEvent) {
+        if let Some(ref stats) = self.stats {
+            stats.record(&event);
+        }
+        if let Some(ref hook) = self.event_hook {
+            hook.emit(event);
+        }
+    }
+
+    /// Run a check, converting a panic into [`Error::Internal`].
+    async fn catch_panics<T>(
+        &self,
+        check: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        self.record_run();
+        CatchUnwind(Box::pin(check))
+            .await
+            .unwrap_or_else(|message| {
+                self.emit(CheckEvent::Panicked {
+                    message: message.clone(),
+                });
+                Err(Error::Internal(message))
+            })
+    }
+
+    /// Turn a comparison verdict into the reported update, emitting
+    /// [`CheckEvent::NoUpdate`] when there is none.
+    fn conclude(&self, verdict: Verdict) -> Option<DetailedUpdateInfo> {
+        match verdict {
+            Verdict::Update(info) => Some(info),
+            Verdict::NoUpdate(reason) => {
+                self.emit(CheckEvent::NoUpdate { reason });
+                None
+            }
+        }
+    }
+
     /// Check for updates asynchronously.
     ///
     /// Returns `Ok(Some(UpdateInfo))` if a newer version is available,
@@ -113,22 +1131,21 @@ impl UpdateChecker {
     /// In 2.0, `check` and `check_detailed` will likely be combined into a
     /// single method returning `DetailedUpdateInfo` (with `UpdateInfo` removed).
     pub async fn check(&self) -> Result<Option<UpdateInfo>, Error> {
-        #[cfg(feature = "do-not-track")]
-        if do_not_track_enabled() {
+        self.catch_panics(self.check_unguarded()).await
+    }
+
+    async fn check_unguarded(&self) -> Result<Option<UpdateInfo>, Error> {
+        if let Some(reason) = skip_reason(self.mode) {
+            self.emit(CheckEvent::NoUpdate { reason });
             return Ok(None);
         }
 
-        validate_crate_name(&self.crate_name)?;
+        self.config.validate(Backend::Reqwest)?;
 
-        let client = reqwest::Client::builder()
-            .timeout(self.timeout)
-            .user_agent(USER_AGENT)
-            .build()
-            .map_err(|e| Error::HttpError(e.to_string()))?;
+        let client = self.build_client(&self.config.lookup(&self.config.crate_name).0)?;
 
-        let (latest, _) = self.get_latest_version(&client).await?;
-
-        compare_versions(&self.current_version, latest, self.include_prerelease)
+        let (verdict, _) = self.latest_verdict(&client).await?;
+        Ok(self.conclude(verdict).map(UpdateInfo::from))
     }
 
     /// Check for updates asynchronously with extended metadata.
@@ -142,105 +1159,802 @@ impl UpdateChecker {
     /// In 2.0, `check` and `check_detailed` will likely be combined into a
     /// single method returning `DetailedUpdateInfo` (with `UpdateInfo` removed).
     pub async fn check_detailed(&self) -> Result<Option<DetailedUpdateInfo>, Error> {
-        #[cfg(feature = "do-not-track")]
-        if do_not_track_enabled() {
-            return Ok(None);
+        self.catch_panics(self.check_detailed_unguarded()).await
+    }
+
+    async fn check_detailed_unguarded(&self) -> Result<Option<DetailedUpdateInfo>, Error> {
+        let verdict = self.detailed_verdict().await?;
+        Ok(self.conclude(verdict))
+    }
+
+    /// Check for updates asynchronously, reporting why none is available or
+    /// the check failed.
+    ///
+    /// See [`crate::UpdateChecker::check_outcome`].
+    pub async fn check_outcome(&self) -> CheckOutcome {
+        let verdict = self
+            .catch_panics(async {
+                let verdict = self.detailed_verdict().await?;
+                if let Verdict::NoUpdate(ref reason) = verdict {
+                    self.emit(CheckEvent::NoUpdate {
+                        reason: reason.clone(),
+                    });
+                }
+                Ok(verdict)
+            })
+            .await;
+        CheckOutcome::from_verdict(verdict)
+    }
+
+    /// List the registry requests [`check`](Self::check) would send on a
+    /// cache miss, in order, without sending anything.
+    ///
+    /// See [`crate::UpdateChecker::dry_run`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the configuration errors a check would.
+    pub fn dry_run(&self) -> Result<Vec<PlannedRequest>, Error> {
+        self.config.validate(Backend::Reqwest)?;
+        if let Some(ref successor) = self.successor_crate {
+            validate_crate_name(successor, self.config.lenient_names)?;
+        }
+        // reqwest adds `Accept: */*` unless another `Accept` is set, and
+        // `Accept-Encoding: gzip` with the `gzip` feature.
+        self.successor_crate
+            .iter()
+            .chain([&self.config.crate_name])
+            .map(|name| self.config.lookup(name))
+            .filter(|(_, format)| *format != ResponseFormat::GitRefs)
+            .map(|(url, format)| {
+                let token = self.config.token(&url, format)?;
+                let headers = format.headers(self.config.release_source.as_ref(), token.as_ref());
+                Ok(PlannedRequest::get(
+                    url,
+                    &self.config.user_agent(),
+                    headers,
+                    &self.config.headers,
+                    true,
+                    cfg!(feature = "gzip"),
+                ))
+            })
+            .collect()
+    }
+
+    /// Run a detailed check, without emitting [`CheckEvent::NoUpdate`].
+    async fn detailed_verdict(&self) -> Result<Verdict, Error> {
+        if let Some(reason) = skip_reason(self.mode) {
+            return Ok(Verdict::NoUpdate(reason));
         }
 
-        validate_crate_name(&self.crate_name)?;
+        self.config.validate(Backend::Reqwest)?;
 
-        let client = reqwest::Client::builder()
-            .timeout(self.timeout)
-            .user_agent(USER_AGENT)
-            .build()
-            .map_err(|e| Error::HttpError(e.to_string()))?;
+        let client = self.build_client(&self.config.lookup(&self.config.crate_name).0)?;
 
         #[cfg(feature = "response-body")]
-        let (latest, response_body) = self.get_latest_version(&client).await?;
+        let (verdict, response_body) = self.latest_verdict(&client).await?;
         #[cfg(not(feature = "response-body"))]
-        let (latest, _) = self.get_latest_version(&client).await?;
+        let (verdict, _) = self.latest_verdict(&client).await?;
 
-        let update = compare_versions(&self.current_version, latest, self.include_prerelease)?;
+        let Verdict::Update(mut detailed) = verdict else {
+            return Ok(verdict);
+        };
+        if let Some(url) = self
+            .config
+            .message_url
+            .as_ref()
+            .filter(|_| !self.net_offline())
+        {
+            detailed.message = self.fetch_message(url).await;
+        }
+        detailed.age_notice = self.age_notice();
+        #[cfg(feature = "response-body")]
+        {
+            detailed.response_body = response_body;
+        }
+        Ok(Verdict::Update(detailed))
+    }
 
-        match update {
-            Some(info) => {
-                let mut detailed = DetailedUpdateInfo::from(info);
-                if let Some(ref url) = self.message_url {
-                    detailed.message = Self::fetch_message(&client, url).await;
-                }
-                #[cfg(feature = "response-body")]
-                {
-                    detailed.response_body = response_body;
+    /// A client with the configured timeout for requests to `url`, built
+    /// once per configuration and reused like the blocking checker's agents,
+    /// or else the one given with [`with_client`](Self::with_client).
+    fn build_client(&self, url: &str) -> Result<reqwest::Client, Error> {
+        if let Some(ref client) = self.client {
+            return Ok(client.clone());
+        }
+        let proxy = self.config.proxy_for(url);
+        let tls = self.config.tls_settings(Backend::Reqwest)?;
+        let build = || {
+            // reqwest reads the environment differently, so it is given the
+            // proxy chosen for the blocking transport instead. Credentials
+            // in the proxy's URL are sent as its basic auth.
+            let mut builder = reqwest::Client::builder()
+                .timeout(self.timeout)
+                .user_agent(USER_AGENT)
+                .no_proxy();
+            if let Some(proxy) = proxy
+                .as_deref()
+                .and_then(|proxy| reqwest::Proxy::all(proxy).ok())
+            {
+                builder = builder.proxy(proxy);
+            }
+            for (host, addr) in self.config.hosts.iter() {
+                builder = builder.resolve(host, addr);
+            }
+            if tls.has_roots() {
+                builder = builder.tls_certs_only(
+                    tls.root_ders()
+                        .iter()
+                        .filter_map(|der| reqwest::Certificate::from_der(der).ok()),
+                );
+            }
+            if let Some(version) = tls.min_version() {
+                builder = builder.tls_version_min(match version {
+                    TlsVersion::Tls1_2 => reqwest::tls::Version::TLS_1_2,
+                    TlsVersion::Tls1_3 => reqwest::tls::Version::TLS_1_3,
+                });
+            }
+            if tls.accepts_invalid_certs() {
+                builder = builder.tls_danger_accept_invalid_certs(true);
+            }
+            #[cfg(feature = "rustls")]
+            if !tls.pins().is_empty() {
+                // The pinning setup has the roots and minimum version too.
+                builder = builder.tls_backend_preconfigured(crate::pin::client_config(&tls)?);
+            }
+            builder.build().map_err(|e| http_error(ErrorKind::Http, e))
+        };
+        #[cfg(feature = "shared-pool")]
+        let clients = &*crate::pool::REQWEST;
+        #[cfg(not(feature = "shared-pool"))]
+        let clients = &*self.clients;
+        clients.get(
+            crate::pool::Key::new(
+                self.timeout,
+                self.config.bypasses_proxy(url),
+                proxy.clone(),
+                self.config.hosts.clone(),
+                tls.clone(),
+            ),
+            build,
+        )
+    }
+
+    /// Look up the latest version and decide whether it is an update, under
+    /// the [project policy](Self::project_config) if there is one.
+    async fn latest_verdict(
+        &self,
+        client: &reqwest::Client,
+    ) -> Result<(Verdict, Option<String>), Error> {
+        let project = if self.project_config {
+            project::load(
+                self.config.project_dir.as_deref(),
+                self.project_file.as_deref(),
+            )?
+        } else {
+            None
+        };
+        let Some(project) = project else {
+            return self.lookup_verdict(client).await;
+        };
+        if let Some(reason) = project.skip_reason() {
+            return Ok((Verdict::NoUpdate(reason), None));
+        }
+        let (verdict, response_body) = self.lookup_verdict(client).await?;
+        Ok((project.restrict(verdict), response_body))
+    }
+
+    /// Look up the latest version and decide whether it is an update,
+    /// preferring a newer successor crate.
+    async fn lookup_verdict(
+        &self,
+        client: &reqwest::Client,
+    ) -> Result<(Verdict, Option<String>), Error> {
+        if let Some(ref successor) = self.successor_crate {
+            validate_crate_name(successor, self.config.lenient_names)?;
+            if let Ok(Ok(latest)) = self
+                .get_latest_version(client, successor, &default_cache_key(successor))
+                .await
+            {
+                if let Some(info) = successor_update(
+                    &self.current_version,
+                    latest.version,
+                    latest.updated_at,
+                    successor,
+                    self.compare,
+                ) {
+                    return Ok((Verdict::Update(info), latest.response_body));
                 }
-                Ok(Some(detailed))
             }
-            None => Ok(None),
         }
+
+        let latest = match self
+            .get_latest_version(client, &self.config.crate_name, &self.latest_key())
+            .await?
+        {
+            Ok(latest) => latest,
+            Err(reason) => return Ok((Verdict::NoUpdate(reason), None)),
+        };
+        let options =
+            ReleaseSource::compare_options(self.config.release_source.as_ref(), self.compare);
+        let verdict = latest_verdict(&self.current_version, &latest, options)?;
+        Ok((verdict, latest.response_body))
+    }
+
+    /// Whether checks must stay off the network, because they are
+    /// [offline](Self::offline) or `CARGO_NET_OFFLINE` is set.
+    fn net_offline(&self) -> bool {
+        self.offline
+            || tiny_update_check_offline()
+            || (!self.ignore_cargo_net_offline && cargo_net_offline())
+    }
+
+    /// Answer from the cache under `key`, however old, without asking the
+    /// registry, or else conclude that there's no network to ask.
+    ///
+    /// A `stale` answer is marked so and reported with
+    /// [`CheckEvent::StaleCacheHit`]; others with [`CheckEvent::CacheHit`].
+    fn answer_offline(
+        &self,
+        path: Option<&Path>,
+        key: &str,
+        stale: bool,
+    ) -> Result<Latest, NoUpdateReason> {
+        let mut cached = cached_however_old(
+            self.config.memory.as_deref(),
+            path,
+            key,
+            self.compare.suggest,
+        )
+        .ok_or(NoUpdateReason::Offline)?;
+        cached.offline = true;
+        let version = cached.version.clone();
+        if stale {
+            cached.stale = true;
+            self.emit(CheckEvent::StaleCacheHit {
+                version,
+                status: None,
+            });
+        } else {
+            self.emit(CheckEvent::CacheHit { version });
+        }
+        Ok(cached)
     }
 
-    /// Get the latest version, using cache if available and fresh.
+    /// Get the latest version of a crate, using cache if available and fresh.
     async fn get_latest_version(
         &self,
         client: &reqwest::Client,
-    ) -> Result<(String, Option<String>), Error> {
-        use std::fs;
+        crate_name: &str,
+        key: &str,
+    ) -> Result<Result<Latest, NoUpdateReason>, Error> {
+        let path = self.config.cache_dir.as_ref().map(|d| cache_file(d, key));
 
-        let path = self
-            .cache_dir
-            .as_ref()
-            .map(|d| d.join(format!("{}-update-check", self.crate_name)));
+        // Answer from the cache, however old, when told to stay off the
+        // network.
+        if self.net_offline() {
+            return Ok(self.answer_offline(path.as_deref(), key, false));
+        }
 
         // Check cache first
-        if self.cache_duration > Duration::ZERO {
-            if let Some(ref path) = path {
-                if let Some(cached) = read_cache(path, self.cache_duration) {
-                    return Ok((cached, None));
-                }
+        if self.cache_duration > Duration::ZERO && self.mode != CheckMode::Forced {
+            let max_age = || {
+                let configured = jittered(self.cache_duration, self.cache_jitter, &self.rng);
+                self.honor_cache_control
+                    .then(|| cached_max_age(self.config.memory.as_deref(), path.as_deref(), key))
+                    .flatten()
+                    .map_or(configured, |server| server.min(configured))
+            };
+            let cached = match (&self.config.memory, &path) {
+                (Some(memory), _) => memory.read(key, max_age(), self.compare.suggest),
+                (None, Some(path)) => read_cached_latest(path, max_age(), self.compare.suggest),
+                (None, None) => None,
+            };
+            if let Some(cached) = cached {
+                self.emit(CheckEvent::CacheHit {
+                    version: cached.version.clone(),
+                });
+                return Ok(Ok(cached));
+            }
+        }
+
+        // Answer from the cache, however old, while an earlier rate limit
+        // lasts.
+        let disk = path.as_deref().filter(|_| self.config.memory.is_none());
+        if let Some(waiting) = disk.and_then(|path| rate_limited(path, self.compare.suggest)) {
+            if let Ok(ref cached) = waiting {
+                self.emit(CheckEvent::CacheHit {
+                    version: cached.version.clone(),
+                });
             }
+            return Ok(waiting);
+        }
+
+        let allowed = self.config.memory.as_ref().map_or_else(
+            || request_allowed(self.state_dir.as_deref(), key, self.daily_request_budget),
+            |memory| memory.spend(key, self.daily_request_budget),
+        );
+        if !allowed {
+            return Ok(Err(NoUpdateReason::BudgetExhausted));
         }
 
-        // Fetch from crates.io
-        let (latest, response_body) = self.fetch_latest_version(client).await?;
+        // Fetch from the registry, or race it against the release source
+        let latest = if self.race_registry
+            && self.config.release_source.is_some()
+            && crate_name == self.config.crate_name
+        {
+            self.race(client, crate_name, key).await
+        } else {
+            self.fetch(client, crate_name, key).await
+        }
+        .inspect_err(|e| note_rate_limit(disk, e));
+        let latest = match latest {
+            Ok(latest) => latest,
+            // With the offline probe on, a host that can't be resolved means
+            // there's no network to wait for.
+            Err(e) if self.offline_probe && e.kind() == ErrorKind::Dns => {
+                return Ok(self.answer_offline(path.as_deref(), key, true));
+            }
+            Err(e) if self.stale_on_server_error => {
+                let status = e.status();
+                let stale = stale_cached(
+                    &e,
+                    self.config.memory.as_deref(),
+                    path.as_deref(),
+                    key,
+                    self.compare.suggest,
+                )
+                .ok_or(e)?;
+                self.emit(CheckEvent::StaleCacheHit {
+                    version: stale.version.clone(),
+                    status,
+                });
+                return Ok(Ok(stale));
+            }
+            Err(e) => return Err(e),
+        };
 
         // Update cache
-        if let Some(ref path) = path {
-            let _ = fs::write(path, &latest);
+        match (&self.config.memory, &path) {
+            (Some(memory), _) => memory.write(key, &latest),
+            (None, Some(path)) => write_cache(path, &latest, self.durable_cache),
+            (None, None) => {}
         }
 
-        Ok((latest, response_body))
+        Ok(Ok(latest))
     }
 
-    /// Fetch the latest version from crates.io asynchronously.
-    async fn fetch_latest_version(
+    /// Fetch the latest version of `crate_name` from where
+    /// [`lookup`](Self::lookup) points, trying the fallback registries in
+    /// turn for the web API.
+    async fn fetch(
         &self,
         client: &reqwest::Client,
-    ) -> Result<(String, Option<String>), Error> {
-        let url = format!("https://crates.io/api/v1/crates/{}", self.crate_name);
+        crate_name: &str,
+        key: &str,
+    ) -> Result<Latest, Error> {
+        let (url, format) = self.config.lookup(crate_name);
+        if format == ResponseFormat::Api {
+            let registries =
+                registry_attempts(&self.config.registry_url, &self.fallback_registries);
+            for registry in &registries {
+                validate_base_url(registry)?;
+                validate_registry_url(registry, self.config.allow_insecure_http)?;
+            }
+            let total = self.total_deadline.unwrap_or(self.timeout);
+            let deadline = Instant::now() + total;
+            let mut failures = Vec::new();
+            loop {
+                let untried = registries.get(failures.len()..).unwrap_or_default();
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let Some(registry) = untried.first().filter(|_| !remaining.is_zero()) else {
+                    return Err(registries_unavailable(&failures, untried));
+                };
+                let url = crate_api_url(registry, crate_name);
+                // The first attempt gets the whole deadline, so that an
+                // unchanged timeout keeps sharing its client.
+                let timeout = self.timeout.min(if failures.is_empty() {
+                    total
+                } else {
+                    remaining
+                });
+                let result = if failures.is_empty() && timeout == self.timeout {
+                    self.fetch_retrying(client, &url, format, key, deadline)
+                        .await
+                } else {
+                    // Later registries may differ in needing the proxy, and
+                    // get only the time left.
+                    let attempt = self.clone().timeout(timeout);
+                    match attempt.build_client(&url) {
+                        Ok(client) => {
+                            attempt
+                                .fetch_retrying(&client, &url, format, key, deadline)
+                                .await
+                        }
+                        Err(e) => Err(e),
+                    }
+                };
+                match result {
+                    Ok(mut latest) => {
+                        check_crate_name(&latest, crate_name)?;
+                        latest.registry_url = Some(registry.clone());
+                        return Ok(latest);
+                    }
+                    Err(e) if registries.len() > 1 && is_unavailable(&e) => {
+                        failures.push((registry.clone(), e));
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        } else {
+            validate_registry_url(&url, self.config.allow_insecure_http)?;
+            let deadline = Instant::now() + self.total_deadline.unwrap_or(self.timeout);
+            let latest = self
+                .fetch_retrying(client, &url, format, key, deadline)
+                .await?;
+            check_crate_name(&latest, crate_name)?;
+            Ok(latest)
+        }
+    }
 
-        let body = client
-            .get(&url)
-            .send()
+    /// [Fetch](Self::fetch_from) `url`, retrying a transient failure up to
+    /// [`retries`](Self::retries) times while `deadline` allows.
+    async fn fetch_retrying(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        format: ResponseFormat,
+        key: &str,
+        deadline: Instant,
+    ) -> Result<Latest, Error> {
+        let mut attempts = 1;
+        let mut result = self.fetch_from(client, url.to_string(), format, key).await;
+        loop {
+            let e = match result {
+                Err(e) if attempts <= self.retries && is_unavailable(&e) => e,
+                result => return result.map_err(|e| after_attempts(e, attempts)),
+            };
+            let delay = retry_delay(attempts, &self.rng);
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if delay >= remaining {
+                return Err(after_attempts(e, attempts));
+            }
+            tokio::time::sleep(delay).await;
+            attempts += 1;
+            // Later attempts get only the time left.
+            let timeout = self.timeout.min(remaining.saturating_sub(delay));
+            result = if timeout == self.timeout {
+                self.fetch_from(client, url.to_string(), format, key).await
+            } else {
+                let attempt = self.clone().timeout(timeout);
+                match attempt.build_client(url) {
+                    Ok(client) => {
+                        attempt
+                            .fetch_from(&client, url.to_string(), format, key)
+                            .await
+                    }
+                    Err(e) => Err(e),
+                }
+            };
+        }
+    }
+
+    /// Fetch from the release source and the registry at once, taking the
+    /// first successful answer.
+    ///
+    /// Both lookups are polled together; the slower one is dropped once the
+    /// other succeeds. A failure only counts once both have failed.
+    async fn race(
+        &self,
+        client: &reqwest::Client,
+        crate_name: &str,
+        key: &str,
+    ) -> Result<Latest, Error> {
+        let mut checker = self.clone();
+        checker.config.release_source = None;
+        let (release_url, _) = self.config.lookup(crate_name);
+        let (registry_url, _) = checker.config.lookup(crate_name);
+        // The registry may differ from the release source in needing the
+        // proxy.
+        let registry_client = checker.build_client(&registry_url)?;
+        let mut release = std::pin::pin!(self.fetch(client, crate_name, key));
+        let mut registry = std::pin::pin!(checker.fetch(&registry_client, crate_name, key));
+        let mut release_failed = None;
+        let mut registry_failed = None;
+        let (mut latest, rank) = std::future::poll_fn(|cx| {
+            if release_failed.is_none() {
+                match release.as_mut().poll(cx) {
+                    Poll::Ready(Ok(latest)) => return Poll::Ready(Ok((latest, 0))),
+                    Poll::Ready(Err(e)) => release_failed = Some(e),
+                    Poll::Pending => {}
+                }
+            }
+            if registry_failed.is_none() {
+                match registry.as_mut().poll(cx) {
+                    Poll::Ready(Ok(latest)) => return Poll::Ready(Ok((latest, 1))),
+                    Poll::Ready(Err(e)) => registry_failed = Some(e),
+                    Poll::Pending => {}
+                }
+            }
+            match (release_failed.take(), registry_failed.take()) {
+                (Some(release), Some(registry)) => Poll::Ready(Err(race_lost(vec![
+                    (0, release_url.clone(), release),
+                    (1, registry_url.clone(), registry),
+                ]))),
+                (release, registry) => {
+                    release_failed = release;
+                    registry_failed = registry;
+                    Poll::Pending
+                }
+            }
+        })
+        .await?;
+        latest.race_winner = Some(RaceWinner::from_rank(rank));
+        Ok(latest)
+    }
+
+    /// Fetch the latest version from `url`, emitting fetch events around it.
+    async fn fetch_from(
+        &self,
+        client: &reqwest::Client,
+        url: String,
+        format: ResponseFormat,
+        key: &str,
+    ) -> Result<Latest, Error> {
+        self.emit(CheckEvent::FetchStarted { url: url.clone() });
+        let started = Instant::now();
+        let limit = body_limit(self.max_memory);
+        let probed = if format == ResponseFormat::Custom {
+            Ok(())
+        } else {
+            self.probe(&url).await
+        };
+        let result = match probed {
+            Ok(()) if format == ResponseFormat::Custom => self.fetch_custom().await,
+            Ok(()) => {
+                let source = self.config.release_source.as_ref();
+                let capture = self.config.capture_target(key);
+                self.fetch_direct(client, &url, limit, format, source, capture)
+                    .await
+            }
+            Err(e) => Err(e),
+        }
+        .map_err(|e| match self.config.release_source {
+            Some(ref source) if format.is_release() => source.explain(e),
+            _ => e,
+        });
+        self.emit(CheckEvent::FetchCompleted {
+            url,
+            started,
+            elapsed: started.elapsed(),
+            success: result.is_ok(),
+        });
+        result
+    }
+
+    /// Fetch the latest version from `url`, answered in `format` for the
+    /// release `source` if any, saving the response to `capture` if it
+    /// fails to parse.
+    async fn fetch_direct(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        limit: usize,
+        format: ResponseFormat,
+        source: Option<&ReleaseSource>,
+        capture: Option<(&Path, &str)>,
+    ) -> Result<Latest, Error> {
+        match format {
+            ResponseFormat::GitRefs => {
+                let bytes = self.ls_remote(url, limit).await?;
+                parse_fetched(&bytes, format, source, self.compare, capture)
+            }
+            ResponseFormat::OciTags => self.list_oci_tags(client, url, limit).await,
+            _ => {
+                self.fetch_latest_version(client, url, limit, format, source, capture)
+                    .await
+            }
+        }
+    }
+
+    /// Fetch the latest version from the configured [`VersionSource`], on
+    /// tokio's blocking thread pool, sending its requests with this
+    /// checker's client.
+    async fn fetch_custom(&self) -> Result<Latest, Error> {
+        let Some(ReleaseSource::Custom(ref source)) = self.config.release_source else {
+            return Err(Error::Internal("no custom version source".to_string()));
+        };
+        let (source, checker) = (source.clone(), self.clone());
+        let handle = tokio::runtime::Handle::current();
+        tokio::task::spawn_blocking(move || {
+            SourceContext::pooled(&checker, handle).answer_from(source.0.as_ref())
+        })
+        .await
+        .map_err(|e| Error::Internal(e.to_string()))?
+    }
+
+    /// Fetch the latest version from `url` for a [`SourceContext`],
+    /// answered in `format` for the release `source` if any.
+    pub(crate) async fn fetch_pooled(
+        &self,
+        url: &str,
+        format: ResponseFormat,
+        source: Option<&ReleaseSource>,
+    ) -> Result<Latest, Error> {
+        validate_registry_url(url, self.config.allow_insecure_http)?;
+        self.probe(url).await?;
+        let client = self.build_client(url)?;
+        let limit = body_limit(self.max_memory);
+        self.fetch_direct(&client, url, limit, format, source, None)
+            .await
+    }
+
+    /// The body of a successful `GET` of `url`, for a [`SourceContext`].
+    pub(crate) async fn get(&self, url: &str) -> Result<Vec<u8>, Error> {
+        validate_registry_url(url, self.config.allow_insecure_http)?;
+        self.probe(url).await?;
+        let client = self.build_client(url)?;
+        self.send(&client, url, &[], body_limit(self.max_memory))
+            .await?
+            .into_body()
+    }
+
+    /// Run the [offline probe](Self::fast_offline_probe) for `url`, if enabled.
+    async fn probe(&self, url: &str) -> Result<(), Error> {
+        if !self.offline_probe || self.config.proxy.is_some() || self.config.hosts.overrides(url) {
+            return Ok(());
+        }
+        let url = url.to_string();
+        tokio::task::spawn_blocking(move || probe::resolve(&url, probe::BUDGET))
             .await
-            .map_err(|e| Error::HttpError(e.to_string()))?
-            .text()
+            .unwrap_or(Ok(()))
+    }
+
+    /// List the tags of the remote at `url` with `git ls-remote`, on tokio's
+    /// blocking thread pool.
+    async fn ls_remote(&self, url: &str, limit: usize) -> Result<Vec<u8>, Error> {
+        let url = url.to_string();
+        let timeout = self.timeout;
+        let proxy = self.config.proxy_for(&url);
+        tokio::task::spawn_blocking(move || git::ls_remote(&url, timeout, proxy.as_deref(), limit))
             .await
-            .map_err(|e| Error::HttpError(e.to_string()))?;
+            .map_err(|e| Error::Internal(e.to_string()))?
+    }
 
-        let version = extract_newest_version(&body)?;
+    /// List an image's tags from an OCI registry, starting at `url`, and
+    /// take the highest version among them.
+    async fn list_oci_tags(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        limit: usize,
+    ) -> Result<Latest, Error> {
+        let mut listing = oci::Listing::new(url);
+        while let Some((url, headers)) = listing.next_request() {
+            validate_registry_url(&url, self.config.allow_insecure_http)?;
+            listing.handle(self.send(client, &url, &headers, limit).await?)?;
+        }
+        listing.finish(self.compare.suggest)
+    }
 
-        #[cfg(feature = "response-body")]
-        return Ok((version, Some(body)));
+    /// Fetch the latest version from the registry or the release `source`
+    /// asynchronously, saving the response to `capture` if it fails to parse.
+    async fn fetch_latest_version(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        limit: usize,
+        format: ResponseFormat,
+        source: Option<&ReleaseSource>,
+        capture: Option<(&Path, &str)>,
+    ) -> Result<Latest, Error> {
+        let token = self.config.token(url, format)?;
+        let headers = format.headers(source, token.as_ref());
+        let response = self.send(client, url, &headers, limit).await?;
+        let max_age = response.max_age();
+        let bytes = response
+            .into_body()
+            .map_err(|e| refused_credentials(e, url, format))?;
+        let mut latest = parse_fetched(&bytes, format, source, self.compare, capture)?;
+        latest.max_age = max_age;
+        Ok(latest)
+    }
 
-        #[cfg(not(feature = "response-body"))]
-        Ok((version, None))
+    /// Send a `GET` of `url` with the `User-Agent` and `headers`, reading
+    /// the body, up to `limit` bytes, only if it succeeded.
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        headers: &[(&'static str, String)],
+        limit: usize,
+    ) -> Result<Response, Error> {
+        let mut response = self
+            .config
+            .request_headers(headers)
+            .iter()
+            .fold(
+                client.get(url).timeout(self.timeout),
+                |request, (name, value)| request.header(name, value),
+            )
+            .send()
+            .await
+            .map_err(reqwest_error)?;
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect();
+        let mut body = Vec::new();
+        if (200..300).contains(&status) {
+            if response
+                .content_length()
+                .is_some_and(|length| length > limit as u64)
+            {
+                return Err(too_large_error(limit));
+            }
+
+            // Read chunk by chunk so an oversized body is never held in full.
+            while let Some(chunk) = response.chunk().await.map_err(reqwest_error)? {
+                if body.len() + chunk.len() > limit {
+                    return Err(too_large_error(limit));
+                }
+                body.extend_from_slice(&chunk);
+            }
+        }
+        Ok(Response {
+            status,
+            headers,
+            body,
+        })
     }
 
     /// Fetch a plain text message from the configured URL.
     ///
-    /// Best-effort: returns `None` on any failure.
-    async fn fetch_message(client: &reqwest::Client, url: &str) -> Option<String> {
-        let body = client.get(url).send().await.ok()?.text().await.ok()?;
-        truncate_message(&body)
+    /// Best-effort: returns `None` on any failure. Only the first
+    /// [`MESSAGE_READ_LIMIT`] bytes are read.
+    async fn fetch_message(&self, url: &str) -> Option<String> {
+        let client = self.build_client(url).ok()?;
+        let mut response = self
+            .config
+            .request_headers(&[])
+            .iter()
+            .fold(
+                client.get(url).timeout(self.timeout),
+                |request, (name, value)| request.header(name, value),
+            )
+            .send()
+            .await
+            .ok()?;
+        let mut body = Vec::new();
+        while body.len() < MESSAGE_READ_LIMIT {
+            let Some(chunk) = response.chunk().await.ok()? else {
+                break;
+            };
+            body.extend_from_slice(&chunk);
+        }
+        body.truncate(MESSAGE_READ_LIMIT);
+        message_text(&body)
+    }
+}
+
+/// Convert a reqwest error, keeping its [`ErrorKind`].
+fn reqwest_error(e: reqwest::Error) -> Error {
+    #[cfg(feature = "rustls")]
+    if let Some(mismatch) = crate::pin::mismatch(&e) {
+        return mismatch;
+    }
+    match e.status() {
+        Some(status) => status_error(status.as_u16()),
+        None if e.is_timeout() => http_error(ErrorKind::Timeout, e),
+        None if e.is_decode() => http_error(
+            ErrorKind::Http,
+            format!("the response's Content-Encoding couldn't be decoded: {e}"),
+        ),
+        None => http_error(ErrorKind::Http, e),
     }
 }
 
@@ -0,0 +1,169 @@
+//! Waiting out a registry's rate limit across runs.
+//!
+//! When a lookup is rate limited, the time the server allows the next
+//! request is written next to the crate's cache file, as Unix seconds in a
+//! `.retry-after` file. Until then, checks answer from the cache, however
+//! stale, instead of asking again.
+//!
+//! The time comes from the response's `Retry-After` header, either a number
+//! of seconds or an HTTP date such as `Sun, 06 Nov 1994 08:49:37 GMT`, or
+//! from GitHub's `x-ratelimit-reset`. Without either, the wait is
+//! [`DEFAULT_WAIT`], and no wait is longer than [`MAX_WAIT`].
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The wait after a rate-limited response that doesn't say how long.
+pub const DEFAULT_WAIT: Duration = Duration::from_secs(60 * 60);
+
+/// The longest wait honored, whatever the server asks for.
+pub const MAX_WAIT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The time a `Retry-After` header `value` allows the next request, as of
+/// `now`.
+pub fn retry_after(value: &str, now: SystemTime) -> Option<SystemTime> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return now.checked_add(Duration::from_secs(seconds));
+    }
+    http_date(value)
+}
+
+/// Parse an HTTP date in its preferred form, `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn http_date(value: &str) -> Option<SystemTime> {
+    let (_, date) = value.split_once(", ")?;
+    let mut fields = date.split(' ');
+    let day: u64 = fields.next()?.parse().ok()?;
+    let month = fields.next()?;
+    let year: u64 = fields.next()?.parse().ok()?;
+    let time = fields.next()?;
+    if fields.next()? != "GMT" || fields.next().is_some() {
+        return None;
+    }
+    let month = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ]
+    .iter()
+    .position(|name| *name == month)?;
+    let mut clock = time.split(':').map(|field| field.parse::<u64>().ok());
+    let (Some(Some(hour)), Some(Some(minute)), Some(Some(second)), None) =
+        (clock.next(), clock.next(), clock.next(), clock.next())
+    else {
+        return None;
+    };
+    if !(1..=31).contains(&day) || year < 1970 || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let days = days_since_epoch(year, month as u64 + 1, day);
+    UNIX_EPOCH.checked_add(Duration::from_secs(
+        days * 86_400 + hour * 3600 + minute * 60 + second,
+    ))
+}
+
+/// Days from 1970-01-01 to the given date in the proleptic Gregorian
+/// calendar, for dates from 1970 on.
+const fn days_since_epoch(year: u64, month: u64, day: u64) -> u64 {
+    // Count from March, so the leap day ends the year.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The file recording when a rate limit on the crate cached in
+/// `cache_file` ends.
+pub fn backoff_file(cache_file: &Path) -> PathBuf {
+    let mut name = cache_file.as_os_str().to_owned();
+    name.push(".retry-after");
+    PathBuf::from(name)
+}
+
+/// Record that no request should be made before `until`, or for
+/// [`DEFAULT_WAIT`] if the server didn't say, ignoring failures.
+pub fn record(cache_file: &Path, until: Option<SystemTime>, now: SystemTime) {
+    let until = until.unwrap_or(now + DEFAULT_WAIT).min(now + MAX_WAIT);
+    let Ok(seconds) = until.duration_since(UNIX_EPOCH) else {
+        return;
+    };
+    if let Some(dir) = cache_file.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    // Round up, so the wait is never cut short.
+    let seconds = seconds.as_secs() + u64::from(seconds.subsec_nanos() > 0);
+    let _ = fs::write(backoff_file(cache_file), seconds.to_string());
+}
+
+/// When the recorded rate limit ends, if it hasn't by `now`.
+///
+/// A wait longer than [`MAX_WAIT`], as after the clock is set back, is
+/// shortened to it.
+pub fn until(cache_file: &Path, now: SystemTime) -> Option<SystemTime> {
+    let seconds = fs::read_to_string(backoff_file(cache_file)).ok()?;
+    let until = UNIX_EPOCH.checked_add(Duration::from_secs(seconds.trim().parse().ok()?))?;
+    (until > now).then(|| until.min(now + MAX_WAIT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn retry_after_takes_seconds_or_an_http_date() {
+        let now = at(1_000);
+        assert_eq!(retry_after("120", now), Some(at(1_120)));
+        assert_eq!(retry_after(" 0 ", now), Some(now));
+        assert_eq!(
+            retry_after("Sun, 06 Nov 1994 08:49:37 GMT", now),
+            Some(at(784_111_777))
+        );
+        assert_eq!(
+            retry_after("Thu, 01 Jan 1970 00:00:00 GMT", now),
+            Some(UNIX_EPOCH)
+        );
+        assert_eq!(
+            retry_after("Tue, 29 Feb 2028 23:59:59 GMT", now),
+            Some(at(1_835_481_599))
+        );
+        for invalid in [
+            "",
+            "-5",
+            "soon",
+            "Sun, 06 Nov 1994 08:49:37 PST",
+            "Sun, 06 Foo 1994 08:49:37 GMT",
+            "Sun, 32 Nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 08:49 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+        ] {
+            assert_eq!(retry_after(invalid, now), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn waits_are_recorded_and_expire() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("tool-update-check");
+        let now = at(1_000_000);
+        assert_eq!(until(&cache, now), None);
+
+        record(&cache, Some(at(1_000_090)), now);
+        assert_eq!(until(&cache, now), Some(at(1_000_090)));
+        assert_eq!(until(&cache, at(1_000_089)), Some(at(1_000_090)));
+        assert_eq!(until(&cache, at(1_000_090)), None);
+
+        record(&cache, None, now);
+        assert_eq!(until(&cache, now), Some(now + DEFAULT_WAIT));
+        record(&cache, Some(now + 10 * MAX_WAIT), now);
+        assert_eq!(until(&cache, now), Some(now + MAX_WAIT));
+
+        // Setting the clock back doesn't lengthen the wait beyond the cap.
+        let earlier = now - 5 * MAX_WAIT;
+        assert_eq!(until(&cache, earlier), Some(earlier + MAX_WAIT));
+    }
+}
@@ -0,0 +1,110 @@
+//! A persistent, machine-wide cap on registry requests per day.
+//!
+//! Each crate's budget lives in the state directory as a list of request
+//! timestamps (Unix seconds, one per line) covering the last 24 hours. The
+//! file is only read and rewritten while holding a sibling lock file, so
+//! concurrent processes cannot overspend it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The rolling window the budget applies to.
+const WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long to wait for another process to release the lock.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Locks older than this were left behind by a crashed process.
+const STALE_LOCK: Duration = Duration::from_secs(30);
+
+/// The budget file for a cache key inside a state directory.
+pub fn budget_file(state_dir: &Path, key: &str) -> PathBuf {
+    state_dir.join(format!("{key}.budget"))
+}
+
+/// Record a request at `now` if fewer than `limit` were made in the preceding
+/// 24 hours, returning whether the request may proceed.
+///
+/// Fails closed: if the budget cannot be read, locked, or written, the request
+/// is refused.
+pub fn spend(path: &Path, limit: u32, now: SystemTime) -> bool {
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let Some(_lock) = Lock::acquire(&lock_file(path)) else {
+        return false;
+    };
+    let Ok(now) = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()) else {
+        return false;
+    };
+
+    let window_start = now.saturating_sub(WINDOW.as_secs());
+    let mut spent: Vec<u64> = fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        // Entries from the future mean the clock went backwards; keep them
+        // so that rewinding the clock cannot reset the budget.
+        .filter(|&at| at > window_start)
+        .collect();
+
+    if spent.len() >= limit as usize {
+        return false;
+    }
+    spent.push(now);
+
+    let mut contents = spent
+        .iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+    contents.push('\n');
+    fs::write(path, contents).is_ok()
+}
+
+fn lock_file(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".lock");
+    PathBuf::from(name)
+}
+
+/// An exclusive lock held by creating a file, removed on drop.
+struct Lock(PathBuf);
+
+impl Lock {
+    fn acquire(path: &Path) -> Option<Self> {
+        let deadline = SystemTime::now() + LOCK_TIMEOUT;
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path)
+            {
+                Ok(_) => return Some(Self(path.to_path_buf())),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let stale = fs::metadata(path)
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .and_then(|at| at.elapsed().ok())
+                        .is_some_and(|age| age > STALE_LOCK);
+                    if stale {
+                        let _ = fs::remove_file(path);
+                    } else if SystemTime::now() > deadline {
+                        return None;
+                    } else {
+                        thread::sleep(Duration::from_millis(10));
+                    }
+                }
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
@@ -0,0 +1,59 @@
+//! Copies of registry responses that failed to parse, for bug reports.
+//!
+//! With [`capture_failed_bodies`](crate::UpdateChecker::capture_failed_bodies),
+//! a response the parser rejects is saved next to the cache file as
+//! `<key>.parse-failure-<unix millis>.json`, and the error names the file so
+//! a user can attach it to an issue. Saving a capture prunes older ones, so
+//! repeated failures never fill the cache directory.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The most of a response body a capture keeps.
+pub const MAX_BYTES: usize = 64 * 1024;
+
+/// How many captures per cache key are kept, including the newest.
+const MAX_KEPT: usize = 5;
+
+/// Captures older than this are removed even if fewer than [`MAX_KEPT`] exist.
+const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The prefix of every capture file name for `key`.
+fn prefix(key: &str) -> String {
+    format!("{key}.parse-failure-")
+}
+
+/// Save up to [`MAX_BYTES`] of `body` in `cache_dir`, returning the file's
+/// path, or `None` if it couldn't be written.
+pub fn save(cache_dir: &Path, key: &str, body: &[u8], now: SystemTime) -> Option<PathBuf> {
+    prune(cache_dir, key, now);
+    let millis = now.duration_since(UNIX_EPOCH).ok()?.as_millis();
+    let path = cache_dir.join(format!("{}{millis}.json", prefix(key)));
+    fs::create_dir_all(cache_dir).ok()?;
+    fs::write(&path, body.get(..MAX_BYTES).unwrap_or(body)).ok()?;
+    Some(path)
+}
+
+/// Remove captures for `key` older than [`MAX_AGE`], and all but the newest
+/// `MAX_KEPT - 1` of the rest to make room for another.
+fn prune(cache_dir: &Path, key: &str, now: SystemTime) {
+    let Ok(entries) = fs::read_dir(cache_dir) else {
+        return;
+    };
+    let prefix = prefix(key);
+    let mut captures: Vec<(SystemTime, PathBuf)> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect();
+    captures.sort();
+
+    let excess = captures.len().saturating_sub(MAX_KEPT - 1);
+    for (index, (modified, path)) in captures.iter().enumerate() {
+        let expired = now.duration_since(*modified).is_ok_and(|age| age > MAX_AGE);
+        if index < excess || expired {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
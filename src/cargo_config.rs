@@ -0,0 +1,317 @@
+//! Finding the registry Cargo's configuration puts in place of crates.io,
+//! and the tokens Cargo keeps for other registries.
+//!
+//! Organizations often send Cargo's crates.io traffic to a mirror with a
+//! [source replacement](https://doc.rust-lang.org/cargo/reference/source-replacement.html):
+//!
+//! ```toml
+//! [source.crates-io]
+//! replace-with = "mirror"
+//!
+//! [source.mirror]
+//! registry = "sparse+https://mirror.example.com/index/"
+//! ```
+//!
+//! Cargo reads `.cargo/config.toml` from the current directory and each of
+//! its ancestors, then from `$CARGO_HOME`, and a file nearer the current
+//! directory overrides one further away key by key. The same TOML subset as
+//! [`manifest`](crate::manifest) is understood, plus single-line inline
+//! tables. Only a sparse index can be queried; a git, local or directory
+//! replacement is ignored.
+//!
+//! Registry tokens are also read from Cargo's `credentials.toml`, for
+//! [`use_cargo_credentials`](crate::UpdateChecker::use_cargo_credentials).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::source::manifest::{key_path, split_key, string_value};
+
+/// The most `replace-with` links followed, bounding a cycle.
+const MAX_REPLACEMENTS: usize = 8;
+
+/// The sparse index URL replacing crates.io in the configuration found from
+/// `dir`, or from the current directory, without its `sparse+` prefix.
+///
+/// Returns `None` when crates.io isn't replaced, or not by a sparse index.
+/// Files that can't be read are skipped, as Cargo's absence would be.
+pub fn crates_io_replacement(dir: Option<&Path>) -> Option<String> {
+    let start = dir
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::current_dir().ok())?;
+    let configs: Vec<String> = config_files(&start, cargo_home().as_deref())
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .collect();
+    let configs: Vec<&str> = configs.iter().map(String::as_str).collect();
+    resolve(&configs, |key| std::env::var(key).ok())
+}
+
+/// The token for the registry Cargo knows as `name`, from the
+/// `credentials.toml` in Cargo's home directory.
+///
+/// Like Cargo, the extensionless legacy `credentials` is read when both it
+/// and `credentials.toml` exist. A missing or unreadable file means no token.
+pub fn registry_token(name: &str) -> Option<String> {
+    let home = cargo_home()?;
+    let credentials = ["credentials", "credentials.toml"]
+        .into_iter()
+        .map(|file| home.join(file))
+        .find(|path| path.is_file())?;
+    token(&fs::read_to_string(credentials).ok()?, name)
+}
+
+/// The non-empty `registries.<name>.token` in a credentials file.
+fn token(credentials: &str, name: &str) -> Option<String> {
+    strings(credentials)
+        .into_iter()
+        .find(|(key, _)| {
+            key.iter()
+                .map(String::as_str)
+                .eq(["registries", name, "token"])
+        })
+        .map(|(_, token)| token)
+        .filter(|token| !token.is_empty())
+}
+
+/// Cargo's home directory: `$CARGO_HOME`, or `.cargo` in the user's home.
+fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(std::env::home_dir()?.join(".cargo")))
+}
+
+/// The configuration files that apply in `start`, nearest first.
+///
+/// Like Cargo, the extensionless legacy `config` is read when both it and
+/// `config.toml` exist, and `cargo_home`'s file isn't read twice when it is
+/// also an ancestor's.
+fn config_files(start: &Path, cargo_home: Option<&Path>) -> Vec<PathBuf> {
+    let config_in = |dir: &Path| {
+        ["config", "config.toml"]
+            .into_iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+    };
+    let mut files: Vec<PathBuf> = start
+        .ancestors()
+        .filter_map(|dir| config_in(&dir.join(".cargo")))
+        .collect();
+    if let Some(home) = cargo_home.and_then(config_in) {
+        if !files.contains(&home) {
+            files.push(home);
+        }
+    }
+    files
+}
+
+/// The sparse index replacing crates.io in `configs`, nearest first, with
+/// `CARGO_REGISTRIES_<NAME>_INDEX` variables read through `env` taking
+/// precedence over the files as they do for Cargo.
+pub fn resolve(configs: &[&str], env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let entries: Vec<Vec<(Vec<String>, String)>> = configs.iter().map(|c| strings(c)).collect();
+    let get = |path: &[&str]| {
+        entries.iter().find_map(|entries| {
+            entries
+                .iter()
+                .find(|(key, _)| key.iter().map(String::as_str).eq(path.iter().copied()))
+                .map(|(_, value)| value.clone())
+        })
+    };
+
+    let mut name = "crates-io".to_string();
+    for hops in 0.. {
+        match get(&["source", &name, "replace-with"]) {
+            Some(replacement) if hops < MAX_REPLACEMENTS => name = replacement,
+            Some(_) => return None,
+            None => break,
+        }
+    }
+    if name == "crates-io" {
+        return None;
+    }
+    let variable = format!(
+        "CARGO_REGISTRIES_{}_INDEX",
+        name.to_ascii_uppercase().replace('-', "_")
+    );
+    let index = get(&["source", &name, "registry"])
+        .or_else(|| env(&variable))
+        .or_else(|| get(&["registries", &name, "index"]))?;
+    index.strip_prefix("sparse+").map(String::from)
+}
+
+/// Every string value in `toml`, with the full path of its key.
+fn strings(toml: &str) -> Vec<(Vec<String>, String)> {
+    let mut entries = Vec::new();
+    let mut table = Vec::new();
+    let mut in_multiline = None;
+
+    for line in toml.lines() {
+        if let Some(delimiter) = in_multiline {
+            if line.matches(delimiter).count() % 2 == 1 {
+                in_multiline = None;
+            }
+            continue;
+        }
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[') {
+            table = if header.starts_with('[') {
+                vec!["[]".to_string()]
+            } else {
+                header
+                    .split_once(']')
+                    .map(|(path, _)| key_path(path))
+                    .unwrap_or_default()
+            };
+            continue;
+        }
+        let Some((key, value)) = split_key(line) else {
+            continue;
+        };
+        let value = value.trim_start();
+        for delimiter in ["\"\"\"", "'''"] {
+            if value.starts_with(delimiter) && value.matches(delimiter).count() == 1 {
+                in_multiline = Some(delimiter);
+            }
+        }
+
+        let mut path = table.clone();
+        path.extend(key_path(key));
+        if let Some(inline) = value.strip_prefix('{') {
+            for (key, value) in inline_entries(inline) {
+                let mut path = path.clone();
+                path.extend(key_path(key));
+                entries.extend(string_value(value.trim_start()).map(|value| (path, value)));
+            }
+        } else if let Some(value) = string_value(value) {
+            entries.push((path, value));
+        }
+    }
+    entries
+}
+
+/// The `key = value` pairs of an inline table, after its `{`. Nested tables
+/// and arrays are skipped.
+fn inline_entries(inline: &str) -> Vec<(&str, &str)> {
+    let mut pairs = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in inline.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, ',' | '}') => {
+                pairs.extend(inline.get(start..i).and_then(split_key));
+                start = i + 1;
+                if c == '}' {
+                    break;
+                }
+            }
+            (None, '{' | '[') => return pairs,
+            _ => {}
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn replacements_are_followed_to_a_sparse_index() {
+        let config = r#"
+            [source.crates-io]
+            replace-with = "corp"
+
+            [source.corp]
+            replace-with = 'mirror'
+
+            [source.mirror]
+            registry = "sparse+https://mirror.example.com/index/"
+        "#;
+        assert_eq!(
+            resolve(&[config], no_env).unwrap(),
+            "https://mirror.example.com/index/"
+        );
+
+        let registries = r#"
+            source.crates-io.replace-with = "corp" # a comment
+            [registries]
+            corp = { index = "sparse+https://corp.example.com/", token = "x" }
+        "#;
+        assert_eq!(
+            resolve(&[registries], no_env).unwrap(),
+            "https://corp.example.com/"
+        );
+        let env = |key: &str| {
+            (key == "CARGO_REGISTRIES_CORP_INDEX").then(|| "sparse+https://env.example.com/".into())
+        };
+        assert_eq!(
+            resolve(&[registries], env).unwrap(),
+            "https://env.example.com/"
+        );
+    }
+
+    #[test]
+    fn nearer_files_override_further_ones_by_key() {
+        let home = r#"
+            [source.crates-io]
+            replace-with = "mirror"
+            [source.mirror]
+            registry = "sparse+https://home.example.com/"
+        "#;
+        let project = r#"
+            [source.mirror]
+            registry = "sparse+https://project.example.com/"
+        "#;
+        assert_eq!(
+            resolve(&[project, home], no_env).unwrap(),
+            "https://project.example.com/"
+        );
+        assert_eq!(
+            resolve(&[home, project], no_env).unwrap(),
+            "https://home.example.com/"
+        );
+    }
+
+    #[test]
+    fn tokens_are_found_by_registry_name() {
+        let credentials = r#"
+            [registry]
+            token = "crates-io-token"
+
+            [registries.kellnr]
+            token = "kellnr-token"
+
+            [registries]
+            corp = { token = "corp-token" }
+            empty = { token = "" }
+        "#;
+        assert_eq!(token(credentials, "kellnr").unwrap(), "kellnr-token");
+        assert_eq!(token(credentials, "corp").unwrap(), "corp-token");
+        assert_eq!(token(credentials, "empty"), None);
+        assert_eq!(token(credentials, "crates-io"), None);
+        assert_eq!(token("not toml at all", "kellnr"), None);
+    }
+
+    #[test]
+    fn unusable_replacements_are_ignored() {
+        for config in [
+            "",
+            "[source.crates-io]\nregistry = \"sparse+https://x.example.com/\"",
+            "[source.crates-io]\nreplace-with = \"vendored\"\n[source.vendored]\ndirectory = \"vendor\"",
+            "[source.crates-io]\nreplace-with = \"git\"\n[source.git]\nregistry = \"https://github.com/o/index\"",
+            "[source.crates-io]\nreplace-with = \"a\"\n[source.a]\nreplace-with = \"crates-io\"",
+            "[source.crates-io]\nreplace-with = \"a\"\n[source.a]\nreplace-with = \"b\"\n[source.b]\nreplace-with = \"a\"",
+            "x = \"\"\"\n[source.crates-io]\nreplace-with = \"a\"\n\"\"\"\n[source.a]\nregistry = \"sparse+https://a/\"",
+        ] {
+            assert_eq!(resolve(&[config], no_env), None, "{config}");
+        }
+    }
+}
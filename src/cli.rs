@@ -0,0 +1,72 @@
+//! Mapping command-line flags onto a checker.
+
+use crate::{CheckMode, UpdateChecker};
+
+/// The update-check flags an application exposes on its command line, and
+/// the default from its configuration.
+///
+/// Applies the usual precedence: a command-line flag beats an opt-out in the
+/// environment (`DO_NOT_TRACK`), which beats the application's configured
+/// default. Build the checker first and apply the policy last, so the flags
+/// always win:
+///
+/// ```no_run
+/// use tiny_update_check::{CliPolicy, UpdateChecker};
+///
+/// # let (no_update_check, check_updates, update_check) = (false, false, None);
+/// let policy = CliPolicy {
+///     no_update_check,
+///     force_check: check_updates,
+///     config_enabled: update_check,
+/// };
+/// let checker = policy.apply(UpdateChecker::new("my-crate", "1.0.0"));
+/// if let Ok(Some(update)) = checker.check() {
+///     eprintln!("{}", update.latest);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CliPolicy {
+    /// The user asked not to check, e.g. with `--no-update-check`.
+    pub no_update_check: bool,
+    /// The user asked to check now, e.g. with `--check-updates`, even if
+    /// checks are opted out of or a cached result is fresh.
+    pub force_check: bool,
+    /// Whether the application's configuration turns checks on or off,
+    /// e.g. with `update_check = false` in its config file, or `None` to
+    /// keep the checker's mode.
+    ///
+    /// Turning checks on still honors `DO_NOT_TRACK`.
+    pub config_enabled: Option<bool>,
+}
+
+impl CliPolicy {
+    /// The mode the flags and configuration call for, or `None` to keep the
+    /// checker's own.
+    ///
+    /// `no_update_check` wins if both flags are set. Use this to configure
+    /// `r#async::UpdateChecker::mode` with the `async` feature.
+    #[must_use]
+    pub const fn mode(&self) -> Option<CheckMode> {
+        if self.no_update_check {
+            Some(CheckMode::Disabled)
+        } else if self.force_check {
+            Some(CheckMode::Forced)
+        } else {
+            match self.config_enabled {
+                Some(true) => Some(CheckMode::Auto),
+                Some(false) => Some(CheckMode::Disabled),
+                None => None,
+            }
+        }
+    }
+
+    /// Apply the flags and configuration to `checker`, overriding its
+    /// [`CheckMode`] if any is set.
+    #[must_use]
+    pub const fn apply(&self, checker: UpdateChecker) -> UpdateChecker {
+        match self.mode() {
+            Some(mode) => checker.mode(mode),
+            None => checker,
+        }
+    }
+}
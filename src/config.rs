@@ -0,0 +1,290 @@
+//! The configuration the blocking and async checkers share.
+//!
+//! Both checkers look a crate up, authenticate, pick a proxy and TLS
+//! settings, and reject a bad configuration the same way, so they keep
+//! those settings in one [`CheckerConfig`] and derive their requests from
+//! it here. The few differences between their HTTP clients are spelled out
+//! by [`Backend`].
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(doc)]
+use crate::UpdateChecker;
+use crate::{
+    AuthToken, CustomHeaders, DEFAULT_INDEX_URL, DEFAULT_REGISTRY_URL, Error, Hosts, MemoryStore,
+    ReleaseSource, ResponseFormat, TlsSettings, cargo_config, crate_api_url, default_registry_url,
+    is_loopback_url, proxy, proxy::ProxyUrl, registry_token, sparse_index_url, url_host_port,
+    user_agent, validate_base_url, validate_cache_key, validate_crate_name, validate_registry_url,
+    validate_user_agent,
+};
+
+/// The HTTP client a checker sends requests with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// The blocking checker's `rustls` transport.
+    Rustls,
+    /// The blocking checker's `native-tls` transport, which can't use a
+    /// proxy, host overrides or TLS settings.
+    NativeTls,
+    /// The async checker's `reqwest` client, which checks certificate pins
+    /// with the `rustls` transport's setup.
+    Reqwest,
+}
+
+/// Where and how a checker sends its requests.
+#[derive(Debug, Clone)]
+// Independent builder switches, not a state machine.
+#[allow(clippy::struct_excessive_bools)]
+pub struct CheckerConfig {
+    pub crate_name: String,
+    pub lenient_names: bool,
+    pub app_user_agent: Option<String>,
+    pub headers: CustomHeaders,
+    pub header_overrides: bool,
+    pub cache_dir: Option<PathBuf>,
+    pub cache_key: Option<String>,
+    pub memory: Option<Arc<MemoryStore>>,
+    pub capture_failed_bodies: bool,
+    pub message_url: Option<String>,
+    pub registry_url: String,
+    pub auth_token: Option<AuthToken>,
+    pub registry_name: Option<String>,
+    pub cargo_credentials: bool,
+    pub netrc: bool,
+    pub project_dir: Option<PathBuf>,
+    pub cargo_config: bool,
+    pub sparse_index: bool,
+    pub index_url: String,
+    pub release_source: Option<ReleaseSource>,
+    pub proxy_loopback: bool,
+    pub env_proxy: bool,
+    pub proxy: Option<ProxyUrl>,
+    pub hosts: Hosts,
+    pub tls: TlsSettings,
+    pub env_certificates: bool,
+    pub allow_insecure_http: bool,
+}
+
+impl CheckerConfig {
+    /// The defaults for checking `crate_name`, caching in `cache_dir`.
+    pub fn new(crate_name: String, cache_dir: Option<PathBuf>) -> Self {
+        Self {
+            crate_name,
+            lenient_names: false,
+            app_user_agent: None,
+            headers: CustomHeaders::default(),
+            header_overrides: false,
+            cache_dir,
+            cache_key: None,
+            memory: None,
+            capture_failed_bodies: false,
+            message_url: None,
+            registry_url: default_registry_url(),
+            auth_token: None,
+            registry_name: None,
+            cargo_credentials: false,
+            netrc: false,
+            project_dir: None,
+            cargo_config: false,
+            sparse_index: false,
+            index_url: DEFAULT_INDEX_URL.to_string(),
+            release_source: None,
+            proxy_loopback: false,
+            env_proxy: true,
+            proxy: None,
+            hosts: Hosts::default(),
+            tls: TlsSettings::default(),
+            env_certificates: true,
+            allow_insecure_http: false,
+        }
+    }
+
+    /// The URL to look up `crate_name`'s latest version at, and the format
+    /// it's answered in.
+    pub fn lookup(&self, crate_name: &str) -> (String, ResponseFormat) {
+        match self.release_source {
+            Some(ref source) if crate_name == self.crate_name => source.lookup(),
+            _ => match self.cargo_replacement() {
+                Some(index) => (
+                    sparse_index_url(&index, crate_name),
+                    ResponseFormat::SparseIndex,
+                ),
+                None if self.sparse_index => (
+                    sparse_index_url(&self.index_url, crate_name),
+                    ResponseFormat::SparseIndex,
+                ),
+                None => (
+                    crate_api_url(&self.registry_url, crate_name),
+                    ResponseFormat::Api,
+                ),
+            },
+        }
+    }
+
+    /// The sparse index Cargo's configuration replaces crates.io with, if
+    /// [`respect_cargo_config`](UpdateChecker::respect_cargo_config) is on
+    /// and lookups would otherwise go to crates.io.
+    fn cargo_replacement(&self) -> Option<String> {
+        let crates_io = if self.sparse_index {
+            self.index_url == DEFAULT_INDEX_URL
+        } else {
+            self.registry_url == DEFAULT_REGISTRY_URL
+        };
+        if !self.cargo_config || !crates_io {
+            return None;
+        }
+        cargo_config::crates_io_replacement(self.project_dir.as_deref())
+    }
+
+    /// The token to send with a request to `url` in `format`, per
+    /// [`registry_token`], with the
+    /// [Cargo credentials](UpdateChecker::use_cargo_credentials) of the
+    /// configured registry. Release sources send their own.
+    pub fn token(&self, url: &str, format: ResponseFormat) -> Result<Option<AuthToken>, Error> {
+        if format.is_release() {
+            return Ok(None);
+        }
+        let configured = [&self.registry_url, &self.index_url]
+            .iter()
+            .any(|registry| url_host_port(registry) == url_host_port(url));
+        let name = self
+            .registry_name
+            .as_deref()
+            .filter(|_| self.cargo_credentials && configured);
+        registry_token(self.auth_token.as_ref(), url, name, self.netrc)
+    }
+
+    /// Whether requests to `url` go directly: for a loopback host unless
+    /// [`proxy_loopback`](UpdateChecker::proxy_loopback) is on, or with
+    /// neither a [proxy](UpdateChecker::proxy) nor
+    /// [`use_env_proxy`](UpdateChecker::use_env_proxy).
+    pub fn bypasses_proxy(&self, url: &str) -> bool {
+        (!self.proxy_loopback && is_loopback_url(url)) || (self.proxy.is_none() && !self.env_proxy)
+    }
+
+    /// The `User-Agent` sent with every request.
+    pub fn user_agent(&self) -> String {
+        user_agent(self.app_user_agent.as_deref())
+    }
+
+    /// The headers of a request with the lookup's `headers`: the
+    /// `User-Agent`, those, and the [custom headers](UpdateChecker::header).
+    pub fn request_headers(&self, headers: &[(&'static str, String)]) -> Vec<(String, String)> {
+        self.headers.apply(
+            std::iter::once(("User-Agent", self.user_agent())).chain(headers.iter().cloned()),
+        )
+    }
+
+    /// The proxy requests to `url` go through, if any: the configured
+    /// [proxy](UpdateChecker::proxy), or else the environment's.
+    pub fn proxy_for(&self, url: &str) -> Option<String> {
+        if self.bypasses_proxy(url) {
+            return None;
+        }
+        match self.proxy {
+            Some(ProxyUrl(ref proxy)) => Some(proxy::with_scheme(proxy)),
+            None => proxy::for_url(url),
+        }
+    }
+
+    /// Reject an invalid crate name, release source, lookup or message URL,
+    /// header, proxy, TLS setting or cache key, or a setting `backend`
+    /// can't honor, before checking.
+    pub fn validate(&self, backend: Backend) -> Result<(), Error> {
+        validate_crate_name(&self.crate_name, self.lenient_names)?;
+        if let Some(ref source) = self.release_source {
+            source.validate()?;
+        }
+        let (url, format) = self.lookup(&self.crate_name);
+        if !format.is_release() {
+            validate_base_url(&url)?;
+        }
+        validate_registry_url(&url, self.allow_insecure_http)?;
+        if let Some(ref message_url) = self.message_url {
+            validate_registry_url(message_url, self.allow_insecure_http)?;
+        }
+        if let Some(ref agent) = self.app_user_agent {
+            validate_user_agent(agent)?;
+        }
+        self.headers.validate(self.header_overrides)?;
+        self.validate_backend(backend)?;
+        if let Some(ProxyUrl(ref proxy)) = self.proxy {
+            proxy::validate(proxy)?;
+        }
+        self.tls_settings(backend)?.validate()?;
+        validate_cache_key(self.cache_key.as_deref())
+    }
+
+    /// Reject a proxy or TLS setting `backend` can't honor.
+    fn validate_backend(&self, backend: Backend) -> Result<(), Error> {
+        let native_tls = backend == Backend::NativeTls;
+        let pinned = !self.tls.pins().is_empty();
+        let unsupported = [
+            (
+                native_tls && self.proxy.is_some(),
+                "a proxy needs the `rustls` feature; the `native-tls` transport can't use one",
+            ),
+            (
+                native_tls && !self.hosts.is_empty(),
+                "host overrides need the `rustls` feature; the `native-tls` transport always \
+                 resolves hosts itself",
+            ),
+            (
+                native_tls && self.tls.has_roots(),
+                "root certificates need the `rustls` feature; the `native-tls` transport only \
+                 trusts the system's",
+            ),
+            (
+                native_tls && self.tls.min_version().is_some(),
+                "a minimum TLS version needs the `rustls` feature; the `native-tls` transport \
+                 can't enforce one",
+            ),
+            (
+                native_tls && pinned,
+                "certificate pins need the `rustls` feature; the `native-tls` transport can't \
+                 check them",
+            ),
+            (
+                native_tls && self.tls.accepts_invalid_certs(),
+                "accepting invalid certificates needs the `rustls` feature; the `native-tls` \
+                 transport always verifies them",
+            ),
+            (
+                backend == Backend::Reqwest && pinned && cfg!(not(feature = "rustls")),
+                "certificate pins need the `rustls` feature, whose rustls setup the async \
+                 checker shares to check them",
+            ),
+            (
+                self.tls.accepts_invalid_certs() && pinned,
+                "certificate pins can't be checked when invalid certificates are accepted",
+            ),
+        ];
+        match unsupported.iter().find(|(unsupported, _)| *unsupported) {
+            Some((_, message)) => Err(Error::ConfigError((*message).to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// The TLS settings for requests with `backend`: the root certificates
+    /// added, and those in the environment if
+    /// [enabled](UpdateChecker::use_env_certificates) and not left to
+    /// `native-tls`.
+    pub fn tls_settings(&self, backend: Backend) -> Result<TlsSettings, Error> {
+        if self.env_certificates && backend != Backend::NativeTls {
+            self.tls.with_env()
+        } else {
+            Ok(self.tls.clone())
+        }
+    }
+
+    /// Where to save a response that fails to parse, if
+    /// [capturing](UpdateChecker::capture_failed_bodies) is on: the cache
+    /// directory, and the key to name the capture after.
+    pub fn capture_target<'a>(&'a self, key: &'a str) -> Option<(&'a Path, &'a str)> {
+        match (&self.cache_dir, &self.memory) {
+            (Some(dir), None) if self.capture_failed_bodies => Some((dir, key)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Atomic, optionally durable, file replacement for the cache.
+//!
+//! A file is replaced by writing a temporary sibling and renaming it over
+//! the original, so readers see the old contents or the new, never a partial
+//! write. Renaming alone doesn't order the data before the rename on disk,
+//! so after a crash a file may still turn up empty; durable writes `fsync`
+//! the temporary file before renaming it, and the directory afterwards.
+//!
+//! A writer that crashes between the two leaves its temporary file behind;
+//! [`remove_strays`] clears those out once they're old enough that no live
+//! writer can still own them.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+/// Distinguishes the temporary files of concurrent writers in one process.
+static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+/// How old a temporary file must be to count as left by a crashed writer.
+pub const STRAY_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Replace the file at `path` with `contents`, `fsync`ing it if `durable`.
+pub fn replace(path: &Path, contents: impl AsRef<[u8]>, durable: bool) -> io::Result<()> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(
+        ".{}-{}.tmp",
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp = path.with_file_name(name);
+    let written = write_temp(&temp, contents.as_ref(), durable);
+    let result = written.and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+fn write_temp(temp: &Path, contents: &[u8], durable: bool) -> io::Result<()> {
+    let mut file = File::create(temp)?;
+    file.write_all(contents)?;
+    if durable {
+        sync(&file)?;
+    }
+    Ok(())
+}
+
+/// Remove the temporary files that writers replacing `path` or a file next
+/// to it (`path` with a further extension) left behind at least [`STRAY_AGE`]
+/// before `now`.
+///
+/// Best-effort: files that can't be listed, dated or removed are skipped.
+pub fn remove_strays(path: &Path, now: SystemTime) {
+    let (Some(dir), Some(prefix)) = (path.parent(), path.file_name()) else {
+        return;
+    };
+    let Some(prefix) = prefix.to_str() else {
+        return;
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if !name.to_str().is_some_and(|name| is_temp_of(name, prefix)) {
+            continue;
+        }
+        let modified = entry.metadata().and_then(|metadata| metadata.modified());
+        if modified.is_ok_and(|modified| {
+            now.duration_since(modified)
+                .is_ok_and(|age| age >= STRAY_AGE)
+        }) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Whether `name` is a temporary file [`replace`] writes for the file named
+/// `prefix`, or for a file named `prefix` with a further extension.
+fn is_temp_of(name: &str, prefix: &str) -> bool {
+    let Some((target, writer)) = name
+        .strip_suffix(".tmp")
+        .and_then(|name| name.rsplit_once('.'))
+    else {
+        return false;
+    };
+    let is_number = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    let is_writer = writer
+        .split_once('-')
+        .is_some_and(|(pid, n)| is_number(pid) && is_number(n));
+    is_writer
+        && target
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// `fsync` `dir`, making renames into it durable.
+///
+/// Directories can't be opened for syncing on Windows, so there only the
+/// files themselves are synced.
+pub fn sync_dir(dir: &Path) -> io::Result<()> {
+    if cfg!(windows) {
+        return Ok(());
+    }
+    sync(&File::open(dir)?)
+}
+
+fn sync(file: &File) -> io::Result<()> {
+    #[cfg(test)]
+    SYNCS.with(|syncs| syncs.set(syncs.get() + 1));
+    file.sync_all()
+}
+
+#[cfg(test)]
+thread_local! {
+    static SYNCS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// How many `fsync`s this thread has issued.
+#[cfg(test)]
+pub fn syncs() -> usize {
+    SYNCS.with(std::cell::Cell::get)
+}
@@ -0,0 +1,33 @@
+//! The version that ran last, remembered across runs.
+//!
+//! Each check records the running version in the state directory, so the
+//! next run of a different version can tell it was upgraded (or downgraded)
+//! from it. The file holds the version alone, and is replaced atomically like
+//! the cache, so a torn write can't leave a shorter version behind.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::durable;
+
+/// The file the last-run version for a cache key is recorded in.
+pub fn file(state_dir: &Path, key: &str) -> PathBuf {
+    state_dir.join(format!("{key}.last-run"))
+}
+
+/// The recorded version, or `None` if there is none or it is garbled.
+pub fn read(path: &Path) -> Option<String> {
+    let contents = fs::read_to_string(path).ok()?;
+    let version = contents.trim();
+    let garbled = version.is_empty() || version.len() > 64 || version.contains(char::is_whitespace);
+    (!garbled).then(|| version.to_string())
+}
+
+/// Record `version` as the last to run, `fsync`ing it if `durable`, ignoring
+/// failures.
+pub fn record(path: &Path, version: &str, durable: bool) {
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let _ = durable::replace(path, version, durable);
+}
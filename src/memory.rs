@@ -0,0 +1,126 @@
+//! In-process storage for [ephemeral](crate::UpdateChecker::ephemeral) checks.
+//!
+//! Stands in for the cache files and the request budget file, keyed by crate
+//! name, so a check leaves nothing on disk. Everything is lost when the last
+//! checker sharing the store is dropped.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::{Latest, SuggestStrategy};
+
+/// The rolling window a request budget applies to, as for the budget file.
+const WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Default)]
+pub struct MemoryStore(Mutex<HashMap<String, Entry>>);
+
+#[derive(Debug, Default)]
+struct Entry {
+    /// The cached [`Latest`] and when it was fetched.
+    cached: Option<(Latest, Instant)>,
+    /// When each request in the last 24 hours was made.
+    spent: Vec<Instant>,
+}
+
+impl MemoryStore {
+    /// The cached [`Latest`] for `crate_name` if it is younger than `max_age`.
+    ///
+    /// Mirrors [`read_cached_latest`](crate::read_cached_latest): with
+    /// [`SuggestStrategy::SafestFirst`], a cache without a version list is a
+    /// miss.
+    pub fn read(
+        &self,
+        crate_name: &str,
+        max_age: Duration,
+        suggest: SuggestStrategy,
+    ) -> Option<Latest> {
+        let (latest, fetched) = {
+            let entries = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+            entries.get(crate_name)?.cached.clone()?
+        };
+        if fetched.elapsed() >= max_age {
+            return None;
+        }
+        let versions = match suggest {
+            SuggestStrategy::Newest => None,
+            SuggestStrategy::SafestFirst => Some(latest.versions?),
+        };
+        Some(Latest {
+            version: latest.version,
+            versions,
+            artifacts: latest.artifacts,
+            updated_at: latest.updated_at,
+            registry_url: None,
+            race_winner: None,
+            max_age: latest.max_age,
+            crate_name: None,
+            stale: false,
+            offline: false,
+            response_body: None,
+        })
+    }
+
+    /// The `max-age` the server gave for what is cached for `crate_name`,
+    /// however old, if it gave one.
+    pub fn max_age(&self, crate_name: &str) -> Option<Duration> {
+        let entries = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.get(crate_name)?.cached.as_ref()?.0.max_age
+    }
+
+    /// Cache a freshly fetched [`Latest`] for `crate_name`.
+    pub fn write(&self, crate_name: &str, latest: &Latest) {
+        let mut entries = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let latest = Latest {
+            version: latest.version.clone(),
+            versions: latest.versions.clone(),
+            artifacts: latest.artifacts.clone(),
+            updated_at: latest.updated_at.clone(),
+            registry_url: None,
+            race_winner: None,
+            max_age: latest.max_age,
+            crate_name: None,
+            stale: false,
+            offline: false,
+            response_body: None,
+        };
+        entries.entry(crate_name.to_string()).or_default().cached = Some((latest, Instant::now()));
+    }
+
+    /// Cache `latest` for `crate_name` as fetched at `fetched`, unless what is
+    /// cached was fetched at or after that.
+    pub fn seed(&self, crate_name: &str, latest: Latest, fetched: Instant) {
+        let mut entries = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let cached = &mut entries.entry(crate_name.to_string()).or_default().cached;
+        if cached.as_ref().is_none_or(|(_, at)| *at < fetched) {
+            *cached = Some((latest, fetched));
+        }
+        drop(entries);
+    }
+
+    /// Forget what is cached for `crate_name`, keeping its request budget.
+    pub fn clear(&self, crate_name: &str) {
+        let mut entries = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(entry) = entries.get_mut(crate_name) {
+            entry.cached = None;
+        }
+    }
+
+    /// Record a request for `crate_name` if fewer than `limit` were made in
+    /// the preceding 24 hours, returning whether the request may proceed.
+    pub fn spend(&self, crate_name: &str, limit: Option<u32>) -> bool {
+        let Some(limit) = limit else {
+            return true;
+        };
+        let mut entries = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let spent = &mut entries.entry(crate_name.to_string()).or_default().spent;
+        spent.retain(|at| at.elapsed() < WINDOW);
+        let allowed = spent.len() < limit as usize;
+        if allowed {
+            spent.push(Instant::now());
+        }
+        drop(entries);
+        allowed
+    }
+}
@@ -0,0 +1,181 @@
+//! Registry credentials from a `.netrc` file.
+//!
+//! A netrc file lists a login and password per machine, with an optional
+//! `default` entry for every other machine:
+//!
+//! ```text
+//! machine artifactory.example.com
+//!   login deploy
+//!   password s3cret
+//!
+//! default login anonymous password guest
+//! ```
+//!
+//! Tokens are separated by any whitespace, so an entry may span lines or
+//! share one. `macdef` macro definitions, which run to the next blank line,
+//! and `#` comment lines are skipped. The first entry for a machine wins,
+//! and `default` applies only when no entry names it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+
+/// The login and password the netrc file has for `host`: the file named
+/// by `$NETRC`, or `.netrc` in the user's home directory.
+///
+/// A missing or unreadable file means no credentials. On Unix, a file any
+/// user can read is refused with an [`Error::ConfigError`] rather than used.
+pub fn credentials(host: &str) -> Result<Option<(String, String)>, Error> {
+    let Some(path) = netrc_path() else {
+        return Ok(None);
+    };
+    let Ok(metadata) = fs::metadata(&path) else {
+        return Ok(None);
+    };
+    refuse_world_readable(&path, &metadata)?;
+    Ok(fs::read_to_string(&path)
+        .ok()
+        .and_then(|netrc| entry(&netrc, host)))
+}
+
+/// `$NETRC`, or `.netrc` in the user's home directory.
+fn netrc_path() -> Option<PathBuf> {
+    std::env::var_os("NETRC")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(std::env::home_dir()?.join(".netrc")))
+}
+
+#[cfg(unix)]
+fn refuse_world_readable(path: &Path, metadata: &fs::Metadata) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if metadata.permissions().mode() & 0o004 == 0 {
+        return Ok(());
+    }
+    Err(Error::ConfigError(format!(
+        "{} is readable by every user, so its credentials were not used; \
+         restrict it with `chmod 600`",
+        path.display()
+    )))
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+const fn refuse_world_readable(_: &Path, _: &fs::Metadata) -> Result<(), Error> {
+    Ok(())
+}
+
+/// An entry: its machine, or `None` for `default`, login and password.
+type Entry<'a> = (Option<&'a str>, Option<&'a str>, Option<&'a str>);
+
+/// The login and password for `host` in `netrc`. An entry without a
+/// login is ignored, and one without a password has an empty one.
+fn entry(netrc: &str, host: &str) -> Option<(String, String)> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut tokens = tokens(netrc).into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" => entries.push((Some(tokens.next().unwrap_or_default()), None, None)),
+            "default" => entries.push((None, None, None)),
+            "login" | "password" | "account" => {
+                let value = tokens.next();
+                if let Some((_, login, password)) = entries.last_mut() {
+                    match token {
+                        "login" => *login = value,
+                        "password" => *password = value,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let named = entries
+        .iter()
+        .find(|(machine, _, _)| machine.is_some_and(|m| m.eq_ignore_ascii_case(host)));
+    let (_, login, password) = named.or_else(|| entries.iter().find(|(m, _, _)| m.is_none()))?;
+    Some((
+        (*login)?.to_string(),
+        password.unwrap_or_default().to_string(),
+    ))
+}
+
+/// The whitespace-separated tokens of `netrc`, without comments or macros.
+fn tokens(netrc: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut lines = netrc.lines();
+    while let Some(line) = lines.next() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        for word in line.split_whitespace() {
+            if word == "macdef" {
+                lines
+                    .by_ref()
+                    .take_while(|line| !line.trim().is_empty())
+                    .for_each(drop);
+                break;
+            }
+            tokens.push(word);
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(login: &str, password: &str) -> (String, String) {
+        (login.to_string(), password.to_string())
+    }
+
+    #[test]
+    fn machines_are_matched_on_one_line_or_many() {
+        let netrc = "
+            # Corporate mirror
+            machine artifactory.example.com
+              login deploy
+              password s3cret
+
+            machine other.example.com login other password x
+            machine ARTIFACTORY.example.com login shadowed password y
+        ";
+        assert_eq!(
+            entry(netrc, "artifactory.example.com"),
+            Some(pair("deploy", "s3cret"))
+        );
+        assert_eq!(entry(netrc, "other.example.com"), Some(pair("other", "x")));
+        assert_eq!(entry(netrc, "missing.example.com"), None);
+    }
+
+    #[test]
+    fn default_applies_to_unnamed_machines() {
+        let netrc = "default login anonymous password guest\n\
+                     machine registry.example.com login me password pw";
+        assert_eq!(entry(netrc, "registry.example.com"), Some(pair("me", "pw")));
+        assert_eq!(
+            entry(netrc, "elsewhere.example.com"),
+            Some(pair("anonymous", "guest"))
+        );
+    }
+
+    #[test]
+    fn macros_and_incomplete_entries_are_skipped() {
+        let netrc = "
+machine a.example.com login a
+macdef init
+machine b.example.com login macro password body
+
+machine c.example.com password only
+machine d.example.com login d account acct password pw
+";
+        assert_eq!(entry(netrc, "a.example.com"), Some(pair("a", "")));
+        assert_eq!(entry(netrc, "b.example.com"), None);
+        assert_eq!(entry(netrc, "c.example.com"), None);
+        assert_eq!(entry(netrc, "d.example.com"), Some(pair("d", "pw")));
+    }
+}
@@ -0,0 +1,127 @@
+//! The full result of a check, and its conventional exit status.
+
+use crate::{DetailedUpdateInfo, Error, ErrorKind, NoUpdateReason, Verdict};
+
+/// What a check concluded, returned by
+/// [`UpdateChecker::check_outcome`](crate::UpdateChecker::check_outcome).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CheckOutcome {
+    /// An update is available.
+    Update(DetailedUpdateInfo),
+    /// No update is reported, for the given reason.
+    NoUpdate(NoUpdateReason),
+    /// The check failed.
+    Failed(Error),
+}
+
+impl CheckOutcome {
+    pub(crate) fn from_verdict(verdict: Result<Verdict, Error>) -> Self {
+        match verdict {
+            Ok(Verdict::Update(info)) => Self::Update(info),
+            Ok(Verdict::NoUpdate(reason)) => Self::NoUpdate(reason),
+            Err(e) => Self::Failed(e),
+        }
+    }
+
+    /// The conventional exit status for this outcome.
+    ///
+    /// See [`ExitCode`] for the mapping.
+    #[must_use]
+    pub const fn exit_status(&self) -> ExitCode {
+        match self {
+            Self::Update(_) => ExitCode::UpdateAvailable,
+            Self::NoUpdate(reason) => match reason {
+                NoUpdateReason::UpToDate
+                | NoUpdateReason::Prerelease
+                | NoUpdateReason::UnparsableLatest { .. }
+                | NoUpdateReason::OutsideVersionReq { .. } => ExitCode::UpToDate,
+                NoUpdateReason::DoNotTrack
+                | NoUpdateReason::Disabled
+                | NoUpdateReason::BudgetExhausted
+                | NoUpdateReason::Offline
+                | NoUpdateReason::RateLimited { .. }
+                | NoUpdateReason::ProjectDisabled { .. } => ExitCode::Skipped,
+            },
+            Self::Failed(e) => match e.kind() {
+                ErrorKind::Dns => ExitCode::Skipped,
+                _ => ExitCode::Error,
+            },
+        }
+    }
+
+    /// The conventional process exit code for this outcome, for applications
+    /// whose wrappers script around update checks.
+    ///
+    /// ```no_run
+    /// use tiny_update_check::UpdateChecker;
+    ///
+    /// let outcome = UpdateChecker::new("my-crate", "1.0.0").check_outcome();
+    /// std::process::exit(outcome.exit_code());
+    /// ```
+    #[must_use]
+    pub const fn exit_code(&self) -> i32 {
+        self.exit_status().as_i32()
+    }
+}
+
+/// A stable process exit status for a [`CheckOutcome`].
+///
+/// | Code | Status | Outcome |
+/// |------|--------|---------|
+/// | 0 | [`UpToDate`](Self::UpToDate) | No update: the current version is the latest, or the latest is a pre-release or can't be compared |
+/// | 1 | [`Error`](Self::Error) | The check failed |
+/// | 10 | [`UpdateAvailable`](Self::UpdateAvailable) | An update is available |
+/// | 11 | [`UpdateAvailableCurrentYanked`](Self::UpdateAvailableCurrentYanked) | An update is available and the current version is yanked |
+/// | 20 | [`Skipped`](Self::Skipped) | Opted out, disabled, out of request budget, or offline (the registry host can't be resolved) |
+///
+/// # Stability
+///
+/// The mapping is part of the API. New statuses only ever add codes; an
+/// existing code is never renumbered or given a different meaning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ExitCode {
+    /// `0`: no update to report.
+    UpToDate,
+    /// `1`: the check failed.
+    Error,
+    /// `10`: an update is available.
+    UpdateAvailable,
+    /// `11`: an update is available and the current version is yanked.
+    ///
+    /// Reserved for consumers: checks don't yet tell whether the current
+    /// version is yanked, so [`CheckOutcome::exit_status`] reports
+    /// [`UpdateAvailable`](Self::UpdateAvailable) instead.
+    UpdateAvailableCurrentYanked,
+    /// `20`: the check was skipped.
+    Skipped,
+}
+
+impl ExitCode {
+    /// The numeric exit code.
+    #[must_use]
+    pub const fn as_i32(self) -> i32 {
+        match self {
+            Self::UpToDate => 0,
+            Self::Error => 1,
+            Self::UpdateAvailable => 10,
+            Self::UpdateAvailableCurrentYanked => 11,
+            Self::Skipped => 20,
+        }
+    }
+
+    /// The status an exit code stands for, or `None` if it isn't one of the
+    /// codes above.
+    #[must_use]
+    pub const fn from_i32(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(Self::UpToDate),
+            1 => Some(Self::Error),
+            10 => Some(Self::UpdateAvailable),
+            11 => Some(Self::UpdateAvailableCurrentYanked),
+            20 => Some(Self::Skipped),
+            _ => None,
+        }
+    }
+}
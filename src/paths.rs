@@ -0,0 +1,105 @@
+//! Resolution of the platform cache and state directories.
+//!
+//! Directories are resolved from environment variables read through the
+//! [`Paths`] trait rather than from the process environment directly, so the
+//! full resolution matrix can be tested in-process for every platform without
+//! mutating process-global state that other tests read concurrently.
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// The platforms with distinct directory conventions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Os {
+    Linux,
+    Macos,
+    Windows,
+    Other,
+}
+
+impl Os {
+    /// The platform this crate was compiled for.
+    pub const CURRENT: Self = if cfg!(target_os = "linux") {
+        Self::Linux
+    } else if cfg!(target_os = "macos") {
+        Self::Macos
+    } else if cfg!(target_os = "windows") {
+        Self::Windows
+    } else {
+        Self::Other
+    };
+}
+
+/// Where directories are resolved from: the process environment in
+/// production, a fixed map in tests.
+pub trait Paths {
+    /// Look up an environment variable.
+    fn var_os(&self, key: &str) -> Option<OsString>;
+
+    /// The user's home directory, from `HOME`.
+    fn home(&self) -> Option<PathBuf> {
+        self.var_os("HOME")
+            .filter(|home| !home.is_empty())
+            .map(PathBuf::from)
+    }
+
+    /// An XDG base directory variable, ignored when empty or relative as the
+    /// specification requires.
+    ///
+    /// Checks for a root rather than [`Path::is_absolute`](std::path::Path::is_absolute)
+    /// so the Linux rules resolve the same when tested on Windows.
+    fn xdg(&self, key: &str) -> Option<PathBuf> {
+        self.var_os(key)
+            .map(PathBuf::from)
+            .filter(|dir| dir.has_root())
+    }
+
+    /// The user cache directory on `os`.
+    ///
+    /// - **Linux**: `$XDG_CACHE_HOME` or `$HOME/.cache`
+    /// - **macOS**: `$HOME/Library/Caches`
+    /// - **Windows**: `%LOCALAPPDATA%`
+    fn cache_dir(&self, os: Os) -> Option<PathBuf> {
+        match os {
+            Os::Linux => self
+                .xdg("XDG_CACHE_HOME")
+                .or_else(|| Some(self.home()?.join(".cache"))),
+            Os::Macos => Some(self.home()?.join("Library/Caches")),
+            Os::Windows => self.local_app_data(),
+            Os::Other => None,
+        }
+    }
+
+    /// The user state directory on `os`.
+    ///
+    /// - **Linux**: `$XDG_STATE_HOME` or `$HOME/.local/state`
+    /// - **macOS**: `$HOME/Library/Application Support`
+    /// - **Windows**: `%LOCALAPPDATA%`
+    fn state_dir(&self, os: Os) -> Option<PathBuf> {
+        match os {
+            Os::Linux => self
+                .xdg("XDG_STATE_HOME")
+                .or_else(|| Some(self.home()?.join(".local/state"))),
+            Os::Macos => Some(self.home()?.join("Library/Application Support")),
+            Os::Windows => self.local_app_data(),
+            Os::Other => None,
+        }
+    }
+
+    /// `%LOCALAPPDATA%`, if set.
+    fn local_app_data(&self) -> Option<PathBuf> {
+        self.var_os("LOCALAPPDATA")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+    }
+}
+
+/// The process environment.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct System;
+
+impl Paths for System {
+    fn var_os(&self, key: &str) -> Option<OsString> {
+        std::env::var_os(key)
+    }
+}
@@ -0,0 +1,360 @@
+//! Certificate pinning for the `rustls` feature: a rustls configuration
+//! whose verifier also requires a [pinned](crate::UpdateChecker::pin_certificates)
+//! key in the server's chain, and a ureq connector that secures connections
+//! with it.
+//!
+//! ureq builds its rustls configuration from its own settings, which offer
+//! no way to check the chain, so pinned agents connect through the
+//! connectors of ureq's default chain followed by [`PinnedConnector`]. The
+//! async checker hands the same configuration to reqwest.
+
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, StreamOwned};
+use ureq::unversioned::transport::{
+    Buffers, ConnectionDetails, Connector, Either, LazyBuffers, NextTimeout, Transport,
+    TransportAdapter,
+};
+
+use crate::{Error, ErrorKind, PIN_MISMATCH_PREFIX, Spki256, TlsSettings, TlsVersion, http_error};
+
+/// The rustls configuration for `tls`, which has pins: its root
+/// certificates, or else the bundled Mozilla roots ureq trusts by default,
+/// its minimum version, and a verifier that checks the pins.
+pub fn client_config(tls: &TlsSettings) -> Result<ClientConfig, Error> {
+    let mut provider = rustls::crypto::ring::default_provider();
+    if tls.min_version() == Some(TlsVersion::Tls1_3) {
+        provider
+            .cipher_suites
+            .retain(|suite| matches!(suite, rustls::SupportedCipherSuite::Tls13(_)));
+    }
+    let provider = Arc::new(provider);
+
+    let roots = if tls.has_roots() {
+        let mut roots = RootCertStore::empty();
+        roots.add_parsable_certificates(tls.root_ders().into_iter().map(CertificateDer::from));
+        roots
+    } else {
+        RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        }
+    };
+    let roots = Arc::new(roots);
+    let inner =
+        WebPkiServerVerifier::builder_with_provider(Arc::clone(&roots), Arc::clone(&provider))
+            .build()
+            .map_err(|e| Error::ConfigError(format!("the root certificates can't be used: {e}")))?;
+
+    let verifier = PinnedVerifier {
+        inner,
+        roots,
+        provider: Arc::clone(&provider),
+        pins: tls.pins().to_vec(),
+    };
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| Error::ConfigError(format!("TLS can't be configured: {e}")))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    Ok(config)
+}
+
+/// An [`Error::HttpError`] for a pin mismatch found in `e` or its sources,
+/// where the transport wrapped the verifier's error.
+pub fn mismatch(e: &(dyn std::error::Error + 'static)) -> Option<Error> {
+    let mut source = Some(e);
+    while let Some(e) = source {
+        let text = e.to_string();
+        if let Some((_, detail)) = text.split_once(PIN_MISMATCH_PREFIX) {
+            // rustls shows the verifier's error inside `Other(OtherError(..))`.
+            let detail = detail.split(')').next().unwrap_or(detail);
+            return Some(http_error(ErrorKind::PinMismatch, detail));
+        }
+        source = e.source();
+    }
+    None
+}
+
+/// The SHA-256 hash of `spki`.
+fn hash(spki: &[u8]) -> Spki256 {
+    // rustls exposes ring's SHA-256 only through its cipher suites.
+    let suite = rustls::crypto::ring::cipher_suite::TLS13_AES_128_GCM_SHA256;
+    let output = suite
+        .tls13()
+        .map(|suite| suite.common.hash_provider.hash(spki));
+    let mut hash = [0; 32];
+    if let Some(output) = output {
+        hash.copy_from_slice(output.as_ref());
+    }
+    Spki256::new(hash)
+}
+
+/// `contents` wrapped as a DER `SEQUENCE`, as a trust anchor's
+/// `SubjectPublicKeyInfo` is kept without its own.
+fn der_sequence(contents: &[u8]) -> Vec<u8> {
+    let mut der = vec![0x30];
+    if let Ok(length @ 0..0x80) = u8::try_from(contents.len()) {
+        der.push(length);
+    } else {
+        let length: Vec<_> = contents
+            .len()
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|&octet| octet == 0)
+            .collect();
+        der.push(0x80 | u8::try_from(length.len()).unwrap_or(0x7f));
+        der.extend(length);
+    }
+    der.extend_from_slice(contents);
+    der
+}
+
+/// Verifies certificates as rustls does, then requires one of `pins` among
+/// the keys of a verified chain: the server's own, an intermediate's, or
+/// the root's.
+#[derive(Debug)]
+struct PinnedVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    roots: Arc<RootCertStore>,
+    provider: Arc<CryptoProvider>,
+    pins: Vec<Spki256>,
+}
+
+/// The error for a server whose verified chain has none of the pinned keys.
+///
+/// rustls shows its [`Debug`] form, so that is the message too.
+struct PinMismatch(Vec<Spki256>);
+
+impl std::fmt::Display for PinMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let keys = self
+            .0
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(
+            f,
+            "{PIN_MISMATCH_PREFIX}the server's chain has keys {keys}, none of them pinned"
+        )
+    }
+}
+
+impl std::fmt::Debug for PinMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for PinMismatch {}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+
+        // Build the chain again, accepting only a path with a pinned key:
+        // pinning a key the server merely sent would let a compromised CA
+        // pass by sending it alongside its own chain.
+        let cert = webpki::EndEntityCert::try_from(end_entity)
+            .map_err(|e| rustls::Error::General(e.to_string()))?;
+        let seen = std::cell::RefCell::new(Vec::new());
+        let pinned = |path: &webpki::VerifiedPath<'_>| {
+            let keys: Vec<_> = std::iter::once(path.end_entity().subject_public_key_info())
+                .chain(
+                    path.intermediate_certificates()
+                        .map(webpki::Cert::subject_public_key_info),
+                )
+                .map(|spki| hash(&spki))
+                .chain(std::iter::once(hash(&der_sequence(
+                    &path.anchor().subject_public_key_info,
+                ))))
+                .collect();
+            if keys.iter().any(|key| self.pins.contains(key)) {
+                return Ok(());
+            }
+            let mut seen = seen.borrow_mut();
+            if seen.is_empty() {
+                *seen = keys;
+            }
+            Err(webpki::Error::UnknownIssuer)
+        };
+        cert.verify_for_usage(
+            self.provider.signature_verification_algorithms.all,
+            &self.roots.roots,
+            intermediates,
+            now,
+            webpki::KeyUsage::server_auth(),
+            None,
+            Some(&pinned),
+        )
+        .map_err(|_| {
+            let mismatch = PinMismatch(seen.take());
+            rustls::Error::InvalidCertificate(rustls::CertificateError::Other(rustls::OtherError(
+                Arc::new(mismatch),
+            )))
+        })?;
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// A ureq agent with `config` that connects like ureq's default connector,
+/// securing connections with `tls` instead of ureq's own rustls setup.
+pub fn agent(
+    config: ureq::config::Config,
+    tls: Arc<ClientConfig>,
+    resolver: crate::resolve::Resolver,
+) -> ureq::Agent {
+    use ureq::unversioned::transport::{ConnectProxyConnector, TcpConnector};
+
+    let connector = ();
+    #[cfg(feature = "socks")]
+    let connector = connector.chain(ureq::unversioned::transport::SocksConnector::default());
+    let connector = connector
+        .chain(ConnectProxyConnector::default())
+        .chain(TcpConnector::default())
+        .chain(PinnedConnector(tls));
+    ureq::Agent::with_parts(config, connector, resolver)
+}
+
+/// Wraps connections to `https://` URLs in TLS with its configuration, as
+/// ureq's `RustlsConnector` does with its own.
+#[derive(Debug)]
+struct PinnedConnector(Arc<ClientConfig>);
+
+impl<In: Transport> Connector<In> for PinnedConnector {
+    type Out = Either<In, PinnedTransport>;
+
+    fn connect(
+        &self,
+        details: &ConnectionDetails,
+        chained: Option<In>,
+    ) -> Result<Option<Self::Out>, ureq::Error> {
+        let Some(transport) = chained else {
+            return Ok(None);
+        };
+        if !details.needs_tls() || transport.is_tls() {
+            return Ok(Some(Either::A(transport)));
+        }
+
+        let name = details
+            .uri
+            .authority()
+            .map(|authority| authority.host().trim_matches(['[', ']']))
+            .and_then(|host| ServerName::try_from(host).ok())
+            .ok_or(ureq::Error::Tls("invalid server name for TLS"))?
+            .to_owned();
+        let connection = ClientConnection::new(Arc::clone(&self.0), name)?;
+        Ok(Some(Either::B(PinnedTransport {
+            buffers: LazyBuffers::new(
+                details.config.input_buffer_size(),
+                details.config.output_buffer_size(),
+            ),
+            stream: StreamOwned::new(connection, TransportAdapter::new(transport.boxed())),
+        })))
+    }
+}
+
+/// A connection secured by [`PinnedConnector`].
+struct PinnedTransport {
+    buffers: LazyBuffers,
+    stream: StreamOwned<ClientConnection, TransportAdapter>,
+}
+
+impl std::fmt::Debug for PinnedTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PinnedTransport").finish_non_exhaustive()
+    }
+}
+
+impl Transport for PinnedTransport {
+    fn buffers(&mut self) -> &mut dyn Buffers {
+        &mut self.buffers
+    }
+
+    fn transmit_output(&mut self, amount: usize, timeout: NextTimeout) -> Result<(), ureq::Error> {
+        self.stream.get_mut().set_timeout(timeout);
+        let output = self.buffers.output().get(..amount).unwrap_or_default();
+        self.stream.write_all(output)?;
+        Ok(())
+    }
+
+    fn await_input(&mut self, timeout: NextTimeout) -> Result<bool, ureq::Error> {
+        self.stream.get_mut().set_timeout(timeout);
+        let input = self.buffers.input_append_buf();
+        let amount = self.stream.read(input)?;
+        self.buffers.input_appended(amount);
+        Ok(amount > 0)
+    }
+
+    fn is_open(&mut self) -> bool {
+        self.stream.get_mut().get_mut().is_open()
+    }
+
+    fn is_tls(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trust_anchor_keys_are_rewrapped() {
+        let ca = CertificateDer::from(include_bytes!("../tests/fixtures/tls/ca.der").as_slice());
+        let anchor = webpki::anchor_from_trusted_cert(&ca).unwrap();
+        let cert = webpki::EndEntityCert::try_from(&ca).unwrap();
+        assert_eq!(
+            der_sequence(&anchor.subject_public_key_info),
+            cert.subject_public_key_info().as_ref()
+        );
+        assert_eq!(
+            hash(&der_sequence(&anchor.subject_public_key_info)),
+            Spki256::from_base64("eWbHKcSO/CeOjgXTiIiOrYKvfCdiPYKGe+eV81QY34w=").unwrap()
+        );
+
+        let long = vec![0; 300];
+        assert_eq!(der_sequence(&long)[..4], [0x30, 0x82, 0x01, 0x2c]);
+    }
+}
@@ -0,0 +1,179 @@
+//! Pools of HTTP clients: each checker's own, shared by its clones, and a
+//! process-wide one enabled by the `shared-pool` feature.
+//!
+//! Checks with identical connection settings share one client, and with it
+//! its TLS setup and idle connections. A pooled client is keyed by every
+//! setting it is built from, including the proxy environment it reads when
+//! built, so differently configured checks never share one.
+
+// Only the `rustls` and `async` transports have clients to pool.
+#![cfg_attr(
+    not(any(feature = "rustls", feature = "async")),
+    allow(dead_code, unused_imports, unused_mut)
+)]
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::{Hosts, TlsSettings};
+
+/// The most distinct configurations pooled per transport. Checkers with any
+/// other configuration build their own client, as without the feature.
+const MAX_CLIENTS: usize = 16;
+
+/// Proxy variables read when a client is built, including the `NO_PROXY`
+/// exceptions the offline probe ignores.
+const PROXY_ENV: &[&str] = &[
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+    "NO_PROXY",
+    "no_proxy",
+];
+
+/// Every setting a pooled client is built from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Key {
+    timeout: Duration,
+    bypass_proxy: bool,
+    proxy: Option<String>,
+    proxy_env: Vec<Option<String>>,
+    hosts: Hosts,
+    tls: TlsSettings,
+}
+
+impl Key {
+    /// The key for a client with `timeout` that bypasses any proxy if
+    /// `bypass_proxy` or else uses `proxy`, under the current proxy
+    /// environment, connects to `hosts` at their overridden addresses, and
+    /// secures connections with `tls`.
+    pub fn new(
+        timeout: Duration,
+        bypass_proxy: bool,
+        proxy: Option<String>,
+        hosts: Hosts,
+        tls: TlsSettings,
+    ) -> Self {
+        Self {
+            timeout,
+            bypass_proxy,
+            proxy,
+            proxy_env: PROXY_ENV
+                .iter()
+                .map(|var| std::env::var(var).ok())
+                .collect(),
+            hosts,
+            tls,
+        }
+    }
+}
+
+/// Clients of one transport, by configuration.
+#[derive(Debug)]
+pub struct Pool<C> {
+    clients: Mutex<HashMap<Key, C>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<C> Default for Pool<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> Pool<C> {
+    pub fn new() -> Self {
+        Self {
+            clients: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// How many times a client was built because none matched.
+    #[cfg(all(test, feature = "rustls", not(feature = "shared-pool")))]
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+impl<C: Clone> Pool<C> {
+    /// The pooled client for `key`, built with `build` and pooled if there
+    /// is none yet.
+    ///
+    /// Clients are built under the pool's lock, so checkers racing to
+    /// configure one share the first.
+    pub fn get<E>(&self, key: Key, build: impl FnOnce() -> Result<C, E>) -> Result<C, E> {
+        let mut clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(client) = clients.get(&key).cloned() {
+            drop(clients);
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(client);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let client = build()?;
+        if clients.len() < MAX_CLIENTS {
+            clients.insert(key, client.clone());
+        }
+        drop(clients);
+        Ok(client)
+    }
+
+    #[cfg(feature = "shared-pool")]
+    fn add_to(&self, stats: &mut PoolStats) {
+        stats.hits += self.hits.load(Ordering::Relaxed);
+        stats.misses += self.misses.load(Ordering::Relaxed);
+        stats.clients += self
+            .clients
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len();
+    }
+}
+
+/// Blocking clients, used with the `rustls` feature.
+#[cfg(all(feature = "shared-pool", feature = "rustls"))]
+pub static UREQ: std::sync::LazyLock<Pool<ureq::Agent>> = std::sync::LazyLock::new(Pool::new);
+
+/// Async clients.
+#[cfg(all(feature = "shared-pool", feature = "async"))]
+pub static REQWEST: std::sync::LazyLock<Pool<reqwest::Client>> =
+    std::sync::LazyLock::new(Pool::new);
+
+/// How checkers have drawn on the shared client pool, as returned by
+/// [`shared_pool_stats`].
+#[cfg(feature = "shared-pool")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PoolStats {
+    /// Requests that reused a pooled client. A blocking check takes a
+    /// client for each request it sends, an async check one for all of them.
+    pub hits: u64,
+    /// Requests that built a client because none matched their configuration.
+    pub misses: u64,
+    /// Clients currently pooled.
+    pub clients: usize,
+}
+
+/// Read the shared client pool's counters, across the blocking and async
+/// transports.
+///
+/// Only the `rustls` and `async` transports pool clients; `native-tls`
+/// requests open a connection each time, so they count as neither hits nor
+/// misses.
+#[cfg(feature = "shared-pool")]
+#[must_use]
+pub fn shared_pool_stats() -> PoolStats {
+    let mut stats = PoolStats::default();
+    #[cfg(feature = "rustls")]
+    UREQ.add_to(&mut stats);
+    #[cfg(feature = "async")]
+    REQWEST.add_to(&mut stats);
+    stats
+}
@@ -0,0 +1,65 @@
+//! A time-boxed lookup of the registry host, to fail fast when offline.
+//!
+//! Without a network, a request can wait out the whole timeout before giving
+//! up. Resolving the host first usually fails within milliseconds instead.
+//! The lookup runs on its own thread and is abandoned once [`BUDGET`] passes,
+//! so a slow resolver never delays a check by more than that.
+
+use std::net::ToSocketAddrs;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::{Error, ErrorKind, http_error, url_host};
+
+/// How long a check waits for the lookup before going ahead without it.
+pub const BUDGET: Duration = Duration::from_millis(200);
+
+/// Variables through which HTTP clients pick up a proxy.
+pub const PROXY_VARS: &[&str] = &[
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+];
+
+/// Resolve the host of `url`, failing only if the lookup fails within
+/// `budget`.
+///
+/// Skipped when a proxy is configured, since the proxy resolves the host.
+pub fn resolve(url: &str, budget: Duration) -> Result<(), Error> {
+    if crate::proxy::for_url(url).is_some() {
+        return Ok(());
+    }
+    let (host, port) = url_host(url);
+    let port = match port.map(str::parse) {
+        Some(Ok(port)) => port,
+        Some(Err(_)) => return Ok(()),
+        None if url.starts_with("http://") => 80,
+        None => 443,
+    };
+    let host = host.to_string();
+
+    let (sender, receiver) = mpsc::channel();
+    let lookup = thread::Builder::new()
+        .name("update-check-probe".into())
+        .spawn(move || {
+            let _ = sender.send(
+                (host.as_str(), port)
+                    .to_socket_addrs()
+                    .map(|mut addrs| addrs.next().is_some())
+                    .map_err(|e| http_error(ErrorKind::Dns, e)),
+            );
+        });
+    if lookup.is_err() {
+        return Ok(());
+    }
+
+    match receiver.recv_timeout(budget) {
+        Ok(Ok(true)) | Err(_) => Ok(()),
+        Ok(Ok(false)) => Err(http_error(ErrorKind::Dns, "host has no addresses")),
+        Ok(Err(e)) => Err(e),
+    }
+}
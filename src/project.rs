@@ -0,0 +1,189 @@
+//! Repository-local update-check policy.
+//!
+//! With [`project_config`](crate::UpdateChecker::project_config), each check
+//! looks for a `.tiny-update-check.toml` file in the starting directory and
+//! its ancestors, so a repository can restrict update notices for everyone
+//! working in it:
+//!
+//! ```toml
+//! # Never suggest 2.x here, and stay quiet in CI.
+//! version_req = "<2"
+//! notify_policy = "outside-ci"
+//! ```
+//!
+//! Only a flat subset of TOML is read: one `key = value` per line, with
+//! string and boolean values and `#` comments.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{Error, NoUpdateReason, Verdict};
+
+/// The file name searched for unless another is configured.
+pub const DEFAULT_FILE_NAME: &str = ".tiny-update-check.toml";
+
+/// When update notices may be shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NotifyPolicy {
+    /// Wherever checks run (the default).
+    #[default]
+    Always,
+    /// Only outside continuous integration, detected by the `CI` variable.
+    OutsideCi,
+}
+
+/// The policy a project config file sets.
+#[derive(Debug, Default)]
+pub struct ProjectConfig {
+    /// The file the policy was read from.
+    pub path: PathBuf,
+    /// Skip checks entirely.
+    pub disable: bool,
+    /// Only report latest versions matching this requirement.
+    pub version_req: Option<semver::VersionReq>,
+    /// When update notices may be shown.
+    pub notify_policy: NotifyPolicy,
+}
+
+impl ProjectConfig {
+    /// Why the policy skips checks, if it does.
+    pub fn skip_reason(&self) -> Option<NoUpdateReason> {
+        (self.disable || (self.notify_policy == NotifyPolicy::OutsideCi && in_ci())).then(|| {
+            NoUpdateReason::ProjectDisabled {
+                path: self.path.clone(),
+            }
+        })
+    }
+
+    /// Withhold an update whose latest version doesn't match `version_req`.
+    /// Versions that don't parse are left to the usual comparison rules.
+    pub fn restrict(&self, verdict: Verdict) -> Verdict {
+        match (verdict, &self.version_req) {
+            (Verdict::Update(info), Some(req))
+                if semver::Version::parse(info.latest.trim())
+                    .is_ok_and(|latest| !req.matches(&latest)) =>
+            {
+                Verdict::NoUpdate(NoUpdateReason::OutsideVersionReq {
+                    latest: info.latest,
+                    path: self.path.clone(),
+                })
+            }
+            (verdict, _) => verdict,
+        }
+    }
+}
+
+/// Whether checks run in continuous integration, as most CI services signal
+/// with a non-empty `CI` variable.
+pub fn in_ci() -> bool {
+    std::env::var("CI").is_ok_and(|value| !value.is_empty() && value != "0" && value != "false")
+}
+
+/// The policy for a check: the nearest `file_name` (by default
+/// [`DEFAULT_FILE_NAME`]) from `dir`, or from the current directory.
+pub fn load(dir: Option<&Path>, file_name: Option<&str>) -> Result<Option<ProjectConfig>, Error> {
+    let Some(start) = dir
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::current_dir().ok())
+    else {
+        return Ok(None);
+    };
+    discover(&start, file_name.unwrap_or(DEFAULT_FILE_NAME))
+}
+
+/// Find `file_name` in `start` or its nearest ancestor and read its policy.
+///
+/// Returns `Ok(None)` if no such file exists.
+fn discover(start: &Path, file_name: &str) -> Result<Option<ProjectConfig>, Error> {
+    let Some(path) = start
+        .ancestors()
+        .map(|dir| dir.join(file_name))
+        .find(|path| path.is_file())
+    else {
+        return Ok(None);
+    };
+    let text = fs::read_to_string(&path)
+        .map_err(|e| Error::ConfigError(format!("{}: {e}", path.display())))?;
+    parse(&text, path).map(Some)
+}
+
+/// Parse the policy in `text`, read from `path`.
+fn parse(text: &str, path: PathBuf) -> Result<ProjectConfig, Error> {
+    let error = |problem: String| Error::ConfigError(format!("{}: {problem}", path.display()));
+    let mut config = ProjectConfig::default();
+    let mut seen = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(error(format!(
+                "line {}: expected `key = value`",
+                number + 1
+            )));
+        };
+        let key = key.trim();
+        if seen.contains(&key) {
+            return Err(error(format!("key '{key}': set more than once")));
+        }
+        seen.push(key);
+        let value = parse_value(value.trim())
+            .ok_or_else(|| error(format!("key '{key}': expected a string or boolean")))?;
+        let invalid = |expected: &str| error(format!("key '{key}': expected {expected}"));
+        match (key, value) {
+            ("disable", Value::Bool(disable)) => config.disable = disable,
+            ("disable", _) => return Err(invalid("`true` or `false`")),
+            ("version_req", Value::String(req)) => {
+                let req = semver::VersionReq::parse(&req)
+                    .map_err(|e| error(format!("key '{key}': {e}")))?;
+                config.version_req = Some(req);
+            }
+            ("version_req", _) => return Err(invalid("a version requirement string")),
+            ("notify_policy", Value::String(policy)) => {
+                config.notify_policy = match policy.as_str() {
+                    "always" => NotifyPolicy::Always,
+                    "outside-ci" => NotifyPolicy::OutsideCi,
+                    _ => return Err(invalid("\"always\" or \"outside-ci\"")),
+                };
+            }
+            ("notify_policy", _) => return Err(invalid("\"always\" or \"outside-ci\"")),
+            _ => return Err(error(format!("key '{key}': unknown key"))),
+        }
+    }
+    config.path = path;
+    Ok(config)
+}
+
+/// A value in the supported subset of TOML.
+enum Value {
+    String(String),
+    Bool(bool),
+}
+
+/// Parse a value and any trailing comment, or `None` if it is malformed.
+fn parse_value(raw: &str) -> Option<Value> {
+    let Some(quoted) = raw.strip_prefix('"') else {
+        let bare = raw.split('#').next().unwrap_or_default().trim();
+        return match bare {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        };
+    };
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    loop {
+        match chars.next()? {
+            '"' => break,
+            '\\' => match chars.next()? {
+                '"' => value.push('"'),
+                '\\' => value.push('\\'),
+                _ => return None,
+            },
+            c => value.push(c),
+        }
+    }
+    let rest = chars.as_str().trim_start();
+    (rest.is_empty() || rest.starts_with('#')).then_some(Value::String(value))
+}
@@ -0,0 +1,117 @@
+//! Host overrides: addresses to connect to for chosen hosts in place of the
+//! ones DNS has, so tests can keep real hostnames in URLs while talking to a
+//! local server.
+//!
+//! The `rustls` transport resolves through [`Resolver`], which falls back to
+//! ureq's own resolver for other hosts; the async transport hands the
+//! overrides to reqwest.
+
+use std::net::SocketAddr;
+
+use crate::url_host;
+
+/// The address to connect to for each overridden host.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Hosts(Vec<(String, SocketAddr)>);
+
+impl Hosts {
+    /// Connect to `addr` for `host`, in place of any address set before.
+    pub fn insert(&mut self, host: &str, addr: SocketAddr) {
+        let host = host.to_ascii_lowercase();
+        self.0.retain(|(known, _)| *known != host);
+        self.0.push((host, addr));
+    }
+
+    /// The address to connect to for `host`, if overridden.
+    pub fn get(&self, host: &str) -> Option<SocketAddr> {
+        self.0
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(host))
+            .map(|&(_, addr)| addr)
+    }
+
+    /// Whether the host of `url` is overridden.
+    pub fn overrides(&self, url: &str) -> bool {
+        self.get(url_host(url).0).is_some()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Each overridden host and its address.
+    #[cfg(feature = "async")]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, SocketAddr)> {
+        self.0.iter().map(|(host, addr)| (host.as_str(), *addr))
+    }
+}
+
+/// A ureq resolver answering overridden hosts from [`Hosts`] and others
+/// with ureq's default resolver.
+///
+/// As with reqwest, a port in the URL is used in place of the override's,
+/// and an override with port 0 gets the scheme's default port.
+#[cfg(feature = "rustls")]
+#[derive(Debug)]
+pub struct Resolver {
+    hosts: Hosts,
+    fallback: ureq::unversioned::resolver::DefaultResolver,
+}
+
+#[cfg(feature = "rustls")]
+impl Resolver {
+    pub fn new(hosts: Hosts) -> Self {
+        Self {
+            hosts,
+            fallback: ureq::unversioned::resolver::DefaultResolver::default(),
+        }
+    }
+}
+
+#[cfg(feature = "rustls")]
+impl ureq::unversioned::resolver::Resolver for Resolver {
+    fn resolve(
+        &self,
+        uri: &ureq::http::Uri,
+        config: &ureq::config::Config,
+        timeout: ureq::unversioned::transport::NextTimeout,
+    ) -> Result<ureq::unversioned::resolver::ResolvedSocketAddrs, ureq::Error> {
+        let Some(mut addr) = uri
+            .host()
+            .and_then(|host| self.hosts.get(host.trim_matches(['[', ']'])))
+        else {
+            return self.fallback.resolve(uri, config, timeout);
+        };
+        if let Some(port) = uri.port_u16() {
+            addr.set_port(port);
+        } else if addr.port() == 0 {
+            addr.set_port(if uri.scheme_str() == Some("http") {
+                80
+            } else {
+                443
+            });
+        }
+        let mut addrs = self.empty();
+        addrs.push(addr);
+        Ok(addrs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hosts_are_matched_case_insensitively_and_replaced() {
+        let mut hosts = Hosts::default();
+        let first: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let second: SocketAddr = "127.0.0.1:2".parse().unwrap();
+        hosts.insert("Crates.io", first);
+        hosts.insert("index.crates.io", first);
+        assert_eq!(hosts.get("crates.IO"), Some(first));
+        hosts.insert("crates.io", second);
+        assert_eq!(hosts.get("crates.io"), Some(second));
+        assert!(hosts.overrides("https://crates.io/api/v1/crates/serde"));
+        assert!(!hosts.overrides("https://static.crates.io/"));
+    }
+}
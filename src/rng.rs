@@ -0,0 +1,132 @@
+//! Pluggable randomness for jittered behavior.
+//!
+//! Every randomized decision the checker makes draws from a single [`Rng`], so
+//! tests can substitute a fixed sequence and deployments can opt into
+//! reproducible per-machine behavior with [`XorShiftRng::per_machine`].
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A source of uniformly distributed random numbers.
+///
+/// Implementations need not be cryptographically secure.
+pub trait Rng {
+    /// Return the next number in `[0.0, 1.0)`.
+    fn next_f64(&mut self) -> f64;
+}
+
+/// A small xorshift64* generator, the default [`Rng`].
+///
+/// Fast and dependency-free, but not suitable for anything security-related.
+#[derive(Debug, Clone)]
+pub struct XorShiftRng {
+    state: u64,
+}
+
+impl XorShiftRng {
+    /// Create a generator that always produces the same sequence for `seed`.
+    #[must_use]
+    pub const fn seeded(seed: u64) -> Self {
+        // xorshift gets stuck at zero, so remap that one seed.
+        Self {
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    /// Create a generator seeded from the clock, process ID, and std's
+    /// per-process hash keys. This is the default.
+    #[must_use]
+    pub fn from_entropy() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        // Truncating the nanosecond count keeps its fast-changing low bits.
+        #[allow(clippy::cast_possible_truncation)]
+        let time = nanos as u64;
+        let keys = RandomState::new().hash_one(std::process::id());
+        Self::seeded(time ^ keys.rotate_left(32))
+    }
+
+    /// Create a generator seeded from a hash of the machine's hostname.
+    ///
+    /// Every process on the same machine makes the same random choices, while
+    /// different machines still spread out. Falls back to
+    /// [`XorShiftRng::from_entropy`] when no hostname can be determined.
+    #[must_use]
+    pub fn per_machine() -> Self {
+        hostname().map_or_else(Self::from_entropy, |name| {
+            Self::seeded(fnv1a(name.as_bytes()))
+        })
+    }
+
+    const fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+impl Rng for XorShiftRng {
+    fn next_f64(&mut self) -> f64 {
+        // The top 53 bits fill an f64 mantissa exactly; scale them by 2^-53.
+        #[allow(clippy::cast_precision_loss)]
+        let value = (self.next_u64() >> 11) as f64;
+        value * (1.0 / 9_007_199_254_740_992.0)
+    }
+}
+
+/// The checker's random source, shared between clones.
+///
+/// Wrapped so that the checkers can keep deriving `Debug` and `Clone`.
+#[derive(Clone)]
+pub struct SharedRng(Arc<Mutex<dyn Rng + Send>>);
+
+impl SharedRng {
+    pub fn new(rng: impl Rng + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(rng)))
+    }
+
+    pub fn next_f64(&self) -> f64 {
+        let mut rng = self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        rng.next_f64().clamp(0.0, 1.0)
+    }
+}
+
+impl Default for SharedRng {
+    fn default() -> Self {
+        Self::new(XorShiftRng::from_entropy())
+    }
+}
+
+impl std::fmt::Debug for SharedRng {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedRng")
+    }
+}
+
+fn hostname() -> Option<String> {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// FNV-1a, chosen over std's hashers because its output is stable across
+/// Rust releases, which keeps per-machine seeds and cache keys stable too.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
@@ -0,0 +1,58 @@
+//! A crate's latest version on a crates.io-compatible registry, as a
+//! [`VersionSource`] for composing with other sources.
+
+use super::{SourceContext, VersionSource};
+use crate::{
+    Error, ResponseFormat, check_crate_name, crate_api_url, default_registry_url,
+    validate_base_url, validate_crate_name,
+};
+
+/// A crate's latest version on a crates.io-compatible registry's web API,
+/// as a [`VersionSource`].
+///
+/// This is the lookup a checker makes by default, for composing with other
+/// sources or wrapping in a test double. Its request goes out through the
+/// checker it is plugged into, with that checker's proxy, TLS settings,
+/// headers and registry credentials.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CratesIoSource {
+    crate_name: String,
+    registry_url: String,
+}
+
+impl CratesIoSource {
+    /// The crate named `crate_name` on crates.io.
+    #[must_use]
+    pub fn new(crate_name: impl Into<String>) -> Self {
+        Self {
+            crate_name: crate_name.into(),
+            registry_url: default_registry_url(),
+        }
+    }
+
+    /// Set the base URL of the registry. Defaults to `https://crates.io`,
+    /// or `TINY_UPDATE_CHECK_BASE_URL` if set.
+    #[must_use]
+    pub fn registry_url(mut self, url: impl Into<String>) -> Self {
+        self.registry_url = url.into();
+        self
+    }
+}
+
+#[cfg(not(tiny_update_check_network_disabled))]
+impl VersionSource for CratesIoSource {
+    fn latest(&self, context: &SourceContext<'_>) -> Result<String, Error> {
+        validate_crate_name(&self.crate_name, false)?;
+        let url = self.id();
+        validate_base_url(&url)?;
+        let version = context.fetch(&url, ResponseFormat::Api, None)?;
+        if let Some(ref latest) = *context.answer.borrow() {
+            check_crate_name(latest, &self.crate_name)?;
+        }
+        Ok(version)
+    }
+
+    fn id(&self) -> String {
+        crate_api_url(&self.registry_url, &self.crate_name)
+    }
+}
@@ -0,0 +1,207 @@
+//! A cargo-dist release, described by its `dist-manifest.json`.
+
+use super::{ReleaseSource, SourceContext, VersionSource};
+#[cfg(doc)]
+use crate::{DetailedUpdateInfo, UpdateChecker};
+use crate::{Error, ErrorKind, Latest, parse_response};
+
+/// A cargo-dist `dist-manifest.json`, describing a release and the files it
+/// publishes.
+///
+/// Passed to [`UpdateChecker::dist_manifest`]. The latest version is read
+/// from the manifest's `announcement_tag`, or else its release's
+/// `app_version`, and the release's artifacts are listed in
+/// [`DetailedUpdateInfo::artifacts`]. Only fields that every manifest
+/// schema since cargo-dist 0.0 shares are read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistManifestSource {
+    pub(super) url: String,
+    pub(super) app: Option<String>,
+}
+
+impl DistManifestSource {
+    /// The manifest at `url`, typically the latest release's asset, such as
+    /// `https://github.com/my-org/my-tool/releases/latest/download/dist-manifest.json`.
+    #[must_use]
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            app: None,
+        }
+    }
+
+    /// For a manifest announcing several apps, take the version and
+    /// artifacts of the app named `app`.
+    #[must_use]
+    pub fn app(mut self, app: impl Into<String>) -> Self {
+        self.app = Some(app.into());
+        self
+    }
+
+    /// Explain a `404 Not Found`.
+    pub(crate) fn explain(&self, e: Error) -> Error {
+        if e.kind() != ErrorKind::NotFound {
+            return e;
+        }
+        e.noted(&format!(": dist manifest {} was not found", self.url))
+    }
+}
+
+/// A file a cargo-dist release publishes, as its
+/// [manifest](DistManifestSource) lists it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DistArtifact {
+    /// The file name, such as `my-tool-x86_64-unknown-linux-gnu.tar.xz`.
+    pub name: String,
+    /// What the file is, such as `executable-zip`, `installer` or
+    /// `checksum`, if the manifest says.
+    pub kind: Option<String>,
+    /// The targets the file is for, empty if it isn't target-specific.
+    pub target_triples: Vec<String>,
+}
+
+#[cfg(not(tiny_update_check_network_disabled))]
+impl VersionSource for DistManifestSource {
+    fn latest(&self, context: &SourceContext<'_>) -> Result<String, Error> {
+        context.fetch_release(&ReleaseSource::Dist(self.clone()))
+    }
+
+    fn id(&self) -> String {
+        self.url.clone()
+    }
+}
+
+/// Parse a cargo-dist manifest, taking the version from its announcement
+/// tag, or else from its release's `app_version`, with the release's
+/// artifacts. With `app`, the version and artifacts are that app's.
+///
+/// Older manifests list each release's artifacts inline; newer ones list
+/// their names, described in a top-level `artifacts` map.
+pub fn parse_dist_manifest(body: &str, app: Option<&str>) -> Result<Latest, Error> {
+    let json = parse_response(body)?;
+    let releases = json
+        .get("releases")
+        .and_then(serde_json::Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let release = match app {
+        Some(app) => Some(
+            releases
+                .iter()
+                .find(|release| {
+                    release.get("app_name").and_then(serde_json::Value::as_str) == Some(app)
+                })
+                .ok_or_else(|| Error::ParseError(format!("manifest has no release of '{app}'")))?,
+        ),
+        None => releases.first(),
+    };
+    let app_version = release
+        .and_then(|release| release.get("app_version"))
+        .and_then(serde_json::Value::as_str);
+    let announced = json
+        .get("announcement_tag")
+        .and_then(serde_json::Value::as_str)
+        .and_then(tag_version);
+    let version = match app {
+        Some(_) => app_version.or(announced),
+        None => announced.or(app_version),
+    }
+    .ok_or_else(|| {
+        Error::ParseError("manifest has no 'announcement_tag' or 'app_version'".to_string())
+    })?;
+
+    let described = json.get("artifacts");
+    let artifacts = release
+        .and_then(|release| release.get("artifacts"))
+        .and_then(serde_json::Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|artifact| {
+            let Some(name) = artifact.as_str() else {
+                return dist_artifact(artifact);
+            };
+            let described = described.and_then(|described| described.get(name));
+            Some(
+                described
+                    .and_then(dist_artifact)
+                    .unwrap_or_else(|| DistArtifact {
+                        name: name.to_string(),
+                        kind: None,
+                        target_triples: Vec::new(),
+                    }),
+            )
+        })
+        .collect();
+    Ok(Latest {
+        version: version.to_string(),
+        versions: None,
+        artifacts,
+        updated_at: None,
+        registry_url: None,
+        race_winner: None,
+        max_age: None,
+        crate_name: None,
+        stale: false,
+        offline: false,
+        #[cfg(feature = "response-body")]
+        response_body: Some(body.to_string()),
+        #[cfg(not(feature = "response-body"))]
+        response_body: None,
+    })
+}
+
+/// A dist manifest's description of an artifact.
+fn dist_artifact(json: &serde_json::Value) -> Option<DistArtifact> {
+    let text = |field: &str| json.get(field)?.as_str().map(String::from);
+    Some(DistArtifact {
+        name: text("name")?,
+        kind: text("kind"),
+        target_triples: json
+            .get("target_triples")
+            .and_then(serde_json::Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|target| target.as_str().map(String::from))
+            .collect(),
+    })
+}
+
+/// The version in a release tag such as `v1.2.0`, `1.2.0` or
+/// `my-tool-v1.2.0`: the first valid version starting at a digit that
+/// follows the start of the tag, a `v`, or punctuation.
+fn tag_version(tag: &str) -> Option<&str> {
+    let mut previous = None;
+    for (i, c) in tag.char_indices() {
+        let boundary =
+            previous.is_none_or(|p: char| matches!(p, 'v' | 'V') || !p.is_alphanumeric());
+        if c.is_ascii_digit() && boundary {
+            let rest = tag.get(i..)?;
+            if semver::Version::parse(rest).is_ok() {
+                return Some(rest);
+            }
+        }
+        previous = Some(c);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_version_finds_the_version_in_a_tag() {
+        for (tag, version) in [
+            ("v1.2.0", Some("1.2.0")),
+            ("1.2.0-rc.1", Some("1.2.0-rc.1")),
+            ("my-tool-v1.2.0", Some("1.2.0")),
+            ("tool2/3.0.0", Some("3.0.0")),
+            ("tool2", None),
+        ] {
+            assert_eq!(tag_version(tag), version, "{tag}");
+        }
+    }
+}
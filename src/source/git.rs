@@ -0,0 +1,262 @@
+//! Versions from a repository's tags, listed with `git ls-remote` or from
+//! a JSON tags endpoint.
+//!
+//! Tags are the one thing every forge serves alike, so a remote's tags can
+//! stand in for a releases API. `git` is run without a terminal or askpass
+//! helper, so an inaccessible remote fails instead of prompting, and is
+//! killed if it outlives the check's timeout.
+
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{ReleaseSource, SourceContext, VersionSource, latest_tag};
+#[cfg(doc)]
+use crate::UpdateChecker;
+use crate::{
+    Error, ErrorKind, Latest, ResponseFormat, SuggestStrategy, http_error, parse_response, probe,
+    status_error, too_large_error,
+};
+
+/// A repository whose tags are the source of versions, for forges without a
+/// releases API such as Gitea, Forgejo or a bare Git server.
+///
+/// Passed to [`UpdateChecker::git_tags`]. Tags are listed with
+/// `git ls-remote` or from a JSON tags endpoint. Each is stripped of the
+/// [tag prefix](Self::tag_prefix) and parsed as a version; the highest is
+/// the latest, and tags that don't parse are skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitTagsSource {
+    location: TagLocation,
+    pub(super) prefix: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TagLocation {
+    /// A remote repository, listed with `git ls-remote`.
+    Remote(String),
+    /// An endpoint answering with a JSON list of tags.
+    Endpoint(String),
+}
+
+impl GitTagsSource {
+    /// List the tags of the remote repository at `url` by running
+    /// `git ls-remote`, which must be on `PATH`.
+    ///
+    /// The remote is read anonymously over `https://`, or plain `http://`
+    /// where [allowed](UpdateChecker::allow_insecure_http); `git` is never
+    /// allowed to prompt for credentials. It is killed if it runs longer
+    /// than the checker's [timeout](UpdateChecker::timeout).
+    #[must_use]
+    pub fn remote(url: impl Into<String>) -> Self {
+        Self {
+            location: TagLocation::Remote(url.into()),
+            prefix: None,
+        }
+    }
+
+    /// Fetch the tags from `url`, which answers with a JSON list of tag
+    /// names or of objects with a `name` field, as the Gitea, Forgejo,
+    /// GitHub and GitLab tags APIs do.
+    #[must_use]
+    pub fn endpoint(url: impl Into<String>) -> Self {
+        Self {
+            location: TagLocation::Endpoint(url.into()),
+            prefix: None,
+        }
+    }
+
+    /// Only consider tags starting with `prefix`, and parse the rest of
+    /// the tag as the version, e.g. `my-tool-v` for tags like
+    /// `my-tool-v1.2.0` in a repository that tags several tools.
+    ///
+    /// Without one, every tag is considered, minus any leading `v`.
+    #[must_use]
+    pub fn tag_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// The URL the tags are listed from, and the format it's answered in.
+    pub(crate) fn lookup(&self) -> (String, ResponseFormat) {
+        match self.location {
+            TagLocation::Remote(ref url) => (url.clone(), ResponseFormat::GitRefs),
+            TagLocation::Endpoint(ref url) => (url.clone(), ResponseFormat::TagList),
+        }
+    }
+
+    /// Reject a remote that `git` would read over anything but HTTP(S).
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        let TagLocation::Remote(ref url) = self.location else {
+            return Ok(());
+        };
+        let http = ["https://", "http://"].iter().any(|scheme| {
+            url.get(..scheme.len())
+                .is_some_and(|s| s.eq_ignore_ascii_case(scheme))
+        });
+        if http {
+            Ok(())
+        } else {
+            Err(Error::ConfigError(format!(
+                "Git remote '{url}' must be an https:// URL"
+            )))
+        }
+    }
+
+    /// Explain a `404 Not Found`, also reported when no tag holds a version.
+    pub(crate) fn explain(&self, e: Error) -> Error {
+        if e.kind() != ErrorKind::NotFound {
+            return e;
+        }
+        let (url, _) = self.lookup();
+        let tags = self.prefix.as_ref().map_or_else(
+            || "version tags".to_string(),
+            |p| format!("tags starting with '{p}'"),
+        );
+        e.noted(&format!(
+            ": repository {url} was not found or has no {tags}"
+        ))
+    }
+}
+
+#[cfg(not(tiny_update_check_network_disabled))]
+impl VersionSource for GitTagsSource {
+    fn latest(&self, context: &SourceContext<'_>) -> Result<String, Error> {
+        context.fetch_release(&ReleaseSource::Tags(self.clone()))
+    }
+
+    fn id(&self) -> String {
+        self.lookup().0
+    }
+}
+
+/// Parse `git ls-remote --tags` output with [`latest_tag`].
+///
+/// Each line is an object ID and a ref name such as `refs/tags/v1.0.0`,
+/// listed again with a `^{}` suffix for the commit an annotated tag points
+/// to.
+pub fn parse_git_refs(
+    body: &str,
+    prefix: Option<&str>,
+    suggest: SuggestStrategy,
+) -> Result<Latest, Error> {
+    let tags = body.lines().filter_map(|line| {
+        let (_, name) = line.split_once('\t')?;
+        let tag = name.strip_prefix("refs/tags/")?;
+        Some(tag.strip_suffix("^{}").unwrap_or(tag))
+    });
+    latest_tag(tags, prefix, suggest, body)
+}
+
+/// Parse a JSON list of tags with [`latest_tag`], each a tag name or an
+/// object naming it in a `name` field.
+pub fn parse_tag_list(
+    body: &str,
+    prefix: Option<&str>,
+    suggest: SuggestStrategy,
+) -> Result<Latest, Error> {
+    let json = parse_response(body)?;
+    let list = json
+        .as_array()
+        .ok_or_else(|| Error::ParseError("expected a list of tags".to_string()))?;
+    let tags = list
+        .iter()
+        .filter_map(|tag| tag.as_str().or_else(|| tag.get("name")?.as_str()));
+    latest_tag(tags, prefix, suggest, body)
+}
+
+/// How often a running `git` is polled for exit.
+const POLL: Duration = Duration::from_millis(10);
+
+/// The output of `git ls-remote --tags url`, at most `limit` bytes, within
+/// `timeout`, through `proxy` if given and directly otherwise.
+///
+/// A missing `git`, a remote that doesn't exist, and a timeout are reported
+/// as the [`ErrorKind`]s of the equivalent HTTP failures.
+pub fn ls_remote(
+    url: &str,
+    timeout: Duration,
+    proxy: Option<&str>,
+    limit: usize,
+) -> Result<Vec<u8>, Error> {
+    let mut command = Command::new("git");
+    command
+        .args(["ls-remote", "--tags", "--", url])
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env_remove("GIT_ASKPASS")
+        .env_remove("SSH_ASKPASS")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    for var in probe::PROXY_VARS.iter().chain(&["NO_PROXY", "no_proxy"]) {
+        command.env_remove(var);
+    }
+    if let Some(proxy) = proxy {
+        // The variables libcurl reads, as `git` passes them on.
+        for var in ["http_proxy", "https_proxy", "all_proxy"] {
+            command.env(var, proxy);
+        }
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|e| http_error(ErrorKind::Http, format!("failed to run git: {e}")))?;
+
+    // Read on another thread so a full pipe never stalls `git`.
+    let stdout = child.stdout.take().map(|stdout| {
+        thread::spawn(move || {
+            let mut bytes = Vec::new();
+            stdout
+                .take(limit as u64 + 1)
+                .read_to_end(&mut bytes)
+                .map(|_| bytes)
+        })
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => thread::sleep(POLL),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(http_error(
+                    ErrorKind::Timeout,
+                    format!("git ls-remote {url} took longer than {timeout:?}"),
+                ));
+            }
+            Err(e) => return Err(http_error(ErrorKind::Http, e)),
+        }
+    };
+
+    let bytes = match stdout.map(thread::JoinHandle::join) {
+        Some(Ok(Ok(bytes))) => bytes,
+        Some(Ok(Err(e))) => return Err(http_error(ErrorKind::Http, e)),
+        _ => Vec::new(),
+    };
+    if bytes.len() > limit {
+        return Err(too_large_error(limit));
+    }
+    if status.success() {
+        return Ok(bytes);
+    }
+
+    let mut stderr = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let _ = pipe.read_to_string(&mut stderr);
+    }
+    let detail = stderr
+        .lines()
+        .map(|line| line.trim_start_matches("fatal: ").trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or("no output")
+        .to_string();
+    if detail.contains("not found") || detail.contains("does not appear to be a git repository") {
+        return Err(status_error(404));
+    }
+    Err(http_error(
+        ErrorKind::Http,
+        format!("git ls-remote failed ({status}): {detail}"),
+    ))
+}
@@ -0,0 +1,284 @@
+//! GitHub releases, read from the API or the releases Atom feed.
+
+use super::{ReleaseSource, SourceContext, VersionSource, release_latest};
+#[cfg(doc)]
+use crate::UpdateChecker;
+use crate::{
+    Error, ErrorKind, Latest, ResponseFormat, SuggestStrategy, parse_response, status_error,
+};
+
+/// The GitHub API queried when no other is configured.
+pub(super) const DEFAULT_GITHUB_API_URL: &str = "https://api.github.com";
+
+/// The GitHub web interface serving release feeds when no other is
+/// configured.
+const DEFAULT_GITHUB_WEB_URL: &str = "https://github.com";
+
+/// A GitHub repository whose releases are the source of versions.
+///
+/// Passed to [`UpdateChecker::github_releases`]. The latest version is the
+/// tag of the repository's latest release, which GitHub picks among
+/// published releases that are neither drafts nor pre-releases.
+/// Requests are anonymous unless a [token](Self::token) is set or the
+/// `GITHUB_TOKEN` environment variable holds one.
+#[derive(Clone, PartialEq, Eq)]
+pub struct GitHubSource {
+    owner: String,
+    repo: String,
+    api_url: String,
+    token: Option<String>,
+    feed: bool,
+    web_url: String,
+}
+
+impl GitHubSource {
+    /// The repository `owner/repo` on github.com.
+    #[must_use]
+    pub fn new(owner: impl Into<String>, repo: impl Into<String>) -> Self {
+        Self {
+            owner: owner.into(),
+            repo: repo.into(),
+            api_url: DEFAULT_GITHUB_API_URL.to_string(),
+            token: None,
+            feed: false,
+            web_url: DEFAULT_GITHUB_WEB_URL.to_string(),
+        }
+    }
+
+    /// Set the base URL of the GitHub API. Defaults to
+    /// `https://api.github.com`; GitHub Enterprise Server serves it at
+    /// `https://HOST/api/v3`.
+    #[must_use]
+    pub fn api_url(mut self, url: impl Into<String>) -> Self {
+        self.api_url = url.into();
+        self
+    }
+
+    /// Read the repository's public releases Atom feed instead of the API.
+    /// Defaults to `false`.
+    ///
+    /// The feed needs no authentication and isn't subject to the API's
+    /// rate limit, but only works for public repositories. It lists recent
+    /// releases including pre-releases; the highest version among them is
+    /// the latest, so pre-releases follow the
+    /// [pre-release policy](UpdateChecker::prerelease_policy) as registry
+    /// versions do. A [token](Self::token) isn't sent to the feed.
+    #[must_use]
+    pub const fn releases_feed(mut self, feed: bool) -> Self {
+        self.feed = feed;
+        self
+    }
+
+    /// Set the base URL of GitHub's web interface, which serves the
+    /// [releases feed](Self::releases_feed). Defaults to
+    /// `https://github.com`.
+    #[must_use]
+    pub fn web_url(mut self, url: impl Into<String>) -> Self {
+        self.web_url = url.into();
+        self
+    }
+
+    /// Authenticate requests with this token, sent as
+    /// `Authorization: Bearer <token>`. Authenticated requests get a much
+    /// higher rate limit, and can see private repositories the token has
+    /// access to.
+    ///
+    /// Without one, the `GITHUB_TOKEN` environment variable is used if set.
+    /// The token never appears in `Debug` output or error messages.
+    #[must_use]
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// The token to authenticate with: the configured one, or else a
+    /// non-empty `GITHUB_TOKEN`.
+    fn bearer(&self) -> Option<String> {
+        self.token
+            .clone()
+            .or_else(|| std::env::var("GITHUB_TOKEN").ok())
+            .filter(|token| !token.is_empty())
+    }
+
+    /// The GitHub API's headers, and the token to authenticate with, or the
+    /// feed's.
+    pub(crate) fn headers(&self) -> Vec<(&'static str, String)> {
+        if self.feed {
+            return vec![("Accept", "application/atom+xml".to_string())];
+        }
+        let mut headers = vec![
+            ("Accept", "application/vnd.github+json".to_string()),
+            ("X-GitHub-Api-Version", "2022-11-28".to_string()),
+        ];
+        if let Some(token) = self.bearer() {
+            headers.push(("Authorization", format!("Bearer {token}")));
+        }
+        headers
+    }
+
+    /// The API URL of the repository's latest release.
+    pub(crate) fn latest_release_url(&self) -> String {
+        let api_url = self.api_url.trim_end_matches('/');
+        format!(
+            "{api_url}/repos/{}/{}/releases/latest",
+            self.owner, self.repo
+        )
+    }
+
+    /// The URL of the repository's latest release, and the format it's
+    /// answered in.
+    pub(crate) fn lookup(&self) -> (String, ResponseFormat) {
+        if self.feed {
+            let web_url = self.web_url.trim_end_matches('/');
+            let url = format!("{web_url}/{}/{}/releases.atom", self.owner, self.repo);
+            (url, ResponseFormat::GitHubFeed)
+        } else {
+            (self.latest_release_url(), ResponseFormat::GitHubRelease)
+        }
+    }
+
+    /// Reject an owner or repository name that isn't a single safe path
+    /// segment.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        for (what, name) in [("owner", &self.owner), ("repository", &self.repo)] {
+            let valid = !name.is_empty()
+                && name.len() <= 100
+                && name != "."
+                && name != ".."
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+            if !valid {
+                return Err(Error::ConfigError(format!(
+                    "invalid GitHub {what} name: '{name}'"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Explain a `404 Not Found`, which GitHub also answers for a repository
+    /// without releases.
+    pub(crate) fn explain(&self, e: Error) -> Error {
+        if e.kind() != ErrorKind::NotFound {
+            return e;
+        }
+        e.noted(&format!(
+            ": GitHub repository {}/{} was not found or has no published release",
+            self.owner, self.repo
+        ))
+    }
+}
+
+impl std::fmt::Debug for GitHubSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GitHubSource")
+            .field("owner", &self.owner)
+            .field("repo", &self.repo)
+            .field("api_url", &self.api_url)
+            .field("token", &self.token.as_ref().map(|_| "***"))
+            .field("feed", &self.feed)
+            .field("web_url", &self.web_url)
+            .finish()
+    }
+}
+
+#[cfg(not(tiny_update_check_network_disabled))]
+impl VersionSource for GitHubSource {
+    fn latest(&self, context: &SourceContext<'_>) -> Result<String, Error> {
+        context.fetch_release(&ReleaseSource::GitHub(self.clone()))
+    }
+
+    fn id(&self) -> String {
+        self.lookup().0
+    }
+}
+
+/// Parse a GitHub release, taking its tag without a leading `v` as the
+/// latest version and its publication time as the update time.
+pub fn parse_release(body: &str) -> Result<Latest, Error> {
+    release_latest(&parse_response(body)?, "published_at", body)
+}
+
+/// Parse a GitHub releases Atom feed, taking the highest version among its
+/// entries as the latest, or the newest entry's if none is semver.
+///
+/// An entry's tag is read from its `<id>`, which ends in the tag, or else
+/// its `<title>`. A feed without entries, for a repository without
+/// releases, is reported as `404 Not Found` like the API does.
+pub fn parse_feed(body: &str, suggest: SuggestStrategy) -> Result<Latest, Error> {
+    let mut entries = Vec::new();
+    let mut rest = body;
+    while let Some((entry, after)) = xml_element(rest, "entry") {
+        rest = after;
+        let tag = xml_element(entry, "id")
+            .and_then(|(id, _)| id.split_once("Repository/")?.1.split_once('/'))
+            .map(|(_, tag)| xml_text(tag))
+            .or_else(|| xml_element(entry, "title").map(|(title, _)| xml_text(title)))
+            .filter(|tag| !tag.is_empty())
+            .ok_or_else(|| Error::ParseError("feed entry has no tag or title".to_string()))?;
+        let version = tag.strip_prefix(['v', 'V']).unwrap_or(&tag).to_string();
+        let updated = xml_element(entry, "updated").map(|(updated, _)| xml_text(updated));
+        entries.push((version, updated));
+    }
+
+    let latest = entries
+        .iter()
+        .filter_map(|entry| Some((semver::Version::parse(&entry.0).ok()?, entry)))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, entry)| entry)
+        .or_else(|| entries.first())
+        .cloned()
+        .ok_or_else(|| status_error(404))?;
+    Ok(Latest {
+        version: latest.0,
+        versions: match suggest {
+            SuggestStrategy::Newest => None,
+            SuggestStrategy::SafestFirst => {
+                Some(entries.into_iter().map(|(version, _)| version).collect())
+            }
+        },
+        artifacts: Vec::new(),
+        updated_at: latest.1,
+        registry_url: None,
+        race_winner: None,
+        max_age: None,
+        crate_name: None,
+        stale: false,
+        offline: false,
+        #[cfg(feature = "response-body")]
+        response_body: Some(body.to_string()),
+        #[cfg(not(feature = "response-body"))]
+        response_body: None,
+    })
+}
+
+/// The content of the first `<name>` element in `xml`, and the text after
+/// it. Attributes are skipped and nested elements left unparsed, which is
+/// all a release feed needs.
+fn xml_element<'a>(xml: &'a str, name: &str) -> Option<(&'a str, &'a str)> {
+    let open = format!("<{name}");
+    let close = format!("</{name}>");
+    let mut rest = xml;
+    loop {
+        let after = rest.get(rest.find(&open)? + open.len()..)?;
+        // Skip elements whose names merely start with `name`.
+        if after.starts_with(|c: char| c == '>' || c.is_ascii_whitespace()) {
+            let content = after.get(after.find('>')? + 1..)?;
+            let end = content.find(&close)?;
+            return Some((content.get(..end)?, content.get(end + close.len()..)?));
+        }
+        rest = after;
+    }
+}
+
+/// Element text with surrounding whitespace trimmed and XML's predefined
+/// entities decoded.
+fn xml_text(text: &str) -> String {
+    text.trim()
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
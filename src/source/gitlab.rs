@@ -0,0 +1,126 @@
+//! GitLab releases, read from the releases API.
+
+use super::{ReleaseSource, SourceContext, VersionSource, release_latest};
+#[cfg(doc)]
+use crate::UpdateChecker;
+use crate::{Error, ErrorKind, Latest, parse_response, status_error};
+
+/// A GitLab project whose releases are the source of versions.
+///
+/// Passed to [`UpdateChecker::gitlab_releases`]. The latest version is the
+/// tag of the project's most recently released release.
+#[derive(Clone, PartialEq, Eq)]
+pub struct GitLabSource {
+    base_url: String,
+    project: String,
+    token: Option<String>,
+}
+
+impl GitLabSource {
+    /// The project at `project` on the GitLab instance at `base_url`, such
+    /// as `https://gitlab.com`. The project is its full path, like
+    /// `group/subgroup/project`, or its numeric ID.
+    #[must_use]
+    pub fn new(base_url: impl Into<String>, project: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            project: project.into(),
+            token: None,
+        }
+    }
+
+    /// Authenticate requests with this personal, project, or group access
+    /// token, sent as a `PRIVATE-TOKEN` header. Needed for private projects.
+    ///
+    /// The token never appears in `Debug` output or error messages.
+    #[must_use]
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// The token header, if a token is set.
+    pub(crate) fn headers(&self) -> Vec<(&'static str, String)> {
+        self.token
+            .iter()
+            .map(|token| ("PRIVATE-TOKEN", token.clone()))
+            .collect()
+    }
+
+    /// The API URL listing the project's newest release. A project path is
+    /// sent as one URL-encoded segment.
+    pub(crate) fn latest_release_url(&self) -> String {
+        let base_url = self.base_url.trim_end_matches('/');
+        let id = self.project.trim_matches('/').replace('/', "%2F");
+        format!("{base_url}/api/v4/projects/{id}/releases?per_page=1&order_by=released_at")
+    }
+
+    /// Reject a project path with an empty or unsafe segment.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        let project = self.project.trim_matches('/');
+        let valid = !project.is_empty()
+            && project.split('/').all(|segment| {
+                !segment.is_empty()
+                    && segment != "."
+                    && segment != ".."
+                    && segment
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            });
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::ConfigError(format!(
+                "invalid GitLab project path: '{}'",
+                self.project
+            )))
+        }
+    }
+
+    /// Explain a `404 Not Found`, also reported for a project without
+    /// releases.
+    pub(crate) fn explain(&self, e: Error) -> Error {
+        if e.kind() != ErrorKind::NotFound {
+            return e;
+        }
+        e.noted(&format!(
+            ": GitLab project {} was not found or has no release",
+            self.project.trim_matches('/')
+        ))
+    }
+}
+
+impl std::fmt::Debug for GitLabSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GitLabSource")
+            .field("base_url", &self.base_url)
+            .field("project", &self.project)
+            .field("token", &self.token.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
+#[cfg(not(tiny_update_check_network_disabled))]
+impl VersionSource for GitLabSource {
+    fn latest(&self, context: &SourceContext<'_>) -> Result<String, Error> {
+        context.fetch_release(&ReleaseSource::GitLab(self.clone()))
+    }
+
+    fn id(&self) -> String {
+        self.latest_release_url()
+    }
+}
+
+/// Parse a one-release GitLab release list like [`parse_release`], taking
+/// the release time as the update time.
+///
+/// An empty list, for a project without releases, is reported as
+/// `404 Not Found` like GitHub does.
+pub fn parse_gitlab_release(body: &str) -> Result<Latest, Error> {
+    let json = parse_response(body)?;
+    let releases = json
+        .as_array()
+        .ok_or_else(|| Error::ParseError("expected a list of releases".to_string()))?;
+    let release = releases.first().ok_or_else(|| status_error(404))?;
+    release_latest(release, "released_at", body)
+}
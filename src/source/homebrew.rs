@@ -0,0 +1,113 @@
+//! A Homebrew formula's stable version, from the formulae API.
+
+use super::{ReleaseSource, SourceContext, VersionSource};
+#[cfg(doc)]
+use crate::UpdateChecker;
+use crate::{Error, ErrorKind, Latest, parse_response};
+
+/// The Homebrew formulae API queried when no other is configured.
+const DEFAULT_HOMEBREW_API_URL: &str = "https://formulae.brew.sh/api";
+
+/// A Homebrew formula whose stable version is the latest version.
+///
+/// Passed to [`UpdateChecker::homebrew_formula`]. For tools installed
+/// mostly through Homebrew, this reports an update only once users can
+/// `brew upgrade` to it, rather than when it reaches the registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HomebrewSource {
+    formula: String,
+    api_url: String,
+}
+
+impl HomebrewSource {
+    /// The formula named `formula` in homebrew-core.
+    #[must_use]
+    pub fn new(formula: impl Into<String>) -> Self {
+        Self {
+            formula: formula.into(),
+            api_url: DEFAULT_HOMEBREW_API_URL.to_string(),
+        }
+    }
+
+    /// Set the base URL of the formulae API. Defaults to
+    /// `https://formulae.brew.sh/api`.
+    #[must_use]
+    pub fn api_url(mut self, url: impl Into<String>) -> Self {
+        self.api_url = url.into();
+        self
+    }
+
+    /// The API URL of the formula.
+    pub(crate) fn formula_url(&self) -> String {
+        let api_url = self.api_url.trim_end_matches('/');
+        format!("{api_url}/formula/{}.json", self.formula)
+    }
+
+    /// Reject a formula name that isn't a single safe path segment.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        let name = &self.formula;
+        let valid = !name.is_empty()
+            && name != "."
+            && name != ".."
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+' | '@'));
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::ConfigError(format!(
+                "invalid Homebrew formula name: '{name}'"
+            )))
+        }
+    }
+
+    /// Explain a `404 Not Found`.
+    pub(crate) fn explain(&self, e: Error) -> Error {
+        if e.kind() != ErrorKind::NotFound {
+            return e;
+        }
+        e.noted(&format!(
+            ": Homebrew formula {} was not found",
+            self.formula
+        ))
+    }
+}
+
+#[cfg(not(tiny_update_check_network_disabled))]
+impl VersionSource for HomebrewSource {
+    fn latest(&self, context: &SourceContext<'_>) -> Result<String, Error> {
+        context.fetch_release(&ReleaseSource::Homebrew(self.clone()))
+    }
+
+    fn id(&self) -> String {
+        self.formula_url()
+    }
+}
+
+/// Parse a Homebrew formula, taking its `versions.stable` as the latest.
+pub fn parse_formula(body: &str) -> Result<Latest, Error> {
+    let json = parse_response(body)?;
+    let version = json
+        .get("versions")
+        .and_then(|versions| versions.get("stable"))
+        .and_then(serde_json::Value::as_str)
+        .ok_or_else(|| {
+            Error::ParseError("'versions.stable' field not found in response".to_string())
+        })?;
+    Ok(Latest {
+        version: version.to_string(),
+        versions: None,
+        artifacts: Vec::new(),
+        updated_at: None,
+        registry_url: None,
+        race_winner: None,
+        max_age: None,
+        crate_name: None,
+        stale: false,
+        offline: false,
+        #[cfg(feature = "response-body")]
+        response_body: Some(body.to_string()),
+        #[cfg(not(feature = "response-body"))]
+        response_body: None,
+    })
+}
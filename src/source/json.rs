@@ -0,0 +1,130 @@
+//! A version at a known path in a JSON document.
+
+use super::{ReleaseSource, SourceContext, VersionSource};
+#[cfg(doc)]
+use crate::UpdateChecker;
+use crate::{Error, ErrorKind, Latest, parse_response};
+
+/// A JSON endpoint holding the latest version at a known path, such as an
+/// internal status endpoint answering `{"app":{"latest":"1.4.2"}}`.
+///
+/// Passed to [`UpdateChecker::json_source`]. The path is a JSON pointer
+/// (RFC 6901): `/`-separated object keys and array indexes, with `~1`
+/// standing for a `/` in a key and `~0` for a `~`. A number there is taken
+/// as its decimal form; any other value that isn't a string, or a path
+/// that doesn't exist, fails with [`Error::ParseError`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonSource {
+    pub(super) url: String,
+    pub(super) pointer: String,
+}
+
+impl JsonSource {
+    /// The version at `pointer`, such as `/app/latest`, in the JSON
+    /// served at `url`.
+    #[must_use]
+    pub fn new(url: impl Into<String>, pointer: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            pointer: pointer.into(),
+        }
+    }
+
+    /// Reject a pointer that doesn't start with `/`. The empty pointer,
+    /// the whole document, is allowed.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        if self.pointer.is_empty() || self.pointer.starts_with('/') {
+            Ok(())
+        } else {
+            Err(Error::ConfigError(format!(
+                "JSON pointer '{}' must start with '/'",
+                self.pointer
+            )))
+        }
+    }
+
+    /// Explain a `404 Not Found`.
+    pub(crate) fn explain(&self, e: Error) -> Error {
+        if e.kind() != ErrorKind::NotFound {
+            return e;
+        }
+        e.noted(&format!(": JSON endpoint {} was not found", self.url))
+    }
+}
+
+#[cfg(not(tiny_update_check_network_disabled))]
+impl VersionSource for JsonSource {
+    fn latest(&self, context: &SourceContext<'_>) -> Result<String, Error> {
+        context.fetch_release(&ReleaseSource::Json(self.clone()))
+    }
+
+    fn id(&self) -> String {
+        self.url.clone()
+    }
+}
+
+/// Parse a JSON document, taking the version at `pointer`.
+///
+/// A number is taken in its decimal form; any other value that isn't a
+/// string is an error, as is a missing key or index, which the error names.
+pub fn parse_json_pointer(body: &str, pointer: &str) -> Result<Latest, Error> {
+    let json = parse_response(body)?;
+    let mut value = &json;
+    let mut walked = String::new();
+    for token in pointer.split('/').skip(1) {
+        let key = token.replace("~1", "/").replace("~0", "~");
+        let next = match value {
+            serde_json::Value::Object(map) => map.get(&key),
+            serde_json::Value::Array(items) => key
+                .parse::<usize>()
+                .ok()
+                .filter(|_| key == "0" || !key.starts_with('0'))
+                .and_then(|index| items.get(index)),
+            _ => None,
+        };
+        let Some(next) = next else {
+            let parent = if walked.is_empty() {
+                "the document"
+            } else {
+                &walked
+            };
+            return Err(Error::ParseError(format!(
+                "JSON pointer '{pointer}' not found: {parent} has no '{key}'"
+            )));
+        };
+        walked.push('/');
+        walked.push_str(token);
+        value = next;
+    }
+    let version = match value {
+        serde_json::Value::String(version) => version.clone(),
+        serde_json::Value::Number(number) => number.to_string(),
+        other => {
+            let kind = match other {
+                serde_json::Value::Null => "null",
+                serde_json::Value::Bool(_) => "a boolean",
+                serde_json::Value::Array(_) => "an array",
+                _ => "an object",
+            };
+            return Err(Error::ParseError(format!(
+                "expected a version at JSON pointer '{pointer}', found {kind}"
+            )));
+        }
+    };
+    Ok(Latest {
+        version,
+        versions: None,
+        artifacts: Vec::new(),
+        updated_at: None,
+        registry_url: None,
+        race_winner: None,
+        max_age: None,
+        crate_name: None,
+        stale: false,
+        offline: false,
+        #[cfg(feature = "response-body")]
+        response_body: Some(body.to_string()),
+        #[cfg(not(feature = "response-body"))]
+        response_body: None,
+    })
+}
@@ -0,0 +1,191 @@
+//! A `Cargo.toml`'s `package.version`.
+//!
+//! Only as much TOML is understood as finding one string needs: table
+//! headers, bare, quoted and dotted keys, basic and literal strings, and
+//! comments. Multi-line strings are skipped so their contents are never
+//! mistaken for keys. Anything else is ignored rather than rejected.
+
+use super::{ReleaseSource, SourceContext, VersionSource};
+#[cfg(doc)]
+use crate::UpdateChecker;
+use crate::{Error, ErrorKind, Latest};
+
+/// A `Cargo.toml` whose `package.version` is the latest version, such as
+/// the manifest on a repository's default branch.
+///
+/// Passed to [`UpdateChecker::manifest_source`]. A manifest whose package
+/// inherits its version with `version.workspace = true` takes it from a
+/// `[workspace.package]` table in the same file. A workspace member's
+/// manifest has none, so it fails with [`Error::ParseError`] saying to use
+/// the workspace root's manifest instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestSource {
+    pub(super) url: String,
+}
+
+impl ManifestSource {
+    /// The manifest at `url`, such as
+    /// `https://raw.githubusercontent.com/my-org/my-tool/main/Cargo.toml`.
+    #[must_use]
+    pub fn new(url: impl Into<String>) -> Self {
+        Self { url: url.into() }
+    }
+
+    /// Explain a `404 Not Found`.
+    pub(crate) fn explain(&self, e: Error) -> Error {
+        if e.kind() != ErrorKind::NotFound {
+            return e;
+        }
+        e.noted(&format!(": manifest {} was not found", self.url))
+    }
+}
+
+#[cfg(not(tiny_update_check_network_disabled))]
+impl VersionSource for ManifestSource {
+    fn latest(&self, context: &SourceContext<'_>) -> Result<String, Error> {
+        context.fetch_release(&ReleaseSource::Manifest(self.clone()))
+    }
+
+    fn id(&self) -> String {
+        self.url.clone()
+    }
+}
+
+/// Parse a `Cargo.toml`, taking its package version as the latest.
+pub fn parse_manifest(body: &str) -> Result<Latest, Error> {
+    let version = package_version(body)?;
+    semver::Version::parse(&version).map_err(|e| {
+        Error::ParseError(format!(
+            "package.version '{version}' is not a valid version: {e}"
+        ))
+    })?;
+    Ok(Latest {
+        version,
+        versions: None,
+        artifacts: Vec::new(),
+        updated_at: None,
+        registry_url: None,
+        race_winner: None,
+        max_age: None,
+        crate_name: None,
+        stale: false,
+        offline: false,
+        #[cfg(feature = "response-body")]
+        response_body: Some(body.to_string()),
+        #[cfg(not(feature = "response-body"))]
+        response_body: None,
+    })
+}
+
+/// The package version a manifest declares.
+///
+/// A package inheriting its version with `version.workspace = true` takes
+/// it from `[workspace.package]` in the same manifest; a workspace member's
+/// manifest doesn't have that, so it fails with a message saying to use the
+/// workspace root's instead. A virtual manifest's `[workspace.package]`
+/// version is used when there is no package.
+pub fn package_version(toml: &str) -> Result<String, Error> {
+    let mut table = Vec::new();
+    let mut package = None;
+    let mut inherited = false;
+    let mut workspace = None;
+    let mut in_multiline = None;
+
+    for line in toml.lines() {
+        if let Some(delimiter) = in_multiline {
+            if line.matches(delimiter).count() % 2 == 1 {
+                in_multiline = None;
+            }
+            continue;
+        }
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[') {
+            table = if header.starts_with('[') {
+                // An array of tables, which never holds the version.
+                vec!["[]".to_string()]
+            } else {
+                header
+                    .split_once(']')
+                    .map(|(path, _)| key_path(path))
+                    .unwrap_or_default()
+            };
+            continue;
+        }
+        let Some((key, value)) = split_key(line) else {
+            continue;
+        };
+        let value = value.trim_start();
+        for delimiter in ["\"\"\"", "'''"] {
+            if value.starts_with(delimiter) && value.matches(delimiter).count() == 1 {
+                in_multiline = Some(delimiter);
+            }
+        }
+
+        let mut path = table.clone();
+        path.extend(key_path(key));
+        let path: Vec<&str> = path.iter().map(String::as_str).collect();
+        match path.as_slice() {
+            ["package", "version"] if value.starts_with('{') => {
+                inherited |= value.contains("workspace");
+            }
+            ["package", "version"] => package = string_value(value),
+            ["package", "version", "workspace"] => inherited = true,
+            ["workspace", "package", "version"] => workspace = string_value(value),
+            _ => {}
+        }
+    }
+
+    match (package, inherited, workspace) {
+        (Some(version), _, _) | (None, _, Some(version)) => Ok(version),
+        (None, true, None) => Err(Error::ParseError(
+            "package.version is inherited from the workspace (version.workspace = true); \
+             use the workspace root's Cargo.toml"
+                .to_string(),
+        )),
+        (None, false, None) => Err(Error::ParseError(
+            "no package.version found in manifest".to_string(),
+        )),
+    }
+}
+
+/// Split a `key = value` line at its `=`, outside any quoted key.
+pub fn split_key(line: &str) -> Option<(&str, &str)> {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '=') => return Some((line.get(..i)?, line.get(i + 1..)?)),
+            (None, '#') => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The parts of a dotted key, unquoted.
+pub fn key_path(key: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut quote = None;
+    for c in key.chars() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '.') => parts.push(std::mem::take(&mut part)),
+            (None, c) if c.is_whitespace() => {}
+            _ => part.push(c),
+        }
+    }
+    parts.push(part);
+    parts
+}
+
+/// The content of a single-line basic or literal string at the start of
+/// `value`.
+pub fn string_value(value: &str) -> Option<String> {
+    let quote = value.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+    let rest = value.get(1..)?;
+    let end = rest.find(quote)?;
+    Some(rest.get(..end)?.to_string())
+}
@@ -0,0 +1,503 @@
+//! Where versions come from besides a crate registry.
+//!
+//! Each built-in source has its own module holding its configuration, the
+//! URL it is looked up at and the parser for the answer. [`ReleaseSource`]
+//! gathers them for the checkers, and [`VersionSource`] is how they, and
+//! sources from outside the crate, fetch through a checker.
+
+mod crates_io;
+mod dist;
+pub mod git;
+mod github;
+mod gitlab;
+mod homebrew;
+mod json;
+pub mod manifest;
+mod npm;
+pub mod oci;
+mod text;
+mod winget;
+
+pub use crates_io::CratesIoSource;
+pub use dist::{DistArtifact, DistManifestSource, parse_dist_manifest};
+pub use git::{GitTagsSource, parse_git_refs, parse_tag_list};
+pub use github::{GitHubSource, parse_feed, parse_release};
+pub use gitlab::{GitLabSource, parse_gitlab_release};
+pub use homebrew::{HomebrewSource, parse_formula};
+pub use json::{JsonSource, parse_json_pointer};
+pub use manifest::{ManifestSource, parse_manifest};
+pub use npm::{NpmSource, parse_npm_package};
+pub use oci::OciSource;
+pub use text::{TextSource, parse_text};
+pub use winget::{WingetSource, parse_winget_versions};
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(doc)]
+use crate::{CheckEvent, ErrorKind, PrereleasePolicy};
+use crate::{
+    CompareOptions, Comparison, Error, Latest, ResponseFormat, SuggestStrategy, UpdateChecker,
+    response_text, rng, status_error, validate_registry_url,
+};
+
+/// A backend supplying the latest version, plugged into a checker with
+/// [`UpdateChecker::with_source`].
+///
+/// A source only fetches: the checker caches the version it returns,
+/// spends the daily request budget, compares the version with the current
+/// one and applies the [`PrereleasePolicy`], as for the registry. It
+/// fetches through the checker's [`SourceContext`], so its requests share
+/// the checker's proxy, TLS settings, headers and timeout. The built-in
+/// sources, such as [`GitHubSource`] and [`CratesIoSource`], are version
+/// sources too, so they can be composed into custom ones. Sources make a
+/// seam for test doubles as well:
+///
+/// ```
+/// use tiny_update_check::{Error, SourceContext, UpdateChecker, VersionSource};
+///
+/// struct Fixed(&'static str);
+///
+/// impl VersionSource for Fixed {
+///     fn latest(&self, _context: &SourceContext<'_>) -> Result<String, Error> {
+///         Ok(self.0.to_string())
+///     }
+///
+///     fn id(&self) -> String {
+///         "fixed".to_string()
+///     }
+/// }
+///
+/// let checker = UpdateChecker::new("my-tool", "1.0.0")
+///     .with_source(Fixed("1.1.0"))
+///     .cache_dir(None);
+/// # #[cfg(not(tiny_update_check_network_disabled))]
+/// assert_eq!(checker.check().unwrap().unwrap().latest, "1.1.0");
+/// ```
+pub trait VersionSource: Send + Sync {
+    /// Fetch the latest version through `context`, giving up after about
+    /// its [`timeout`](SourceContext::timeout).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the version can't be fetched. It is returned
+    /// from the check unchanged, so sources should use the [`Error`]
+    /// variant whose [`ErrorKind`] fits.
+    fn latest(&self, context: &SourceContext<'_>) -> Result<String, Error>;
+
+    /// A name for where versions come from, such as the URL fetched.
+    ///
+    /// Each source's version is cached under a key derived from it, so
+    /// sources with different identifiers never share a cached version. It
+    /// is also reported as the URL of [`CheckEvent::FetchStarted`].
+    fn id(&self) -> String;
+}
+
+/// The checker a [`VersionSource`] fetches for.
+///
+/// Requests made with [`get`](Self::get) go out as the checker's own do:
+/// through its proxy and host overrides, with its TLS roots and pins, its
+/// custom headers and `User-Agent`, and within its timeout and memory
+/// limit. The async checker runs custom sources on tokio's blocking thread
+/// pool and sends their requests with its own client.
+pub struct SourceContext<'a> {
+    transport: Transport<'a>,
+    capture: Option<(&'a Path, &'a str)>,
+    /// The whole answer of the last built-in lookup, which carries more
+    /// than the version a source returns.
+    answer: std::cell::RefCell<Option<Latest>>,
+}
+
+/// The checker whose client a [`SourceContext`] sends requests with.
+enum Transport<'a> {
+    Blocking(&'a UpdateChecker),
+    #[cfg(all(feature = "async", not(tiny_update_check_network_disabled)))]
+    Async(&'a crate::r#async::UpdateChecker, tokio::runtime::Handle),
+}
+
+impl std::fmt::Debug for SourceContext<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SourceContext")
+            .field("timeout", &self.timeout())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> SourceContext<'a> {
+    /// A context sending requests with the blocking `checker`, saving a
+    /// response that fails to parse to `capture`.
+    pub(crate) const fn blocking(
+        checker: &'a UpdateChecker,
+        capture: Option<(&'a Path, &'a str)>,
+    ) -> Self {
+        Self {
+            transport: Transport::Blocking(checker),
+            capture,
+            answer: std::cell::RefCell::new(None),
+        }
+    }
+
+    /// A context sending requests with the async `checker` on the runtime
+    /// of `handle`, from one of its blocking threads.
+    #[cfg(all(feature = "async", not(tiny_update_check_network_disabled)))]
+    pub(crate) const fn pooled(
+        checker: &'a crate::r#async::UpdateChecker,
+        handle: tokio::runtime::Handle,
+    ) -> Self {
+        Self {
+            transport: Transport::Async(checker, handle),
+            capture: None,
+            answer: std::cell::RefCell::new(None),
+        }
+    }
+
+    /// How long a lookup should take at most: the checker's
+    /// [`timeout`](UpdateChecker::timeout), or the time left of its
+    /// [`total_deadline`](UpdateChecker::total_deadline).
+    #[must_use]
+    pub const fn timeout(&self) -> Duration {
+        match self.transport {
+            Transport::Blocking(checker) => checker.timeout,
+            #[cfg(all(feature = "async", not(tiny_update_check_network_disabled)))]
+            Transport::Async(checker, _) => checker.timeout,
+        }
+    }
+
+    /// Ask `source` for the latest version, keeping the rest of a built-in
+    /// lookup's answer.
+    pub(crate) fn answer_from(self, source: &dyn VersionSource) -> Result<Latest, Error> {
+        let version = source.latest(&self)?;
+        Ok(match self.answer.into_inner() {
+            Some(latest) if latest.version == version => latest,
+            _ => Latest {
+                version,
+                versions: None,
+                artifacts: Vec::new(),
+                updated_at: None,
+                registry_url: None,
+                race_winner: None,
+                max_age: None,
+                crate_name: None,
+                stale: false,
+                offline: false,
+                response_body: None,
+            },
+        })
+    }
+}
+
+/// Requests, compiled out by `--cfg tiny_update_check_network_disabled`.
+#[cfg(not(tiny_update_check_network_disabled))]
+impl SourceContext<'_> {
+    /// Fetch `url` with a `GET`, returning the body of a successful
+    /// response as text.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URL isn't allowed, the request fails or is
+    /// answered with an unsuccessful status, or the body is too large or
+    /// isn't UTF-8.
+    pub fn get(&self, url: &str) -> Result<String, Error> {
+        let body = match self.transport {
+            Transport::Blocking(checker) => checker.get(url)?,
+            #[cfg(feature = "async")]
+            Transport::Async(checker, ref handle) => handle.block_on(checker.get(url))?,
+        };
+        response_text(&body).map(str::to_string)
+    }
+
+    /// Fetch the latest version from the built-in `source`.
+    pub(crate) fn fetch_release(&self, source: &ReleaseSource) -> Result<String, Error> {
+        source.validate()?;
+        let (url, format) = source.lookup();
+        self.fetch(&url, format, Some(source))
+    }
+
+    /// Fetch the latest version from `url`, answered in `format` for the
+    /// built-in release `source` if any, keeping the whole answer.
+    pub(crate) fn fetch(
+        &self,
+        url: &str,
+        format: ResponseFormat,
+        source: Option<&ReleaseSource>,
+    ) -> Result<String, Error> {
+        let latest = match self.transport {
+            Transport::Blocking(checker) => {
+                validate_registry_url(url, checker.config.allow_insecure_http)?;
+                checker.fetch_direct(url, format, source, self.capture)
+            }
+            #[cfg(feature = "async")]
+            Transport::Async(checker, ref handle) => {
+                handle.block_on(checker.fetch_pooled(url, format, source))
+            }
+        }
+        .map_err(|e| match source {
+            Some(source) => source.explain(e),
+            None => e,
+        })?;
+        let version = latest.version.clone();
+        *self.answer.borrow_mut() = Some(latest);
+        Ok(version)
+    }
+}
+
+/// A [`VersionSource`] plugged into a checker.
+#[derive(Clone)]
+pub struct CustomSource(pub(crate) Arc<dyn VersionSource>);
+
+impl std::fmt::Debug for CustomSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("CustomSource").field(&self.0.id()).finish()
+    }
+}
+
+/// Where releases are looked up instead of the registry.
+#[derive(Debug, Clone)]
+pub enum ReleaseSource {
+    GitHub(GitHubSource),
+    GitLab(GitLabSource),
+    Tags(GitTagsSource),
+    Text(TextSource),
+    Json(JsonSource),
+    Manifest(ManifestSource),
+    Homebrew(HomebrewSource),
+    Dist(DistManifestSource),
+    Npm(NpmSource),
+    Winget(WingetSource),
+    Oci(OciSource),
+    Custom(CustomSource),
+}
+
+impl ReleaseSource {
+    /// The source as a [`VersionSource`], which fetches from it.
+    #[cfg(not(tiny_update_check_network_disabled))]
+    pub(crate) fn version_source(&self) -> &dyn VersionSource {
+        match self {
+            Self::GitHub(source) => source,
+            Self::GitLab(source) => source,
+            Self::Tags(source) => source,
+            Self::Text(source) => source,
+            Self::Json(source) => source,
+            Self::Manifest(source) => source,
+            Self::Homebrew(source) => source,
+            Self::Dist(source) => source,
+            Self::Npm(source) => source,
+            Self::Winget(source) => source,
+            Self::Oci(source) => source,
+            Self::Custom(source) => source.0.as_ref(),
+        }
+    }
+
+    /// The URL of the latest release, and the format it's answered in.
+    pub(crate) fn lookup(&self) -> (String, ResponseFormat) {
+        match self {
+            Self::GitHub(source) => source.lookup(),
+            Self::GitLab(source) => (source.latest_release_url(), ResponseFormat::GitLabRelease),
+            Self::Tags(source) => source.lookup(),
+            Self::Text(source) => (source.url.clone(), ResponseFormat::PlainText),
+            Self::Json(source) => (source.url.clone(), ResponseFormat::Json),
+            Self::Manifest(source) => (source.url.clone(), ResponseFormat::Manifest),
+            Self::Homebrew(source) => (source.formula_url(), ResponseFormat::Homebrew),
+            Self::Dist(source) => (source.url.clone(), ResponseFormat::DistManifest),
+            Self::Npm(source) => (source.package_url(), ResponseFormat::Npm),
+            Self::Winget(source) => (source.versions_url(), ResponseFormat::Winget),
+            Self::Oci(source) => (source.tags_url(), ResponseFormat::OciTags),
+            Self::Custom(source) => (source.0.id(), ResponseFormat::Custom),
+        }
+    }
+
+    pub(crate) fn headers(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Self::GitHub(source) => source.headers(),
+            Self::GitLab(source) => source.headers(),
+            Self::Tags(_) | Self::Json(_) | Self::Oci(_) => {
+                vec![("Accept", "application/json".to_string())]
+            }
+            Self::Text(_) => vec![("Accept", "text/plain".to_string())],
+            // The abbreviated metadata npm's client installs from, much
+            // smaller than the full document.
+            Self::Npm(_) => vec![(
+                "Accept",
+                "application/vnd.npm.install-v1+json; q=1.0, application/json; q=0.8".to_string(),
+            )],
+            Self::Winget(_) => WingetSource::headers(),
+            Self::Manifest(_) | Self::Homebrew(_) | Self::Dist(_) | Self::Custom(_) => Vec::new(),
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        match self {
+            Self::GitHub(source) => source.validate(),
+            Self::GitLab(source) => source.validate(),
+            Self::Tags(source) => source.validate(),
+            Self::Homebrew(source) => source.validate(),
+            Self::Npm(source) => source.validate(),
+            Self::Winget(source) => source.validate(),
+            Self::Oci(source) => source.validate(),
+            Self::Json(source) => source.validate(),
+            Self::Text(_) | Self::Manifest(_) | Self::Dist(_) | Self::Custom(_) => Ok(()),
+        }
+    }
+
+    pub(crate) fn explain(&self, e: Error) -> Error {
+        match self {
+            Self::GitHub(source) => source.explain(e),
+            Self::GitLab(source) => source.explain(e),
+            Self::Tags(source) => source.explain(e),
+            Self::Text(source) => source.explain(e),
+            Self::Json(source) => source.explain(e),
+            Self::Manifest(source) => source.explain(e),
+            Self::Homebrew(source) => source.explain(e),
+            Self::Dist(source) => source.explain(e),
+            Self::Npm(source) => source.explain(e),
+            Self::Winget(source) => source.explain(e),
+            Self::Oci(source) => source.explain(e),
+            Self::Custom(_) => e,
+        }
+    }
+
+    /// The pointer to the version, for a JSON source.
+    pub(crate) fn json_pointer(&self) -> &str {
+        match self {
+            Self::Json(source) => &source.pointer,
+            _ => "",
+        }
+    }
+
+    /// The app to read from a dist manifest, if one is named.
+    pub(crate) fn dist_app(&self) -> Option<&str> {
+        match self {
+            Self::Dist(source) => source.app.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The cache key for the latest version under `storage_key`.
+    ///
+    /// A distribution's packaged version lags the registry's, so it is
+    /// cached apart from it; a checker switching between the two never
+    /// reports the other's cached version.
+    pub(crate) fn cache_key(source: Option<&Self>, storage_key: String) -> String {
+        match source {
+            Some(Self::Homebrew(_)) => format!("{storage_key}-homebrew"),
+            Some(Self::Npm(_)) => format!("{storage_key}-npm"),
+            Some(Self::Winget(_)) => format!("{storage_key}-winget"),
+            Some(Self::Custom(source)) => {
+                format!(
+                    "{storage_key}-{:016x}",
+                    rng::fnv1a(source.0.id().as_bytes())
+                )
+            }
+            _ => storage_key,
+        }
+    }
+
+    /// The comparison settings for versions from `source`: winget versions
+    /// are always compared as [`Comparison::FourPart`].
+    pub(crate) const fn compare_options(
+        source: Option<&Self>,
+        mut options: CompareOptions,
+    ) -> CompareOptions {
+        if let Some(Self::Winget(_)) = source {
+            options.comparison = Comparison::FourPart;
+        }
+        options
+    }
+
+    /// Whether a plain-text version keeps a leading `v`.
+    pub(crate) const fn keeps_v(&self) -> bool {
+        matches!(self, Self::Text(TextSource { strip_v: false, .. }))
+    }
+
+    /// The prefix tags must start with, for a tag source.
+    pub(crate) fn tag_prefix(&self) -> Option<&str> {
+        match self {
+            Self::Tags(source) => source.prefix.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// The highest version among `tags`, each stripped of `prefix`, or of a
+/// leading `v` without one. Tags without the prefix or a valid version are
+/// skipped; if none is left, the lookup is reported as `404 Not Found`.
+fn latest_tag<'a>(
+    tags: impl Iterator<Item = &'a str>,
+    prefix: Option<&str>,
+    suggest: SuggestStrategy,
+    body: &str,
+) -> Result<Latest, Error> {
+    let mut versions: Vec<(semver::Version, &str)> = tags
+        .filter_map(|tag| {
+            let version = match prefix {
+                Some(prefix) => tag.strip_prefix(prefix)?,
+                None => tag.strip_prefix(['v', 'V']).unwrap_or(tag),
+            };
+            Some((semver::Version::parse(version).ok()?, version))
+        })
+        .collect();
+    versions.sort_by(|(a, _), (b, _)| b.cmp(a));
+    versions.dedup_by(|(a, _), (b, _)| a == b);
+    #[cfg(not(feature = "response-body"))]
+    let _ = body;
+    let (_, version) = versions.first().ok_or_else(|| status_error(404))?;
+    Ok(Latest {
+        version: (*version).to_string(),
+        versions: match suggest {
+            SuggestStrategy::Newest => None,
+            SuggestStrategy::SafestFirst => Some(
+                versions
+                    .iter()
+                    .map(|(_, version)| (*version).to_string())
+                    .collect(),
+            ),
+        },
+        artifacts: Vec::new(),
+        updated_at: None,
+        registry_url: None,
+        race_winner: None,
+        max_age: None,
+        crate_name: None,
+        stale: false,
+        offline: false,
+        #[cfg(feature = "response-body")]
+        response_body: Some(body.to_string()),
+        #[cfg(not(feature = "response-body"))]
+        response_body: None,
+    })
+}
+
+/// The latest version a release names, with its `time_field` as the update
+/// time.
+fn release_latest(
+    release: &serde_json::Value,
+    time_field: &str,
+    body: &str,
+) -> Result<Latest, Error> {
+    let tag = release
+        .get("tag_name")
+        .and_then(serde_json::Value::as_str)
+        .ok_or_else(|| Error::ParseError("'tag_name' field not found in response".to_string()))?;
+    #[cfg(not(feature = "response-body"))]
+    let _ = body;
+    Ok(Latest {
+        version: tag.strip_prefix(['v', 'V']).unwrap_or(tag).to_string(),
+        versions: None,
+        artifacts: Vec::new(),
+        updated_at: release
+            .get(time_field)
+            .and_then(serde_json::Value::as_str)
+            .map(String::from),
+        registry_url: None,
+        race_winner: None,
+        max_age: None,
+        crate_name: None,
+        stale: false,
+        offline: false,
+        #[cfg(feature = "response-body")]
+        response_body: Some(body.to_string()),
+        #[cfg(not(feature = "response-body"))]
+        response_body: None,
+    })
+}
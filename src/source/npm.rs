@@ -0,0 +1,125 @@
+//! An npm package's `latest` dist-tag, from its registry metadata.
+
+use super::{ReleaseSource, SourceContext, VersionSource};
+#[cfg(doc)]
+use crate::UpdateChecker;
+use crate::{Error, ErrorKind, Latest, parse_response};
+
+/// The npm registry queried when no other is configured.
+const DEFAULT_NPM_REGISTRY_URL: &str = "https://registry.npmjs.org";
+
+/// A package on an npm registry whose `latest` dist-tag is the latest
+/// version.
+///
+/// Passed to [`UpdateChecker::npm_package`]. For a tool also published to
+/// npm, typically as a wrapper package, users who installed it with
+/// `npm install -g` can be told about the version `npm` would install.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NpmSource {
+    package: String,
+    registry_url: String,
+}
+
+impl NpmSource {
+    /// The package named `package`, which may be scoped, like
+    /// `@my-org/my-tool`, on registry.npmjs.org.
+    #[must_use]
+    pub fn new(package: impl Into<String>) -> Self {
+        Self {
+            package: package.into(),
+            registry_url: DEFAULT_NPM_REGISTRY_URL.to_string(),
+        }
+    }
+
+    /// Set the base URL of the registry, for a private registry such as
+    /// Verdaccio. Defaults to `https://registry.npmjs.org`.
+    #[must_use]
+    pub fn registry_url(mut self, url: impl Into<String>) -> Self {
+        self.registry_url = url.into();
+        self
+    }
+
+    /// The registry URL of the package's metadata. A scoped name's `/` is
+    /// encoded, as npm's own client does.
+    pub(crate) fn package_url(&self) -> String {
+        let registry_url = self.registry_url.trim_end_matches('/');
+        format!("{registry_url}/{}", self.package.replacen('/', "%2F", 1))
+    }
+
+    /// Reject a package name that isn't a valid, optionally scoped, npm
+    /// name.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        let name = &self.package;
+        let part = |part: &str| {
+            !part.is_empty()
+                && !part.starts_with('.')
+                && part.chars().all(|c| {
+                    c.is_ascii_lowercase()
+                        || c.is_ascii_digit()
+                        || matches!(c, '-' | '_' | '.' | '~')
+                })
+        };
+        let valid = name.strip_prefix('@').map_or_else(
+            || part(name),
+            |scoped| {
+                scoped
+                    .split_once('/')
+                    .is_some_and(|(scope, package)| part(scope) && part(package))
+            },
+        );
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::ConfigError(format!(
+                "invalid npm package name: '{name}'"
+            )))
+        }
+    }
+
+    /// Explain a `404 Not Found`.
+    pub(crate) fn explain(&self, e: Error) -> Error {
+        if e.kind() != ErrorKind::NotFound {
+            return e;
+        }
+        e.noted(&format!(": npm package {} was not found", self.package))
+    }
+}
+
+#[cfg(not(tiny_update_check_network_disabled))]
+impl VersionSource for NpmSource {
+    fn latest(&self, context: &SourceContext<'_>) -> Result<String, Error> {
+        context.fetch_release(&ReleaseSource::Npm(self.clone()))
+    }
+
+    fn id(&self) -> String {
+        self.package_url()
+    }
+}
+
+/// Parse an npm package's metadata, taking its `latest` dist-tag.
+pub fn parse_npm_package(body: &str) -> Result<Latest, Error> {
+    let json = parse_response(body)?;
+    let version = json
+        .get("dist-tags")
+        .and_then(|tags| tags.get("latest"))
+        .and_then(serde_json::Value::as_str)
+        .ok_or_else(|| {
+            Error::ParseError("'dist-tags.latest' field not found in response".to_string())
+        })?;
+    Ok(Latest {
+        version: version.to_string(),
+        versions: None,
+        artifacts: Vec::new(),
+        updated_at: None,
+        registry_url: None,
+        race_winner: None,
+        max_age: None,
+        crate_name: None,
+        stale: false,
+        offline: false,
+        #[cfg(feature = "response-body")]
+        response_body: Some(body.to_string()),
+        #[cfg(not(feature = "response-body"))]
+        response_body: None,
+    })
+}
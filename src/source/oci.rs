@@ -0,0 +1,359 @@
+//! An image's version tags, listed with the OCI distribution API.
+//!
+//! A listing takes several requests: a registry such as Docker Hub answers
+//! an anonymous request with `401 Unauthorized` and a challenge naming where
+//! to get a pull token, and large tag lists are split into pages linked by
+//! `Link` headers. [`Listing`] decides each request and reads each response
+//! without sending anything itself, so the blocking and async checkers drive
+//! it with their own clients.
+
+use super::{ReleaseSource, SourceContext, VersionSource, latest_tag};
+#[cfg(doc)]
+use crate::UpdateChecker;
+use crate::{Error, ErrorKind, Latest, Response, SuggestStrategy, parse_response, response_text};
+
+/// The registry serving Docker Hub's images.
+const DOCKER_HUB_REGISTRY_URL: &str = "https://registry-1.docker.io";
+
+/// A container image whose tags are its versions, in a registry speaking
+/// the OCI distribution API.
+///
+/// Passed to [`UpdateChecker::oci_tags`]. The latest version is the highest
+/// tag that is a version, with or without a leading `v`; tags like `latest`
+/// or `sha-1a2b3c` are ignored. Pages of tags are followed through the
+/// registry's `Link` headers, and a registry asking for a token, as Docker
+/// Hub and GHCR do even for public images, is given an anonymous one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OciSource {
+    registry: String,
+    repository: String,
+}
+
+impl OciSource {
+    /// The image `repository` in the registry at `registry`, such as
+    /// `OciSource::new("ghcr.io", "my-org/my-tool")`. A registry without
+    /// a scheme is reached over `https://`.
+    #[must_use]
+    pub fn new(registry: impl Into<String>, repository: impl Into<String>) -> Self {
+        let registry = registry.into();
+        let registry = if registry.contains("://") {
+            registry
+        } else {
+            format!("https://{registry}")
+        };
+        Self {
+            registry,
+            repository: repository.into(),
+        }
+    }
+
+    /// The image `repository` on Docker Hub. An official image's bare name,
+    /// such as `alpine`, is looked up as `library/alpine`.
+    #[must_use]
+    pub fn docker_hub(repository: impl Into<String>) -> Self {
+        let repository = repository.into();
+        let repository = if repository.contains('/') {
+            repository
+        } else {
+            format!("library/{repository}")
+        };
+        Self::new(DOCKER_HUB_REGISTRY_URL, repository)
+    }
+
+    /// The URL of the first page of the image's tags.
+    pub(crate) fn tags_url(&self) -> String {
+        let registry = self.registry.trim_end_matches('/');
+        format!("{registry}/v2/{}/tags/list", self.repository)
+    }
+
+    /// Reject a repository name the distribution API wouldn't accept.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        let name = &self.repository;
+        let valid = name.split('/').all(|segment| {
+            !segment.is_empty()
+                && segment != "."
+                && segment != ".."
+                && segment.chars().all(|c| {
+                    c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-')
+                })
+        });
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::ConfigError(format!(
+                "invalid image repository name: '{name}'"
+            )))
+        }
+    }
+
+    /// Explain a `404 Not Found`, also reported when no tag is a version.
+    pub(crate) fn explain(&self, e: Error) -> Error {
+        if e.kind() != ErrorKind::NotFound {
+            return e;
+        }
+        e.noted(&format!(
+            ": image {} was not found or has no version tags",
+            self.tags_url()
+        ))
+    }
+}
+
+#[cfg(not(tiny_update_check_network_disabled))]
+impl VersionSource for OciSource {
+    fn latest(&self, context: &SourceContext<'_>) -> Result<String, Error> {
+        context.fetch_release(&ReleaseSource::Oci(self.clone()))
+    }
+
+    fn id(&self) -> String {
+        self.tags_url()
+    }
+}
+
+/// The most pages of tags read, bounding a listing that never ends.
+const MAX_PAGES: usize = 50;
+
+/// Requests and responses listing one image's tags.
+#[derive(Debug)]
+pub struct Listing {
+    /// The next page of tags to request.
+    page: Option<String>,
+    /// The token to request before the page, after a challenge.
+    token_url: Option<String>,
+    /// The pull token, once obtained.
+    token: Option<String>,
+    /// Whether the request in flight is for the token.
+    awaiting_token: bool,
+    tags: Vec<String>,
+    pages: usize,
+    /// The last page's body.
+    body: String,
+}
+
+impl Listing {
+    /// A listing starting at the tags URL `url`.
+    pub fn new(url: &str) -> Self {
+        Self {
+            page: Some(url.to_string()),
+            token_url: None,
+            token: None,
+            awaiting_token: false,
+            tags: Vec::new(),
+            pages: 0,
+            body: String::new(),
+        }
+    }
+
+    /// The URL and headers of the next request, or `None` once every page
+    /// is read.
+    pub fn next_request(&mut self) -> Option<(String, Vec<(&'static str, String)>)> {
+        let accept = ("Accept", "application/json".to_string());
+        self.awaiting_token = self.token_url.is_some();
+        if let Some(ref url) = self.token_url {
+            return Some((url.clone(), vec![accept]));
+        }
+        let mut headers = vec![accept];
+        if let Some(ref token) = self.token {
+            headers.push(("Authorization", format!("Bearer {token}")));
+        }
+        Some((self.page.clone()?, headers))
+    }
+
+    /// Read the response to the last request.
+    pub fn handle(&mut self, response: Response) -> Result<(), Error> {
+        if self.awaiting_token {
+            self.token_url = None;
+            self.token = Some(parse_token(&response.into_body()?)?);
+            return Ok(());
+        }
+        let page = self.page.take().unwrap_or_default();
+        if response.status == 401 && self.token.is_none() {
+            if let Some(url) = response.header("WWW-Authenticate").and_then(token_url) {
+                self.token_url = Some(url);
+                self.page = Some(page);
+                return Ok(());
+            }
+        }
+        let next = response
+            .header("Link")
+            .and_then(|link| next_page(link, &page));
+        let body = response_text(&response.into_body()?)?.to_string();
+        read_page(&body, &mut self.tags)?;
+        self.body = body;
+        self.pages += 1;
+        self.page = next.filter(|_| self.pages < MAX_PAGES);
+        Ok(())
+    }
+
+    /// The highest version among the tags listed. Tags that aren't
+    /// versions, like `latest` or `sha-1a2b3c`, are skipped.
+    pub fn finish(self, suggest: SuggestStrategy) -> Result<Latest, Error> {
+        latest_tag(
+            self.tags.iter().map(String::as_str),
+            None,
+            suggest,
+            &self.body,
+        )
+    }
+}
+
+/// The highest version among the tags on one page of a tag list.
+pub fn parse_tags(body: &str, suggest: SuggestStrategy) -> Result<Latest, Error> {
+    let mut tags = Vec::new();
+    read_page(body, &mut tags)?;
+    latest_tag(tags.iter().map(String::as_str), None, suggest, body)
+}
+
+/// Add the tags on a page of a tag list, `{"name": ..., "tags": [...]}`,
+/// to `tags`.
+fn read_page(body: &str, tags: &mut Vec<String>) -> Result<(), Error> {
+    let json = parse_response(body)?;
+    match json.get("tags") {
+        Some(serde_json::Value::Array(page)) => {
+            tags.extend(page.iter().filter_map(|tag| tag.as_str().map(String::from)));
+            Ok(())
+        }
+        // A repository without tags may list them as `null`.
+        Some(serde_json::Value::Null) => Ok(()),
+        _ => Err(Error::ParseError(
+            "'tags' field not found in response".to_string(),
+        )),
+    }
+}
+
+/// The URL to request a pull token at, from a `Bearer` challenge such as
+/// `Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/alpine:pull"`.
+fn token_url(challenge: &str) -> Option<String> {
+    let params = challenge
+        .trim()
+        .strip_prefix("Bearer ")
+        .or_else(|| challenge.trim().strip_prefix("bearer "))?;
+    let mut realm = None;
+    let mut query = Vec::new();
+    for (key, value) in challenge_params(params) {
+        match key.to_ascii_lowercase().as_str() {
+            "realm" => realm = Some(value),
+            "service" | "scope" => query.push(format!("{key}={}", query_encode(&value))),
+            _ => {}
+        }
+    }
+    let realm = realm?;
+    if query.is_empty() {
+        return Some(realm);
+    }
+    let separator = if realm.contains('?') { '&' } else { '?' };
+    Some(format!("{realm}{separator}{}", query.join("&")))
+}
+
+/// The `key="value"` parameters of a challenge, unquoted. Commas inside
+/// quotes, as in a scope of several actions, don't separate parameters.
+fn challenge_params(params: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut rest = params.trim_start();
+    while let Some((key, after)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_string();
+        let after = after.trim_start();
+        let (value, after) = after.strip_prefix('"').map_or_else(
+            || after.split_once(',').unwrap_or((after, "")),
+            |quoted| {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                (
+                    quoted.get(..end).unwrap_or_default(),
+                    quoted.get(end + 1..).unwrap_or_default(),
+                )
+            },
+        );
+        pairs.push((key, value.trim().to_string()));
+        rest = after.trim_start().trim_start_matches(',');
+    }
+    pairs
+}
+
+/// Percent-encode a query parameter value.
+fn query_encode(value: &str) -> String {
+    use std::fmt::Write;
+
+    value.bytes().fold(String::new(), |mut encoded, byte| {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(char::from(byte));
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+        encoded
+    })
+}
+
+/// The token in a token endpoint's response, under `token` or, from some
+/// registries, the `OAuth2` `access_token`.
+fn parse_token(body: &[u8]) -> Result<String, Error> {
+    let json: serde_json::Value = serde_json::from_slice(body)
+        .map_err(|e| Error::ParseError(format!("token response: {e}")))?;
+    json.get("token")
+        .or_else(|| json.get("access_token"))
+        .and_then(serde_json::Value::as_str)
+        .filter(|token| !token.is_empty())
+        .map(String::from)
+        .ok_or_else(|| Error::ParseError("'token' field not found in token response".to_string()))
+}
+
+/// The URL of the next page from a `Link` header such as
+/// `</v2/library/alpine/tags/list?last=3.19&n=100>; rel="next"`, resolved
+/// against the current page's URL.
+fn next_page(link: &str, current: &str) -> Option<String> {
+    let target = link.split(',').find_map(|link| {
+        let (target, params) = link.split_once(';')?;
+        let next = params.split(';').any(|param| {
+            param
+                .trim()
+                .strip_prefix("rel=")
+                .is_some_and(|rel| rel.trim_matches('"').split(' ').any(|rel| rel == "next"))
+        });
+        next.then(|| target.trim().trim_start_matches('<').trim_end_matches('>'))
+    })?;
+    if target.starts_with("https://") || target.starts_with("http://") {
+        return Some(target.to_string());
+    }
+    let (scheme, rest) = current.split_once("://")?;
+    let authority = rest.split('/').next()?;
+    let path = if target.starts_with('/') {
+        target.to_string()
+    } else {
+        format!("/{target}")
+    };
+    Some(format!("{scheme}://{authority}{path}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn challenges_name_the_token_url() {
+        assert_eq!(
+            token_url(
+                r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/alpine:pull""#
+            )
+            .unwrap(),
+            "https://auth.docker.io/token?service=registry.docker.io&scope=repository%3Alibrary%2Falpine%3Apull"
+        );
+        assert_eq!(
+            token_url(r#"Bearer realm="https://ghcr.io/token",scope="repository:o/t:pull,push""#)
+                .unwrap(),
+            "https://ghcr.io/token?scope=repository%3Ao%2Ft%3Apull%2Cpush"
+        );
+        assert_eq!(token_url(r#"Basic realm="Registry""#), None);
+    }
+
+    #[test]
+    fn links_resolve_against_the_registry() {
+        let current = "https://registry-1.docker.io/v2/library/alpine/tags/list";
+        assert_eq!(
+            next_page(
+                r#"</v2/library/alpine/tags/list?last=3.19&n=100>; rel="next""#,
+                current
+            )
+            .unwrap(),
+            "https://registry-1.docker.io/v2/library/alpine/tags/list?last=3.19&n=100"
+        );
+        assert_eq!(next_page(r#"</v2/x/tags/list>; rel="prev""#, current), None);
+    }
+}
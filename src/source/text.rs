@@ -0,0 +1,102 @@
+//! A version published as a plain-text file.
+
+use super::{ReleaseSource, SourceContext, VersionSource};
+#[cfg(doc)]
+use crate::UpdateChecker;
+use crate::{Error, ErrorKind, Latest};
+
+/// The longest plain-text version accepted, trimmed. Anything longer is
+/// almost certainly an error page rather than a version file.
+const MAX_TEXT_VERSION: usize = 128;
+
+/// A URL serving nothing but the latest version, as plain text.
+///
+/// Passed to [`UpdateChecker::text_source`]. Publishing a one-line file is
+/// often all an air-gapped mirror allows. The body is trimmed and, by
+/// default, stripped of a leading `v`; it must then be a valid version of
+/// at most 128 bytes, so a URL answering with an HTML page fails with
+/// [`Error::ParseError`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextSource {
+    pub(super) url: String,
+    pub(super) strip_v: bool,
+}
+
+impl TextSource {
+    /// The version file at `url`, such as
+    /// `https://example.com/my-tool/latest.txt`.
+    #[must_use]
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            strip_v: true,
+        }
+    }
+
+    /// Strip a leading `v` or `V` from the version. Defaults to `true`.
+    #[must_use]
+    pub const fn strip_v_prefix(mut self, strip: bool) -> Self {
+        self.strip_v = strip;
+        self
+    }
+
+    /// Explain a `404 Not Found`.
+    pub(crate) fn explain(&self, e: Error) -> Error {
+        if e.kind() != ErrorKind::NotFound {
+            return e;
+        }
+        e.noted(&format!(": version file {} was not found", self.url))
+    }
+}
+
+#[cfg(not(tiny_update_check_network_disabled))]
+impl VersionSource for TextSource {
+    fn latest(&self, context: &SourceContext<'_>) -> Result<String, Error> {
+        context.fetch_release(&ReleaseSource::Text(self.clone()))
+    }
+
+    fn id(&self) -> String {
+        self.url.clone()
+    }
+}
+
+/// Parse a plain-text version file: one version, surrounded by whitespace
+/// at most, and stripped of a leading `v` if `strip_v`.
+pub fn parse_text(body: &str, strip_v: bool) -> Result<Latest, Error> {
+    let text = body.trim();
+    if text.len() > MAX_TEXT_VERSION {
+        let start: String = text.chars().take(20).collect();
+        return Err(Error::ParseError(format!(
+            "expected a version, got {} bytes starting with '{}'",
+            text.len(),
+            start.escape_debug()
+        )));
+    }
+    let version = if strip_v {
+        text.strip_prefix(['v', 'V']).unwrap_or(text)
+    } else {
+        text
+    };
+    semver::Version::parse(version).map_err(|e| {
+        Error::ParseError(format!(
+            "'{}' is not a valid version: {e}",
+            version.escape_debug()
+        ))
+    })?;
+    Ok(Latest {
+        version: version.to_string(),
+        versions: None,
+        artifacts: Vec::new(),
+        updated_at: None,
+        registry_url: None,
+        race_winner: None,
+        max_age: None,
+        crate_name: None,
+        stale: false,
+        offline: false,
+        #[cfg(feature = "response-body")]
+        response_body: Some(body.to_string()),
+        #[cfg(not(feature = "response-body"))]
+        response_body: None,
+    })
+}
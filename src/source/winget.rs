@@ -0,0 +1,175 @@
+//! A winget package's highest manifest version, listed with the GitHub API.
+
+use super::github::DEFAULT_GITHUB_API_URL;
+use super::{ReleaseSource, SourceContext, VersionSource};
+#[cfg(doc)]
+use crate::{Comparison, UpdateChecker};
+use crate::{
+    Error, ErrorKind, FourPartVersion, Latest, NormalizedVersion, parse_response, status_error,
+};
+
+/// The repository holding the community winget manifests.
+const WINGET_PKGS_REPO: &str = "microsoft/winget-pkgs";
+
+/// A package in the community winget repository, whose highest manifest
+/// version is the latest version.
+///
+/// Passed to [`UpdateChecker::winget_package`]. For tools Windows users
+/// install with `winget`, this reports an update only once its manifest
+/// is merged into `microsoft/winget-pkgs`. The package's versions are
+/// listed with the GitHub API, authenticated with `GITHUB_TOKEN` if set.
+///
+/// Winget versions needn't be semantic versions, so they are read and
+/// compared as up to four numeric components, as with
+/// [`Comparison::FourPart`]: `1.2` is `1.2.0.0`, and `1.2.3.4` is newer
+/// than `1.2.3`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WingetSource {
+    package: String,
+    api_url: String,
+}
+
+impl WingetSource {
+    /// The package with the identifier `package`, such as
+    /// `BurntSushi.ripgrep.MSVC`.
+    #[must_use]
+    pub fn new(package: impl Into<String>) -> Self {
+        Self {
+            package: package.into(),
+            api_url: DEFAULT_GITHUB_API_URL.to_string(),
+        }
+    }
+
+    /// Set the base URL of the GitHub API. Defaults to
+    /// `https://api.github.com`.
+    #[must_use]
+    pub fn api_url(mut self, url: impl Into<String>) -> Self {
+        self.api_url = url.into();
+        self
+    }
+
+    /// The API URL listing the package's manifest directories, which
+    /// winget-pkgs keeps under the identifier's lowercased first letter and
+    /// its dot-separated parts, one directory per version.
+    pub(crate) fn versions_url(&self) -> String {
+        let api_url = self.api_url.trim_end_matches('/');
+        let letter = self
+            .package
+            .get(..1)
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        format!(
+            "{api_url}/repos/{WINGET_PKGS_REPO}/contents/manifests/{letter}/{}",
+            self.package.replace('.', "/")
+        )
+    }
+
+    /// Reject an identifier that isn't `Publisher.Package`.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        let id = &self.package;
+        let valid = id.contains('.')
+            && id.split('.').all(|part| {
+                !part.is_empty()
+                    && part
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+'))
+            });
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::ConfigError(format!(
+                "invalid winget package identifier: '{id}'"
+            )))
+        }
+    }
+
+    /// The GitHub API's headers, with `GITHUB_TOKEN` if set.
+    pub(crate) fn headers() -> Vec<(&'static str, String)> {
+        let mut headers = vec![
+            ("Accept", "application/vnd.github+json".to_string()),
+            ("X-GitHub-Api-Version", "2022-11-28".to_string()),
+        ];
+        if let Some(token) = std::env::var("GITHUB_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty())
+        {
+            headers.push(("Authorization", format!("Bearer {token}")));
+        }
+        headers
+    }
+
+    /// Explain a `404 Not Found`.
+    pub(crate) fn explain(&self, e: Error) -> Error {
+        if e.kind() != ErrorKind::NotFound {
+            return e;
+        }
+        e.noted(&format!(": winget package {} was not found", self.package))
+    }
+}
+
+#[cfg(not(tiny_update_check_network_disabled))]
+impl VersionSource for WingetSource {
+    fn latest(&self, context: &SourceContext<'_>) -> Result<String, Error> {
+        context.fetch_release(&ReleaseSource::Winget(self.clone()))
+    }
+
+    fn id(&self) -> String {
+        self.versions_url()
+    }
+}
+
+/// Parse the listing of a winget package's directory, taking the highest
+/// version among its subdirectories.
+///
+/// A directory whose name isn't a version of up to four numeric components
+/// is skipped; it may be another package whose identifier extends this
+/// one's. If none is a version, the error lists what was found.
+pub fn parse_winget_versions(body: &str) -> Result<Latest, Error> {
+    let json = parse_response(body)?;
+    let entries = json
+        .as_array()
+        .ok_or_else(|| Error::ParseError("expected a directory listing in response".to_string()))?;
+    let names: Vec<&str> = entries
+        .iter()
+        .filter(|entry| entry.get("type").and_then(serde_json::Value::as_str) == Some("dir"))
+        .filter_map(|entry| entry.get("name").and_then(serde_json::Value::as_str))
+        .collect();
+    let newest = names
+        .iter()
+        .filter_map(|name| {
+            FourPartVersion::parse(NormalizedVersion::parse(name).base)
+                .ok()
+                .map(|version| (version, *name))
+        })
+        .max_by(|(a, _), (b, _)| a.cmp(b));
+    let Some((_, version)) = newest else {
+        if names.is_empty() {
+            return Err(status_error(404));
+        }
+        let found = names
+            .iter()
+            .take(5)
+            .map(|name| format!("'{}'", name.escape_debug()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(Error::ParseError(format!(
+            "no winget manifest version could be read as up to four numeric components; found {found}"
+        )));
+    };
+    Ok(Latest {
+        version: version.to_string(),
+        versions: None,
+        artifacts: Vec::new(),
+        updated_at: None,
+        registry_url: None,
+        race_winner: None,
+        max_age: None,
+        crate_name: None,
+        stale: false,
+        offline: false,
+        #[cfg(feature = "response-body")]
+        response_body: Some(body.to_string()),
+        #[cfg(not(feature = "response-body"))]
+        response_body: None,
+    })
+}
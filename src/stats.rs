@@ -0,0 +1,82 @@
+//! Opt-in, in-process counters describing how checks were served.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::CheckEvent;
+
+/// Accumulates statistics across every check that shares it.
+///
+/// Create one, wrap it in an [`Arc`](std::sync::Arc), pass clones to each
+/// checker with [`UpdateChecker::stats`](crate::UpdateChecker::stats), and log
+/// a [`snapshot`](Self::snapshot) when the process exits, e.g. to judge
+/// whether a longer cache duration would be acceptable. Nothing is persisted.
+#[derive(Debug, Default)]
+pub struct CheckStats {
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    network_successes: AtomicU64,
+    network_failures: AtomicU64,
+    network_nanos: AtomicU64,
+}
+
+/// A point-in-time copy of a [`CheckStats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StatsSnapshot {
+    /// Checks answered from a fresh cache.
+    pub cache_hits: u64,
+    /// Checks that needed the registry because nothing fresh was cached.
+    pub cache_misses: u64,
+    /// Registry requests that produced a version.
+    pub network_successes: u64,
+    /// Registry requests that failed.
+    pub network_failures: u64,
+    /// Total time spent on registry requests.
+    pub network_time: Duration,
+}
+
+impl CheckStats {
+    /// Create a set of zeroed counters.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the current counter values.
+    #[must_use]
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.cache_misses.load(Ordering::Relaxed),
+            network_successes: self.network_successes.load(Ordering::Relaxed),
+            network_failures: self.network_failures.load(Ordering::Relaxed),
+            network_time: Duration::from_nanos(self.network_nanos.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Update the counters for an event emitted during a check.
+    pub(crate) fn record(&self, event: &CheckEvent) {
+        match event {
+            CheckEvent::CacheHit { .. } => {
+                self.cache_hits.fetch_add(1, Ordering::Relaxed);
+            }
+            CheckEvent::FetchStarted { .. } => {
+                self.cache_misses.fetch_add(1, Ordering::Relaxed);
+            }
+            CheckEvent::FetchCompleted {
+                elapsed, success, ..
+            } => {
+                let outcome = if *success {
+                    &self.network_successes
+                } else {
+                    &self.network_failures
+                };
+                outcome.fetch_add(1, Ordering::Relaxed);
+                let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+                self.network_nanos.fetch_add(nanos, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+}
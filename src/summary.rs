@@ -0,0 +1,175 @@
+//! One consolidated notice for every check an application runs.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::IsTerminal;
+use std::sync::{Mutex, PoisonError};
+
+use crate::{CheckOutcome, DetailedUpdateInfo, project};
+
+/// What a [`Summary`] entry was checked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Subject {
+    /// The application itself.
+    SelfUpdate,
+    /// A plugin or other component, by name.
+    Plugin(String),
+}
+
+/// Collects check outcomes from anywhere in an application and renders them
+/// as one block, for example at exit.
+///
+/// The block lists the application's own update first, then plugins with
+/// updates by name, whatever order outcomes were pushed in. Outcomes
+/// without an update are left out. A later outcome for the same subject
+/// replaces an earlier one.
+///
+/// ```
+/// use tiny_update_check::{CheckOutcome, DetailedUpdateInfo, Subject, Summary, SummaryStyle, UpdateInfo};
+///
+/// let summary = Summary::new();
+/// let update = |current: &str, latest: &str| {
+///     CheckOutcome::Update(DetailedUpdateInfo::from(UpdateInfo {
+///         current: current.to_string(),
+///         latest: latest.to_string(),
+///     }))
+/// };
+/// summary.push(Subject::Plugin("fmt".to_string()), update("0.3.0", "0.4.0"));
+/// summary.push(Subject::SelfUpdate, update("1.0.0", "1.1.0"));
+/// assert_eq!(
+///     summary.render(SummaryStyle::new()).unwrap(),
+///     "Update available: 1.0.0 -> 1.1.0\n\
+///      1 plugin update available:\n  fmt 0.3.0 -> 0.4.0\n"
+/// );
+/// ```
+///
+/// A summary is `Send + Sync`, so background tasks can push to a shared one.
+#[derive(Debug, Default)]
+pub struct Summary {
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    own: Option<CheckOutcome>,
+    plugins: BTreeMap<String, CheckOutcome>,
+}
+
+impl Summary {
+    /// Create an empty summary.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the outcome of checking `subject`.
+    pub fn push(&self, subject: Subject, outcome: CheckOutcome) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        match subject {
+            Subject::SelfUpdate => entries.own = Some(outcome),
+            Subject::Plugin(name) => {
+                entries.plugins.insert(name, outcome);
+            }
+        }
+    }
+
+    /// Render the summary, or `None` if `style` suppresses it or there is no
+    /// update to report.
+    ///
+    /// Each line ends with a newline, ready for `eprint!`.
+    #[must_use]
+    pub fn render(&self, style: SummaryStyle) -> Option<String> {
+        if style.suppressed() {
+            return None;
+        }
+        let block = self
+            .entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .render();
+        (!block.is_empty()).then_some(block)
+    }
+}
+
+impl Entries {
+    /// The rendered block, empty if there is no update to report.
+    fn render(&self) -> String {
+        let mut block = String::new();
+        if let Some(CheckOutcome::Update(ref info)) = self.own {
+            block.push_str(&info.summary());
+            block.push('\n');
+            if let Some(ref message) = info.message {
+                block.push_str(message);
+                block.push('\n');
+            }
+        }
+        let plugins: Vec<(&String, &DetailedUpdateInfo)> = self
+            .plugins
+            .iter()
+            .filter_map(|(name, outcome)| match outcome {
+                CheckOutcome::Update(info) => Some((name, info)),
+                _ => None,
+            })
+            .collect();
+        if !plugins.is_empty() {
+            let plural = if plugins.len() == 1 { "" } else { "s" };
+            let _ = writeln!(block, "{} plugin update{plural} available:", plugins.len());
+            for (name, info) in plugins {
+                let _ = writeln!(block, "  {name} {} -> {}", info.current, info.latest);
+            }
+        }
+        block
+    }
+}
+
+/// When a [`Summary`] is rendered, applied once to the whole block.
+///
+/// By default the summary always renders.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SummaryStyle {
+    quiet: bool,
+    require_terminal: bool,
+    outside_ci: bool,
+}
+
+impl SummaryStyle {
+    /// A style that always renders.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            quiet: false,
+            require_terminal: false,
+            outside_ci: false,
+        }
+    }
+
+    /// Render nothing, e.g. for a `--quiet` flag.
+    #[must_use]
+    pub const fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Render only if standard error is a terminal, so logs and pipes stay
+    /// clean.
+    #[must_use]
+    pub const fn require_terminal(mut self, require: bool) -> Self {
+        self.require_terminal = require;
+        self
+    }
+
+    /// Render nothing in continuous integration, detected by a non-empty
+    /// `CI` variable other than `0` or `false`.
+    #[must_use]
+    pub const fn outside_ci(mut self, outside_ci: bool) -> Self {
+        self.outside_ci = outside_ci;
+        self
+    }
+
+    fn suppressed(self) -> bool {
+        self.quiet
+            || (self.require_terminal && !std::io::stderr().is_terminal())
+            || (self.outside_ci && project::in_ci())
+    }
+}
@@ -0,0 +1,412 @@
+//! TLS settings beyond a transport's defaults: the root certificates added
+//! with [`UpdateChecker::add_root_certificate`](crate::UpdateChecker::add_root_certificate)
+//! or named by `SSL_CERT_FILE` and `SSL_CERT_DIR`, the
+//! [minimum version](crate::UpdateChecker::min_tls_version), and
+//! [pinned keys](crate::UpdateChecker::pin_certificates).
+//!
+//! Certificates are kept as given, PEM or DER, and checked before each check
+//! so bad data fails it with a clear error rather than a handshake failure.
+
+use std::fs;
+use std::path::Path;
+
+use crate::Error;
+
+/// The variables naming a bundle of root certificates and directories of
+/// them, as OpenSSL reads them.
+const CERT_FILE_VAR: &str = "SSL_CERT_FILE";
+const CERT_DIR_VAR: &str = "SSL_CERT_DIR";
+
+/// Marks the start and end of a certificate in PEM.
+const PEM_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_END: &str = "-----END CERTIFICATE-----";
+
+/// A version of TLS.
+///
+/// Configured with [`UpdateChecker::min_tls_version`](crate::UpdateChecker::min_tls_version).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum TlsVersion {
+    /// TLS 1.2, the oldest version the `rustls` and `async` transports
+    /// negotiate anyway.
+    Tls1_2,
+    /// TLS 1.3.
+    Tls1_3,
+}
+
+/// The TLS implementation, and HTTP client, a check's requests are sent
+/// with.
+///
+/// Chosen by feature: `rustls` if it is enabled, or else `native-tls`. With
+/// both enabled, `UpdateChecker::tls_provider` chooses at runtime, and
+/// [`UpdateChecker::tls_provider_in_use`](crate::UpdateChecker::tls_provider_in_use)
+/// reports the choice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TlsProvider {
+    /// The system's TLS library, through `minreq`: the `native-tls` feature.
+    NativeTls,
+    /// rustls with ring, through `ureq`: the `rustls` feature.
+    Rustls,
+}
+
+impl Default for TlsProvider {
+    /// `rustls` if it was compiled in, or else `native-tls`.
+    fn default() -> Self {
+        if cfg!(feature = "rustls") {
+            Self::Rustls
+        } else {
+            Self::NativeTls
+        }
+    }
+}
+
+impl std::fmt::Display for TlsProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::NativeTls => "native-tls",
+            Self::Rustls => "rustls",
+        })
+    }
+}
+
+/// The SHA-256 hash of a certificate's public key, as its DER-encoded
+/// `SubjectPublicKeyInfo`, to [pin](crate::UpdateChecker::pin_certificates).
+///
+/// This is the hash curl's `--pinnedpubkey` takes, which OpenSSL prints for
+/// a certificate with:
+///
+/// ```sh
+/// openssl x509 -in cert.pem -pubkey -noout | openssl pkey -pubin -outform der \
+///     | openssl dgst -sha256 -binary | base64
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Spki256([u8; 32]);
+
+impl Spki256 {
+    /// The pin for a key with SHA-256 hash `hash`.
+    #[must_use]
+    pub const fn new(hash: [u8; 32]) -> Self {
+        Self(hash)
+    }
+
+    /// The pin for a hash in base64, optionally prefixed with `sha256//` as
+    /// curl writes it, or `None` if `encoded` isn't 32 bytes of base64.
+    ///
+    /// ```
+    /// use tiny_update_check::Spki256;
+    ///
+    /// let pin = Spki256::from_base64("sha256//eWbHKcSO/CeOjgXTiIiOrYKvfCdiPYKGe+eV81QY34w=");
+    /// assert!(pin.is_some());
+    /// ```
+    #[must_use]
+    pub fn from_base64(encoded: &str) -> Option<Self> {
+        let encoded = encoded.strip_prefix("sha256//").unwrap_or(encoded);
+        base64_decode(encoded)?.try_into().ok().map(Self)
+    }
+
+    /// The hash.
+    #[must_use]
+    pub const fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Spki256 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "sha256//{}", crate::base64(&self.0))
+    }
+}
+
+impl std::fmt::Debug for Spki256 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Spki256({self})")
+    }
+}
+
+/// How requests are secured, beyond the transport's defaults.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct TlsSettings {
+    /// The added root certificates, as given.
+    roots: Vec<Root>,
+    /// The oldest TLS version to negotiate, if not the transport's.
+    min_version: Option<TlsVersion>,
+    /// The keys one of which the server's verified chain must have.
+    pins: Vec<Spki256>,
+    /// Whether to skip verifying the server's certificate. Only set with the
+    /// `dangerous-config` feature.
+    accept_invalid_certs: bool,
+}
+
+/// A root certificate, or a bundle of them, and how to name it in errors.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Root {
+    data: Vec<u8>,
+    name: String,
+}
+
+impl TlsSettings {
+    /// Trust `cert`, PEM or DER, as a root certificate.
+    pub fn add_root(&mut self, cert: Vec<u8>) {
+        let name = format!("root certificate {}", self.roots.len() + 1);
+        self.roots.push(Root { data: cert, name });
+    }
+
+    /// These settings with the certificates in the bundle `SSL_CERT_FILE`
+    /// names and in the files of the directories `SSL_CERT_DIR` lists added,
+    /// failing with [`Error::ConfigError`] naming a path that can't be read.
+    ///
+    /// Files in the directories without a PEM certificate, such as CRLs,
+    /// are skipped.
+    pub fn with_env(&self) -> Result<Self, Error> {
+        let mut tls = self.clone();
+        if let Some(file) = std::env::var_os(CERT_FILE_VAR).filter(|file| !file.is_empty()) {
+            let path = Path::new(&file);
+            let data = fs::read(path).map_err(|e| unreadable(path, CERT_FILE_VAR, &e))?;
+            tls.roots.push(Root {
+                data,
+                name: env_name(path, CERT_FILE_VAR),
+            });
+        }
+        if let Some(dirs) = std::env::var_os(CERT_DIR_VAR) {
+            for dir in std::env::split_paths(&dirs).filter(|dir| !dir.as_os_str().is_empty()) {
+                let mut paths: Vec<_> = fs::read_dir(&dir)
+                    .map_err(|e| unreadable(&dir, CERT_DIR_VAR, &e))?
+                    .filter_map(|entry| Some(entry.ok()?.path()))
+                    .filter(|path| path.is_file())
+                    .collect();
+                paths.sort();
+                for path in paths {
+                    let data = fs::read(&path).map_err(|e| unreadable(&path, CERT_DIR_VAR, &e))?;
+                    if data
+                        .windows(PEM_BEGIN.len())
+                        .any(|window| window == PEM_BEGIN.as_bytes())
+                    {
+                        tls.roots.push(Root {
+                            data,
+                            name: env_name(&path, CERT_DIR_VAR),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(tls)
+    }
+
+    /// Negotiate `version` or newer only.
+    pub const fn set_min_version(&mut self, version: TlsVersion) {
+        self.min_version = Some(version);
+    }
+
+    /// The oldest TLS version to negotiate, if not the transport's.
+    pub const fn min_version(&self) -> Option<TlsVersion> {
+        self.min_version
+    }
+
+    /// Require one of `pins` in the server's verified chain, or nothing if
+    /// there are none.
+    pub fn set_pins(&mut self, pins: Vec<Spki256>) {
+        self.pins = pins;
+    }
+
+    /// The keys one of which the server's verified chain must have.
+    pub fn pins(&self) -> &[Spki256] {
+        &self.pins
+    }
+
+    /// Skip verifying the server's certificate if `accept`.
+    #[cfg(feature = "dangerous-config")]
+    pub const fn set_accept_invalid_certs(&mut self, accept: bool) {
+        self.accept_invalid_certs = accept;
+    }
+
+    /// Whether to skip verifying the server's certificate.
+    pub const fn accepts_invalid_certs(&self) -> bool {
+        self.accept_invalid_certs
+    }
+
+    /// Whether any root certificate was added.
+    pub const fn has_roots(&self) -> bool {
+        !self.roots.is_empty()
+    }
+
+    /// Fail with [`Error::ConfigError`] naming the first added root
+    /// certificate that is neither PEM nor DER.
+    pub fn validate(&self) -> Result<(), Error> {
+        for root in &self.roots {
+            certificates(&root.data)
+                .map_err(|e| Error::ConfigError(format!("{} {e}", root.name)))?;
+        }
+        Ok(())
+    }
+
+    /// The DER of every added root certificate, once each, skipping any
+    /// that [`validate`](Self::validate) rejects.
+    #[cfg(any(feature = "rustls", feature = "async"))]
+    pub fn root_ders(&self) -> Vec<Vec<u8>> {
+        // `SSL_CERT_DIR` usually links each certificate under its hash too.
+        let mut ders: Vec<_> = self
+            .roots
+            .iter()
+            .filter_map(|root| certificates(&root.data).ok())
+            .flatten()
+            .collect();
+        ders.sort_unstable();
+        ders.dedup();
+        ders
+    }
+}
+
+impl std::fmt::Debug for TlsSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("TlsSettings");
+        debug
+            .field("roots", &self.roots.len())
+            .field("min_version", &self.min_version)
+            .field("pins", &self.pins);
+        // Shown when on, so support bundles reveal it.
+        if self.accept_invalid_certs {
+            debug.field("danger_accept_invalid_certs", &true);
+        }
+        debug.finish()
+    }
+}
+
+/// How errors name `path`, read because `var` names it.
+fn env_name(path: &Path, var: &str) -> String {
+    format!("`{}` from `{var}`", path.display())
+}
+
+/// The error for `path`, named by `var`, failing to be read with `e`.
+fn unreadable(path: &Path, var: &str, e: &std::io::Error) -> Error {
+    Error::ConfigError(format!("{} can't be read: {e}", env_name(path, var)))
+}
+
+/// The DER certificates in `data`: each `CERTIFICATE` block of a PEM file,
+/// or else `data` itself as a single DER certificate.
+fn certificates(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let Some(text) = std::str::from_utf8(data)
+        .ok()
+        .filter(|text| text.contains("-----BEGIN"))
+    else {
+        return if is_der_sequence(data) {
+            Ok(vec![data.to_vec()])
+        } else {
+            Err("is neither PEM nor a DER certificate".to_string())
+        };
+    };
+
+    let mut ders = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(PEM_BEGIN) {
+        let body = &rest[start + PEM_BEGIN.len()..];
+        let end = body
+            .find(PEM_END)
+            .ok_or_else(|| format!("has no `{PEM_END}` after certificate {}", ders.len() + 1))?;
+        let der = base64_decode(&body[..end])
+            .filter(|der| is_der_sequence(der))
+            .ok_or_else(|| format!("has a malformed PEM certificate {}", ders.len() + 1))?;
+        ders.push(der);
+        rest = &body[end + PEM_END.len()..];
+    }
+    if ders.is_empty() {
+        return Err(format!("is PEM without a `{PEM_BEGIN}` block"));
+    }
+    Ok(ders)
+}
+
+/// Whether `der` is exactly one DER `SEQUENCE`, as every certificate is.
+fn is_der_sequence(der: &[u8]) -> bool {
+    let [0x30, first, rest @ ..] = der else {
+        return false;
+    };
+    let (length, contents) = if *first < 0x80 {
+        (usize::from(*first), rest)
+    } else {
+        let octets = usize::from(first & 0x7f);
+        if !(1..=4).contains(&octets) || rest.len() < octets {
+            return false;
+        }
+        let (length, contents) = rest.split_at(octets);
+        let length = length
+            .iter()
+            .fold(0, |length, &octet| (length << 8) | usize::from(octet));
+        (length, contents)
+    };
+    length == contents.len()
+}
+
+/// Decode standard, padded base64, ignoring whitespace.
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            _ => return None,
+        };
+        buffer = ((buffer << 6) | u32::from(value)) & 0xffff;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits).to_le_bytes()[0]);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CA_PEM: &[u8] = include_bytes!("../tests/fixtures/tls/ca.pem");
+    const CA_DER: &[u8] = include_bytes!("../tests/fixtures/tls/ca.der");
+
+    #[test]
+    fn pem_and_der_certificates_are_read() {
+        assert_eq!(certificates(CA_PEM).unwrap(), vec![CA_DER.to_vec()]);
+        assert_eq!(certificates(CA_DER).unwrap(), vec![CA_DER.to_vec()]);
+
+        let bundle = [CA_PEM, b"\n", CA_PEM].concat();
+        assert_eq!(certificates(&bundle).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn invalid_certificates_are_named() {
+        let mut tls = TlsSettings::default();
+        tls.add_root(CA_PEM.to_vec());
+        tls.add_root(b"not a certificate".to_vec());
+        let err = tls.validate().unwrap_err().to_string();
+        assert!(err.contains("root certificate 2 is neither PEM"), "{err}");
+
+        let truncated = &CA_PEM[..CA_PEM.len() - 40];
+        assert!(
+            certificates(truncated)
+                .unwrap_err()
+                .contains("no `-----END")
+        );
+        let corrupt = String::from_utf8(CA_PEM.to_vec())
+            .unwrap()
+            .replacen("MII", "M*I", 1);
+        assert!(certificates(corrupt.as_bytes()).is_err());
+        assert!(certificates(&CA_DER[..CA_DER.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn pins_round_trip_through_base64() {
+        let encoded = "sha256//gdZ7X6EO/2HnWj3U30zd0qk4TcmMKSSqkpVOvsp9fjU=";
+        let pin = Spki256::from_base64(encoded).unwrap();
+        assert_eq!(pin.to_string(), encoded);
+        assert_eq!(Spki256::from_base64(&encoded[8..]), Some(pin));
+        assert_eq!(Spki256::new(*pin.as_bytes()), pin);
+
+        assert_eq!(Spki256::from_base64("sha256//gdZ7X6EO"), None);
+        assert_eq!(Spki256::from_base64("not base64!"), None);
+    }
+}
@@ -4,8 +4,15 @@
 
 #![cfg(feature = "async")]
 
+mod common;
+
+use common::{MockRegistry, parse_head, planned_head};
 use std::time::Duration;
 use tiny_update_check::r#async::UpdateChecker;
+use tiny_update_check::{
+    AsyncContextPolicy, CheckEvent, CheckMode, CheckOutcome, Error, ErrorKind, GitHubSource,
+    GitLabSource, GitTagsSource, OciSource, RaceWinner, SourceContext, TextSource, VersionSource,
+};
 
 #[tokio::test]
 async fn async_checker_builds() {
@@ -21,29 +28,1125 @@ async fn async_checker_with_options() {
 }
 
 #[tokio::test]
-async fn async_check_real_crate() {
-    let checker = UpdateChecker::new("serde", "0.0.1").cache_duration(Duration::ZERO); // Disable cache for test
-
+async fn async_check_validates_crate_name() {
+    let checker = UpdateChecker::new("", "1.0.0");
     let result = checker.check().await;
-    assert!(result.is_ok(), "Check should succeed: {:?}", result.err());
+    assert!(result.is_err());
+}
 
-    let update = result.unwrap();
-    assert!(update.is_some(), "Should find update from 0.0.1");
+/// Build a checker whose async event callback forwards every event into a channel.
+fn with_event_channel(
+    checker: UpdateChecker,
+) -> (
+    UpdateChecker,
+    tokio::sync::mpsc::UnboundedReceiver<tiny_update_check::CheckEvent>,
+) {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let checker = checker.on_event_async(move |event| {
+        let tx = tx.clone();
+        Box::pin(async move {
+            let _ = tx.send(event);
+        })
+    });
+    (checker, rx)
+}
 
-    let info = update.unwrap();
-    assert_eq!(info.current, "0.0.1");
-    assert!(!info.latest.is_empty());
+async fn next_event(
+    rx: &mut tokio::sync::mpsc::UnboundedReceiver<tiny_update_check::CheckEvent>,
+) -> tiny_update_check::CheckEvent {
+    tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("event should arrive")
+        .expect("channel should stay open")
 }
 
 #[tokio::test]
-async fn async_check_validates_crate_name() {
-    let checker = UpdateChecker::new("", "1.0.0");
-    let result = checker.check().await;
-    assert!(result.is_err());
+async fn async_events_report_cache_hit() {
+    use tiny_update_check::CheckEvent;
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("serde-update-check"), "99.0.0").unwrap();
+
+    let (checker, mut rx) = with_event_channel(
+        UpdateChecker::new("serde", "1.0.0").cache_dir(Some(dir.path().to_path_buf())),
+    );
+
+    let update = checker.check().await.unwrap().unwrap();
+    assert_eq!(update.latest, "99.0.0");
+    assert_eq!(
+        next_event(&mut rx).await,
+        CheckEvent::CacheHit {
+            version: "99.0.0".to_string()
+        }
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn async_callbacks_run_in_event_order() {
+    use tiny_update_check::CheckEvent;
+
+    let registry = MockRegistry::crates(&[("tool", "1.0.0")]);
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let checker = UpdateChecker::new("tool", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None)
+        .on_event_async(move |event| {
+            let tx = tx.clone();
+            Box::pin(async move {
+                // Earlier events take longer, so unordered callbacks would
+                // finish in reverse.
+                let delay = match event {
+                    CheckEvent::FetchStarted { .. } => 200,
+                    CheckEvent::FetchCompleted { .. } => 50,
+                    _ => 0,
+                };
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                let _ = tx.send(event);
+            })
+        });
+
+    assert!(checker.check().await.unwrap().is_none());
+    drop(checker);
+    let mut events = Vec::new();
+    while let Some(event) = next_or_closed(&mut rx).await {
+        events.push(event);
+    }
+    assert!(
+        matches!(
+            events.as_slice(),
+            [
+                CheckEvent::FetchStarted { .. },
+                CheckEvent::FetchCompleted { .. },
+                CheckEvent::NoUpdate { .. }
+            ]
+        ),
+        "{events:?}"
+    );
+}
+
+#[tokio::test]
+async fn hung_async_callbacks_hold_back_a_bounded_queue() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tiny_update_check::CheckEvent;
+
+    let registry = MockRegistry::crates(&[("tool", "1.0.0")]);
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let first = AtomicBool::new(true);
+    let checker = UpdateChecker::new("tool", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None)
+        .on_event_async(move |event| {
+            let tx = tx.clone();
+            let hang = first.swap(false, Ordering::SeqCst);
+            Box::pin(async move {
+                if hang {
+                    std::future::pending::<()>().await;
+                }
+                let _ = tx.send(event);
+            })
+        });
+
+    // Three events each, all behind the first one's callback, which never
+    // completes: the checks don't wait for it.
+    for _ in 0..20 {
+        let check = tokio::time::timeout(Duration::from_secs(5), checker.check());
+        assert!(check.await.unwrap().unwrap().is_none());
+    }
+    drop(checker);
+
+    // Once it times out, the queued events are delivered in order, and those
+    // that didn't fit in the queue are dropped.
+    tokio::time::pause();
+    tokio::time::advance(Duration::from_secs(5)).await;
+    let mut events = Vec::new();
+    while let Some(event) = next_or_closed(&mut rx).await {
+        events.push(event);
+    }
+    assert_eq!(events.len(), 32);
+    for (event, expected) in events.iter().zip([1, 2, 0].into_iter().cycle()) {
+        let position = match event {
+            CheckEvent::FetchStarted { .. } => 0,
+            CheckEvent::FetchCompleted { .. } => 1,
+            CheckEvent::NoUpdate { .. } => 2,
+            _ => panic!("unexpected {event:?}"),
+        };
+        assert_eq!(position, expected, "{events:?}");
+    }
+}
+
+async fn next_or_closed(
+    rx: &mut tokio::sync::mpsc::UnboundedReceiver<tiny_update_check::CheckEvent>,
+) -> Option<tiny_update_check::CheckEvent> {
+    tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("events should arrive")
+}
+
+#[tokio::test]
+async fn slow_or_panicking_async_callbacks_do_not_hang_check() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("serde-update-check"), "99.0.0").unwrap();
+
+    let slow = UpdateChecker::new("serde", "1.0.0")
+        .cache_dir(Some(dir.path().to_path_buf()))
+        .on_event_async(|_| Box::pin(tokio::time::sleep(Duration::from_secs(60))));
+    let result = tokio::time::timeout(Duration::from_secs(5), slow.check()).await;
+    assert!(result.expect("check should not wait for callback").is_ok());
+
+    let panicking_future = UpdateChecker::new("serde", "1.0.0")
+        .cache_dir(Some(dir.path().to_path_buf()))
+        .on_event_async(|_| Box::pin(async { panic!("callback future panicked") }));
+    assert!(panicking_future.check().await.unwrap().is_some());
+
+    let panicking_callback = UpdateChecker::new("serde", "1.0.0")
+        .cache_dir(Some(dir.path().to_path_buf()))
+        .on_event_async(|_| panic!("callback panicked"));
+    assert!(panicking_callback.check().await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn async_bom_prefixed_response_parses() {
+    let registry = MockRegistry::serve(include_bytes!("fixtures/bom_prefixed.json").as_slice());
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None);
+
+    let update = checker.check().await.unwrap().unwrap();
+    assert_eq!(update.latest, "2.0.0");
+}
+
+#[tokio::test]
+async fn async_invalid_utf8_response_reports_offset() {
+    let registry = MockRegistry::serve(b"{\"crate\":\xff}".as_slice());
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None);
+
+    match checker.check().await {
+        Err(Error::ParseError(msg)) => {
+            assert_eq!(msg, "response is not valid UTF-8 at byte 9");
+        }
+        other => panic!("expected a UTF-8 parse error, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn async_newer_successor_crate_is_reported_as_rename() {
+    let registry = MockRegistry::crates(&[("oldtool", "1.5.0"), ("newtool", "2.0.0")]);
+    let checker = UpdateChecker::new("oldtool", "1.4.0")
+        .successor_crate("newtool")
+        .registry_url(registry.url())
+        .cache_dir(None);
+
+    let update = checker.check_detailed().await.unwrap().unwrap();
+    assert_eq!(update.latest, "2.0.0");
+    assert_eq!(update.renamed_to.as_deref(), Some("newtool"));
+}
+
+#[tokio::test]
+async fn async_dry_run_matches_the_requests_sent() {
+    // The successor is missing, so the check falls back to the crate itself.
+    let registry = MockRegistry::crates(&[("oldtool", "1.5.0")]);
+    let checker = UpdateChecker::new("oldtool", "1.4.0")
+        .successor_crate("newtool")
+        .registry_url(registry.url())
+        .cache_dir(None);
+
+    let planned: Vec<_> = checker
+        .dry_run()
+        .unwrap()
+        .into_iter()
+        .map(|request| planned_head(request, registry.url()))
+        .collect();
+    assert!(registry.requests().is_empty());
+
+    assert!(checker.check().await.unwrap().is_some());
+    let sent: Vec<_> = registry
+        .requests()
+        .iter()
+        .map(|head| parse_head(head))
+        .collect();
+    assert_eq!(planned, sent);
+    assert_eq!(sent.len(), 2);
+}
+
+#[tokio::test]
+async fn async_sparse_index_lookup() {
+    let body = std::fs::read(
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/index/se/rd/serde"),
+    )
+    .unwrap();
+    let index = MockRegistry::serve(body);
+    let update = UpdateChecker::new("serde", "1.0.0")
+        .use_sparse_index(true)
+        .sparse_index_url(index.url())
+        .cache_dir(None)
+        .check()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(update.latest, "1.0.218");
+    let (_, path, _) = parse_head(&index.requests()[0]);
+    assert_eq!(path, "/se/rd/serde");
+}
+
+#[tokio::test]
+async fn async_github_releases_are_an_alternative_source() {
+    let github = MockRegistry::serve_with(|path| {
+        if path == "/repos/octo/tool/releases/latest" {
+            (200, br#"{"tag_name":"v2.1.0"}"#.to_vec())
+        } else {
+            (404, br#"{"message":"Not Found"}"#.to_vec())
+        }
+    });
+    let checker = UpdateChecker::new("tool", "2.0.0")
+        .github_releases(GitHubSource::new("octo", "tool").api_url(github.url()))
+        .cache_dir(None);
+    let planned: Vec<_> = checker
+        .dry_run()
+        .unwrap()
+        .into_iter()
+        .map(|request| planned_head(request, github.url()))
+        .collect();
+    assert_eq!(checker.check().await.unwrap().unwrap().latest, "2.1.0");
+    let sent: Vec<_> = github
+        .requests()
+        .iter()
+        .map(|head| parse_head(head))
+        .collect();
+    assert_eq!(planned, sent);
+
+    let err = checker
+        .github_releases(GitHubSource::new("octo", "gone").api_url(github.url()))
+        .check()
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains("octo/gone"), "{err}");
+}
+
+#[tokio::test]
+async fn async_github_releases_feed() {
+    let feeds = MockRegistry::serve_with(|path| {
+        if path == "/BurntSushi/ripgrep/releases.atom" {
+            (200, include_bytes!("fixtures/atom/ripgrep.atom").to_vec())
+        } else {
+            (404, b"Not Found".to_vec())
+        }
+    });
+    let checker = UpdateChecker::new("ripgrep", "15.0.0")
+        .github_releases(
+            GitHubSource::new("BurntSushi", "ripgrep")
+                .releases_feed(true)
+                .web_url(feeds.url()),
+        )
+        .cache_dir(None);
+    let planned = checker.dry_run().unwrap();
+    assert_eq!(checker.check().await.unwrap().unwrap().latest, "15.1.0");
+    assert_eq!(
+        planned_head(planned[0].clone(), feeds.url()),
+        parse_head(&feeds.requests()[0])
+    );
+}
+
+#[tokio::test]
+async fn async_text_source() {
+    let mirror = MockRegistry::serve(" v0.8.1 \n");
+    let checker = UpdateChecker::new("tool", "0.8.0")
+        .text_source(TextSource::new(format!("{}/latest.txt", mirror.url())))
+        .cache_dir(None);
+    assert_eq!(checker.check().await.unwrap().unwrap().latest, "0.8.1");
+}
+
+#[tokio::test]
+async fn async_oci_tags() {
+    let registry = MockRegistry::serve_with_headers(|path| match path {
+        "/v2/library/tool/tags/list" => (
+            200,
+            vec![(
+                "Link",
+                r#"</v2/library/tool/tags/list?last=2>; rel="next""#.to_string(),
+            )],
+            br#"{"tags":["latest","0.9.0"]}"#.to_vec(),
+        ),
+        "/v2/library/tool/tags/list?last=2" => {
+            (200, vec![], br#"{"tags":["1.1.0","sha-f00"]}"#.to_vec())
+        }
+        _ => (404, vec![], b"{}".to_vec()),
+    });
+    let checker = UpdateChecker::new("tool", "1.0.0")
+        .oci_tags(OciSource::new(registry.url(), "library/tool"))
+        .cache_dir(None);
+    assert_eq!(checker.check().await.unwrap().unwrap().latest, "1.1.0");
+    assert_eq!(registry.requests().len(), 2);
+}
+
+struct Fixed;
+
+impl VersionSource for Fixed {
+    fn latest(&self, _context: &SourceContext<'_>) -> Result<String, Error> {
+        Ok("3.0.0".to_string())
+    }
+
+    fn id(&self) -> String {
+        "fixed".to_string()
+    }
+}
+
+#[tokio::test]
+async fn async_version_source() {
+    let checker = UpdateChecker::new("tool", "2.0.0")
+        .with_source(Fixed)
+        .cache_dir(None);
+    assert_eq!(checker.check().await.unwrap().unwrap().latest, "3.0.0");
+}
+
+/// A source that reads the version from a URL.
+struct Fetched(String);
+
+impl VersionSource for Fetched {
+    fn latest(&self, context: &SourceContext<'_>) -> Result<String, Error> {
+        Ok(context.get(&self.0)?.trim().to_string())
+    }
+
+    fn id(&self) -> String {
+        self.0.clone()
+    }
+}
+
+#[tokio::test]
+async fn async_version_sources_fetch_with_the_checkers_client() {
+    let text = MockRegistry::serve("3.1.0\n");
+    let checker = UpdateChecker::new("tool", "2.0.0")
+        .with_source(Fetched(format!("{}/latest.txt", text.url())))
+        .header("X-Team", "tools")
+        .cache_dir(None);
+    assert_eq!(checker.check().await.unwrap().unwrap().latest, "3.1.0");
+    let (_, _, headers) = parse_head(&text.requests()[0]);
+    assert!(headers.contains(&("x-team".to_string(), "tools".to_string())));
+}
+
+#[tokio::test]
+async fn async_fallback_registries() {
+    let down = MockRegistry::serve_status(502, "Bad Gateway");
+    let mirror = MockRegistry::crates(&[("tool", "2.1.0")]);
+    let update = UpdateChecker::new("tool", "2.0.0")
+        .registry_url(down.url())
+        .fallback_registries(vec![mirror.url().to_string()])
+        .cache_dir(None)
+        .check_detailed()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(update.latest, "2.1.0");
+    assert_eq!(update.registry_url.as_deref(), Some(mirror.url()));
+}
+
+#[tokio::test]
+async fn async_auth_token() {
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let checker = |registry: &MockRegistry| {
+        UpdateChecker::new("tool", "1.0.0")
+            .registry_url(registry.url())
+            .auth_token("cio_s3cret")
+            .cache_dir(None)
+    };
+    assert!(checker(&registry).check().await.unwrap().is_some());
+    let (_, _, headers) = parse_head(&registry.requests()[0]);
+    assert!(headers.contains(&("authorization".to_string(), "cio_s3cret".to_string())));
+
+    let refused = MockRegistry::serve_status(401, "");
+    let err = checker(&refused).check().await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unauthorized);
+    assert!(!format!("{:?}", checker(&refused)).contains("cio_s3cret"));
+}
+
+#[tokio::test]
+async fn async_retries() {
+    let body = br#"{"crate":{"newest_version":"2.0.0"}}"#;
+    let checker = |registry: &MockRegistry, retries| {
+        UpdateChecker::new("tool", "1.0.0")
+            .registry_url(registry.url())
+            .retries(retries)
+            .total_deadline(Duration::from_secs(5))
+            .cache_dir(None)
+    };
+
+    let registry = MockRegistry::flaky(2, body.to_vec());
+    let update = checker(&registry, 2).check().await.unwrap().unwrap();
+    assert_eq!(update.latest, "2.0.0");
+    assert_eq!(registry.requests().len(), 3);
+
+    let registry = MockRegistry::flaky(2, body.to_vec());
+    let err = checker(&registry, 1).check().await.unwrap_err();
+    assert_eq!(err.attempts(), Some(2));
+
+    let registry = MockRegistry::serve_status(404, "");
+    let err = checker(&registry, 3).check().await.unwrap_err();
+    assert_eq!(err.attempts(), None);
+    assert_eq!(registry.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn async_basic_auth() {
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let checker = |registry: &MockRegistry| {
+        UpdateChecker::new("tool", "1.0.0")
+            .registry_url(registry.url())
+            .basic_auth("deploy", "p@ss:wörd/+=")
+            .cache_dir(None)
+    };
+    assert!(checker(&registry).check().await.unwrap().is_some());
+    let (_, _, headers) = parse_head(&registry.requests()[0]);
+    assert!(headers.contains(&(
+        "authorization".to_string(),
+        "Basic ZGVwbG95OnBAc3M6d8O2cmQvKz0=".to_string()
+    )));
+
+    let refused = MockRegistry::serve_status(401, "");
+    let err = checker(&refused).check().await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unauthorized);
+    assert!(err.to_string().contains("registry 127.0.0.1"), "{err}");
+    assert!(!format!("{:?}", checker(&refused)).contains("ZGVwbG95"));
+}
+
+#[tokio::test]
+async fn async_race_registry() {
+    let github = MockRegistry::serve_with(|path| {
+        std::thread::sleep(Duration::from_millis(300));
+        if path == "/repos/octo/tool/releases/latest" {
+            (200, br#"{"tag_name":"v2.1.0"}"#.to_vec())
+        } else {
+            (404, Vec::new())
+        }
+    });
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let checker = |registry: &MockRegistry| {
+        UpdateChecker::new("tool", "1.0.0")
+            .github_releases(GitHubSource::new("octo", "tool").api_url(github.url()))
+            .registry_url(registry.url())
+            .race_registry(true)
+            .cache_dir(None)
+    };
+    let update = checker(&registry).check_detailed().await.unwrap().unwrap();
+    assert_eq!(update.latest, "2.0.0");
+    assert_eq!(update.race_winner, Some(RaceWinner::Registry));
+
+    // A fast failure waits for the slower success.
+    let down = MockRegistry::serve_status(500, "down");
+    let update = checker(&down).check_detailed().await.unwrap().unwrap();
+    assert_eq!(update.latest, "2.1.0");
+    assert_eq!(update.race_winner, Some(RaceWinner::ReleaseSource));
+}
+
+#[tokio::test]
+async fn async_git_tags() {
+    let forge = MockRegistry::serve_with(|path| match path {
+        "/tool.git/info/refs?service=git-upload-pack" => (
+            200,
+            b"1111111111111111111111111111111111111111\trefs/tags/v1.3.0\n".to_vec(),
+        ),
+        "/tags" => (200, br#"["v1.0.0", "v1.2.0", "junk"]"#.to_vec()),
+        _ => (404, b"Not Found".to_vec()),
+    });
+    let checker = |source| {
+        UpdateChecker::new("tool", "1.0.0")
+            .git_tags(source)
+            .cache_dir(None)
+    };
+    let endpoint = checker(GitTagsSource::endpoint(format!("{}/tags", forge.url())));
+    assert_eq!(endpoint.check().await.unwrap().unwrap().latest, "1.2.0");
+
+    if std::process::Command::new("git")
+        .arg("--version")
+        .output()
+        .is_ok()
+    {
+        let remote = checker(GitTagsSource::remote(format!("{}/tool.git", forge.url())));
+        assert!(remote.dry_run().unwrap().is_empty());
+        assert_eq!(remote.check().await.unwrap().unwrap().latest, "1.3.0");
+    }
+}
+
+#[tokio::test]
+async fn async_gitlab_releases_are_an_alternative_source() {
+    let gitlab = MockRegistry::serve_with(|path| {
+        if path == "/api/v4/projects/7/releases?per_page=1&order_by=released_at" {
+            (200, br#"[{"tag_name":"2.1.0"}]"#.to_vec())
+        } else {
+            (404, br#"{"message":"404 Project Not Found"}"#.to_vec())
+        }
+    });
+    let checker = UpdateChecker::new("tool", "2.0.0")
+        .gitlab_releases(GitLabSource::new(gitlab.url(), "7").token("glpat-s3cret"))
+        .cache_dir(None);
+    let planned: Vec<_> = checker
+        .dry_run()
+        .unwrap()
+        .into_iter()
+        .map(|request| planned_head(request, gitlab.url()))
+        .collect();
+    assert_eq!(checker.check().await.unwrap().unwrap().latest, "2.1.0");
+    let sent: Vec<_> = gitlab
+        .requests()
+        .iter()
+        .map(|head| parse_head(head))
+        .collect();
+    assert_eq!(planned, sent);
+
+    let err = checker
+        .gitlab_releases(GitLabSource::new(gitlab.url(), "8"))
+        .check()
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+#[tokio::test]
+async fn async_exhausted_github_rate_limit_reports_reset_time() {
+    let github = MockRegistry::serve_with_headers(|_| {
+        let headers = vec![
+            ("x-ratelimit-remaining", "0".to_string()),
+            ("x-ratelimit-reset", "1760000000".to_string()),
+        ];
+        (429, headers, b"{}".to_vec())
+    });
+    let err = UpdateChecker::new("tool", "2.0.0")
+        .github_releases(
+            GitHubSource::new("octo", "tool")
+                .api_url(github.url())
+                .token("ghp_s3cret"),
+        )
+        .cache_dir(None)
+        .check()
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::RateLimited);
+    assert_eq!(
+        err.rate_limit_reset(),
+        Some(std::time::UNIX_EPOCH + Duration::from_secs(1_760_000_000))
+    );
+    let (_, _, headers) = parse_head(&github.requests()[0]);
+    assert!(headers.contains(&("authorization".to_string(), "Bearer ghp_s3cret".to_string())));
+}
+
+#[tokio::test]
+async fn async_plain_http_registry_requires_opt_in() {
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url("http://registry.lab")
+        .cache_dir(None);
+    assert_eq!(checker.check().await.unwrap_err().kind(), ErrorKind::Config);
+
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .message_url("http://messages.lab/notice.txt")
+        .cache_dir(None);
+    assert_eq!(checker.check().await.unwrap_err().kind(), ErrorKind::Config);
+    assert!(registry.requests().is_empty());
+}
+
+#[tokio::test]
+async fn async_cache_key_names_the_cache_file() {
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()))
+        .cache_key("my-app.test");
+    assert!(checker.check().await.unwrap().is_some());
+    assert!(dir.path().join("my-app.test").exists());
+
+    let checker = checker.cache_key("my/app");
+    assert_eq!(checker.check().await.unwrap_err().kind(), ErrorKind::Config);
+}
+
+#[tokio::test]
+async fn async_parse_failures_capture_the_response() {
+    let registry = MockRegistry::serve("not json");
+    let dir = tempfile::tempdir().unwrap();
+    let err = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()))
+        .capture_failed_bodies(true)
+        .check()
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Parse);
+    let capture = std::fs::read_dir(dir.path())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    assert!(
+        err.to_string().contains(&*capture.to_string_lossy()),
+        "{err}"
+    );
+    assert_eq!(std::fs::read(&capture).unwrap(), b"not json");
+}
+
+#[tokio::test]
+async fn async_seeded_cache_answers_first_check() {
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()));
+
+    checker
+        .seed_cache("1.2.0", std::time::SystemTime::now())
+        .unwrap();
+    assert_eq!(checker.check().await.unwrap().unwrap().latest, "1.2.0");
+    assert!(registry.requests().is_empty());
+}
+
+#[tokio::test]
+async fn async_cleared_caches_are_refetched() {
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()));
+
+    checker.check().await.unwrap();
+    checker.clear_cache().unwrap();
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    checker.check().await.unwrap();
+    assert_eq!(registry.requests().len(), 2);
+
+    let err = UpdateChecker::new("../test", "1.0.0").clear_cache();
+    assert_eq!(err.unwrap_err().kind(), ErrorKind::InvalidCrateName);
+}
+
+#[tokio::test]
+async fn async_cache_file_paths_match_sync_ones() {
+    let dir = tempfile::tempdir().unwrap();
+    let sync = tiny_update_check::UpdateChecker::new("test", "1.0.0")
+        .cache_dir(Some(dir.path().to_path_buf()))
+        .cache_key("my-app.test");
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .cache_dir(Some(dir.path().to_path_buf()))
+        .cache_key("my-app.test");
+
+    assert_eq!(checker.cache_file_path(), sync.cache_file_path());
+    assert_eq!(
+        checker.cache_file_path(),
+        Some(dir.path().join("my-app.test"))
+    );
+    assert_eq!(checker.cache_dir(None).cache_file_path(), None);
+}
+
+struct PanickingRng;
+
+impl tiny_update_check::Rng for PanickingRng {
+    fn next_f64(&mut self) -> f64 {
+        panic!("rng exploded")
+    }
+}
+
+#[tokio::test]
+async fn async_panic_becomes_internal_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .cache_dir(Some(dir.path().to_path_buf()))
+        .cache_jitter(Duration::from_secs(60))
+        .rng(PanickingRng);
+
+    match checker.check_detailed().await {
+        Err(Error::Internal(message)) => assert_eq!(message, "rng exploded"),
+        other => panic!("expected an internal error, got {other:?}"),
+    }
+}
+
+/// A blocking checker against a mock registry, recording every event.
+fn blocking_checker(
+    registry: &MockRegistry,
+) -> (
+    tiny_update_check::UpdateChecker,
+    std::sync::Arc<std::sync::Mutex<Vec<CheckEvent>>>,
+) {
+    let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = std::sync::Arc::clone(&events);
+    let checker = tiny_update_check::UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None)
+        .on_event(move |event| sink.lock().unwrap().push(event.clone()));
+    (checker, events)
+}
+
+#[tokio::test]
+async fn blocking_check_in_runtime_warns_by_default() {
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let (checker, events) = blocking_checker(&registry);
+
+    let update = checker.check().unwrap().unwrap();
+    assert_eq!(update.latest, "2.0.0");
+    assert!(
+        events
+            .lock()
+            .unwrap()
+            .contains(&CheckEvent::BlockingInAsyncContext)
+    );
+}
+
+#[tokio::test]
+async fn blocking_check_in_runtime_fails_in_strict_mode() {
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let (checker, _) = blocking_checker(&registry);
+    let checker = checker.async_context(AsyncContextPolicy::Error);
+
+    assert_eq!(
+        checker.check().unwrap_err().kind(),
+        ErrorKind::BlockingInAsyncContext
+    );
+    assert!(registry.requests().is_empty());
+}
+
+#[tokio::test]
+async fn blocking_check_in_runtime_can_be_allowed() {
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let (checker, events) = blocking_checker(&registry);
+    let checker = checker.async_context(AsyncContextPolicy::Allow);
+
+    assert!(checker.check().unwrap().is_some());
+    assert!(
+        !events
+            .lock()
+            .unwrap()
+            .contains(&CheckEvent::BlockingInAsyncContext)
+    );
+}
+
+#[tokio::test]
+async fn check_spawn_blocking_does_not_block_the_runtime() {
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let (checker, events) = blocking_checker(&registry);
+    let checker = checker.async_context(AsyncContextPolicy::Error);
+
+    let update = checker.check_spawn_blocking().await.unwrap().unwrap();
+    assert_eq!(update.latest, "2.0.0");
+    assert!(
+        !events
+            .lock()
+            .unwrap()
+            .contains(&CheckEvent::BlockingInAsyncContext)
+    );
+}
+
+#[tokio::test]
+async fn async_http_failures_report_specific_kinds() {
+    for (status, kind) in [
+        (401, ErrorKind::Unauthorized),
+        (403, ErrorKind::Unauthorized),
+        (404, ErrorKind::NotFound),
+        (429, ErrorKind::RateLimited),
+        (500, ErrorKind::ServerError),
+        (503, ErrorKind::ServerError),
+        (418, ErrorKind::Http),
+    ] {
+        let registry = MockRegistry::serve_status(status, "");
+        let checker = UpdateChecker::new("test", "1.0.0")
+            .registry_url(registry.url())
+            .cache_dir(None);
+        let err = checker.check().await.unwrap_err();
+        assert_eq!(err.kind(), kind, "status {status}");
+        assert_eq!(err.status(), Some(status));
+    }
+}
+
+#[tokio::test]
+async fn async_oversized_response_reports_too_large() {
+    let registry = MockRegistry::serve(vec![b' '; 64 * 1024]);
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .max_memory(16 * 1024)
+        .cache_dir(None);
+    assert_eq!(
+        checker.check().await.unwrap_err().kind(),
+        ErrorKind::TooLarge
+    );
+}
+
+#[tokio::test]
+async fn async_ephemeral_checks_leave_no_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().join("cache")))
+        .state_dir(Some(dir.path().join("state")))
+        .daily_request_budget(1)
+        .ephemeral(true);
+
+    assert!(checker.check().await.unwrap().is_some());
+    assert!(checker.check().await.unwrap().is_some());
+    assert_eq!(registry.requests().len(), 1);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn async_check_outcome_maps_to_exit_codes() {
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None);
+    assert_eq!(checker.check_outcome().await.exit_code(), 10);
+
+    let checker = UpdateChecker::new("missing", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None);
+    assert!(matches!(
+        checker.check_outcome().await,
+        CheckOutcome::Failed(ref e) if e.kind() == ErrorKind::NotFound
+    ));
+    assert_eq!(
+        checker
+            .mode(CheckMode::Disabled)
+            .check_outcome()
+            .await
+            .exit_code(),
+        20
+    );
+}
+
+#[tokio::test]
+async fn async_app_user_agent() {
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let checker = |contact| {
+        UpdateChecker::new("tool", "1.0.0")
+            .registry_url(registry.url())
+            .app_user_agent("mytool", "2.1.0", contact)
+            .cache_dir(None)
+    };
+
+    checker(Some("me@example.com")).check().await.unwrap();
+    let (_, _, headers) = parse_head(&registry.requests()[0]);
+    assert!(
+        headers.contains(&(
+            "user-agent".to_string(),
+            concat!(
+                "mytool/2.1.0 (me@example.com) tiny-update-check/",
+                env!("CARGO_PKG_VERSION")
+            )
+            .to_string()
+        ))
+    );
+
+    let err = checker(Some("me\n@example.com")).check().await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Config);
+    assert_eq!(registry.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn async_custom_headers() {
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let checker = || {
+        UpdateChecker::new("tool", "1.0.0")
+            .registry_url(registry.url())
+            .header("X-Org-Team", "platform")
+            .header("X-Audit-Id", "42")
+            .cache_dir(None)
+    };
+    assert!(checker().check().await.unwrap().is_some());
+    let (_, _, headers) = parse_head(&registry.requests()[0]);
+    for header in [("x-audit-id", "42"), ("x-org-team", "platform")] {
+        assert!(
+            headers.contains(&(header.0.to_string(), header.1.to_string())),
+            "{headers:?}"
+        );
+    }
+
+    let err = checker()
+        .header("User-Agent", "bot/1.0")
+        .check()
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Config);
+    checker()
+        .header("User-Agent", "bot/1.0")
+        .allow_header_overrides(true)
+        .check()
+        .await
+        .unwrap();
+    let (_, _, headers) = parse_head(&registry.requests()[1]);
+    assert!(headers.contains(&("user-agent".to_string(), "bot/1.0".to_string())));
+}
+
+#[tokio::test]
+async fn async_rate_limits_are_waited_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let registry =
+        MockRegistry::serve_with_headers(|_| (429, vec![("Retry-After", "60".into())], Vec::new()));
+    let checker = || {
+        UpdateChecker::new("tool", "1.0.0")
+            .registry_url(registry.url())
+            .cache_dir(Some(dir.path().to_path_buf()))
+    };
+
+    let err = checker().check().await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::RateLimited);
+    assert!(checker().check().await.unwrap().is_none());
+    assert_eq!(registry.requests().len(), 1);
+    assert!(dir.path().join("tool-update-check.retry-after").exists());
+}
+
+#[cfg(feature = "gzip")]
+#[tokio::test]
+async fn async_gzip_responses_are_decoded() {
+    let serve = |body: &'static [u8]| {
+        MockRegistry::serve_with_headers(move |_| {
+            (
+                200,
+                vec![("Content-Encoding", "gzip".to_string())],
+                body.to_vec(),
+            )
+        })
+    };
+    let checker = |registry: &MockRegistry| {
+        UpdateChecker::new("serde", "1.0.0")
+            .registry_url(registry.url())
+            .cache_dir(None)
+    };
+
+    let registry = serve(include_bytes!("fixtures/serde_response.json.gz"));
+    let update = checker(&registry).check().await.unwrap().unwrap();
+    assert_eq!(update.latest, "1.0.228");
+    let (_, _, headers) = parse_head(&registry.requests()[0]);
+    assert!(headers.contains(&("accept-encoding".to_string(), "gzip".to_string())));
+
+    let liar = serve(br#"{"crate":{"newest_version":"2.0.0"}}"#);
+    let err = checker(&liar).check().await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Http);
+    assert!(err.to_string().contains("couldn't be decoded"), "{err}");
+}
+
+#[tokio::test]
+async fn async_server_errors_fall_back_to_a_stale_cache() {
+    let registry = MockRegistry::serve_status(500, "");
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()));
+    let week_ago = std::time::SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60);
+    checker.seed_cache("1.2.0", week_ago).unwrap();
+
+    let update = checker.check_detailed().await.unwrap().unwrap();
+    assert_eq!(update.latest, "1.2.0");
+    assert!(update.stale);
+
+    let err = checker
+        .stale_on_server_error(false)
+        .check()
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ServerError);
+    assert_eq!(registry.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn async_endless_responses_are_abandoned_at_the_limit() {
+    let (url, _) = common::serve_endless(b"<html>");
+    let err = UpdateChecker::new("test", "1.0.0")
+        .registry_url(&url)
+        .max_memory(16 * 1024 * 1024)
+        .cache_dir(None)
+        .check()
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TooLarge);
+
+    let (message_url, _) = common::serve_endless(b"Please upgrade.");
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let update = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .message_url(message_url)
+        .cache_dir(None)
+        .check_detailed()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(update.message.as_deref(), Some("Please upgrade."));
+}
+
+#[tokio::test]
+async fn async_checks_use_the_given_client() {
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert("x-app", reqwest::header::HeaderValue::from_static("mine"));
+    let client = reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None)
+        .with_client(client);
+
+    for _ in 0..2 {
+        assert_eq!(checker.check().await.unwrap().unwrap().latest, "2.0.0");
+    }
+    let requests = registry.requests();
+    assert_eq!(requests.len(), 2);
+    for head in requests {
+        let (_, _, headers) = parse_head(&head);
+        assert!(headers.contains(&("x-app".to_string(), "mine".to_string())));
+    }
+
+    // The checker's timeout still applies to a client without one.
+    let slow = MockRegistry::serve_with(|_| {
+        std::thread::sleep(Duration::from_secs(3));
+        (200, Vec::new())
+    });
+    let err = UpdateChecker::new("test", "1.0.0")
+        .registry_url(slow.url())
+        .timeout(Duration::from_millis(500))
+        .cache_dir(None)
+        .with_client(reqwest::Client::new())
+        .check()
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Timeout);
+}
+
+#[tokio::test]
+async fn a_shorter_server_max_age_expires_the_cache_early() {
+    let registry = MockRegistry::serve_with_headers(|_| {
+        (
+            200,
+            vec![("Cache-Control", "max-age=0".to_string())],
+            br#"{"crate":{"newest_version":"2.0.0"}}"#.to_vec(),
+        )
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()));
+
+    for _ in 0..2 {
+        assert_eq!(checker.check().await.unwrap().unwrap().latest, "2.0.0");
+    }
+    assert_eq!(registry.requests().len(), 1);
+
+    let honoring = checker.honor_cache_control(true);
+    for _ in 0..2 {
+        honoring.check().await.unwrap();
+    }
+    assert_eq!(registry.requests().len(), 3);
 }
 
 #[tokio::test]
-async fn async_convenience_function() {
-    let result = tiny_update_check::r#async::check("serde", "0.0.1").await;
-    assert!(result.is_ok());
+async fn responses_for_another_crate_are_refused() {
+    let registry = MockRegistry::serve(include_bytes!("fixtures/other_crate.json").as_slice());
+    let err = UpdateChecker::new("serde", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None)
+        .check()
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Parse);
+    assert!(
+        err.to_string().contains("'serde_json', not 'serde'"),
+        "{err}"
+    );
 }
@@ -0,0 +1,158 @@
+//! Checks against a mock registry named by `TINY_UPDATE_CHECK_BASE_URL`,
+//! including through the convenience functions, which take no registry.
+//!
+//! Kept in its own test binary because it sets that variable; checkers
+//! read it when they are created.
+
+mod common;
+
+use common::{MockRegistry, parse_head};
+use std::time::Duration;
+use tiny_update_check::UpdateChecker;
+
+const BASE_URL_VAR: &str = "TINY_UPDATE_CHECK_BASE_URL";
+
+/// A registry with `serde` at 1.0.219.
+fn registry() -> MockRegistry {
+    MockRegistry::crates(&[("serde", "1.0.219")])
+}
+
+/// Run `f` with the base URL set to `registry`'s, the user directories in a
+/// fresh temporary directory, so nothing cached earlier answers, and
+/// `DO_NOT_TRACK` unset.
+fn with_base_url<R>(registry: &MockRegistry, f: impl FnOnce() -> R) -> R {
+    let dir = tempfile::tempdir().unwrap();
+    let home = dir.path().to_str();
+    temp_env::with_vars(
+        [
+            (BASE_URL_VAR, Some(registry.url())),
+            ("HOME", home),
+            ("XDG_CACHE_HOME", home),
+            ("XDG_STATE_HOME", home),
+            ("LOCALAPPDATA", home),
+            ("DO_NOT_TRACK", None),
+        ],
+        f,
+    )
+}
+
+#[test]
+fn checkers_query_the_base_url() {
+    let registry = registry();
+    let checker = temp_env::with_var(BASE_URL_VAR, Some(registry.url()), || {
+        UpdateChecker::new("serde", "1.0.0")
+            .cache_duration(Duration::from_secs(3600))
+            .timeout(Duration::from_secs(10))
+            .cache_dir(None)
+    });
+
+    let update = checker.check().unwrap().unwrap();
+    assert_eq!(update.latest, "1.0.219");
+    let detailed = checker.check_detailed().unwrap().unwrap();
+    assert_eq!(detailed.latest, "1.0.219");
+    let (_, path, _) = parse_head(&registry.requests()[0]);
+    assert_eq!(path, "/api/v1/crates/serde");
+}
+
+#[test]
+fn the_convenience_function_queries_the_base_url() {
+    let registry = registry();
+    with_base_url(&registry, || {
+        let update = tiny_update_check::check("serde", "1.0.0").unwrap().unwrap();
+        assert_eq!(update.latest, "1.0.219");
+    });
+    assert_eq!(registry.requests().len(), 1);
+}
+
+#[test]
+fn an_explicit_registry_takes_precedence() {
+    let registry = registry();
+    let other = MockRegistry::crates(&[("serde", "2.0.0")]);
+    let checker = temp_env::with_var(BASE_URL_VAR, Some(registry.url()), || {
+        UpdateChecker::new("serde", "1.0.0")
+            .registry_url(other.url())
+            .cache_dir(None)
+    });
+
+    assert_eq!(checker.check().unwrap().unwrap().latest, "2.0.0");
+    assert!(registry.requests().is_empty());
+}
+
+#[test]
+fn an_empty_base_url_is_ignored() {
+    let checker = temp_env::with_var(BASE_URL_VAR, Some(""), || {
+        UpdateChecker::new("serde", "1.0.0").cache_dir(None)
+    });
+
+    let planned = checker.dry_run().unwrap();
+    assert_eq!(planned[0].url, "https://crates.io/api/v1/crates/serde");
+}
+
+#[cfg(feature = "async")]
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_checkers_query_the_base_url() {
+    use tiny_update_check::CheckEvent;
+    use tiny_update_check::r#async::UpdateChecker;
+
+    let registry = registry();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let timeout = Duration::from_secs(5);
+    let checker = temp_env::with_var(BASE_URL_VAR, Some(registry.url()), || {
+        UpdateChecker::new("serde", "0.0.1")
+            .cache_dir(None)
+            .timeout(timeout)
+            .on_event_async(move |event| {
+                let tx = tx.clone();
+                Box::pin(async move {
+                    let _ = tx.send(event);
+                })
+            })
+    });
+
+    block_on(async {
+        let before = std::time::Instant::now();
+        let update = checker.check().await.unwrap().unwrap();
+        assert_eq!(update.current, "0.0.1");
+        assert_eq!(update.latest, "1.0.219");
+
+        let Some(CheckEvent::FetchStarted { url }) = rx.recv().await else {
+            panic!("expected FetchStarted first");
+        };
+        assert_eq!(url, format!("{}/api/v1/crates/serde", registry.url()));
+        let Some(CheckEvent::FetchCompleted {
+            url: completed_url,
+            started,
+            elapsed,
+            success,
+        }) = rx.recv().await
+        else {
+            panic!("expected FetchCompleted second");
+        };
+        assert_eq!(completed_url, url);
+        assert!(started >= before);
+        assert!(elapsed <= before.elapsed());
+        assert!(elapsed < timeout + Duration::from_secs(1));
+        assert!(success);
+    });
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn the_async_convenience_function_queries_the_base_url() {
+    let registry = registry();
+    with_base_url(&registry, || {
+        let update = block_on(tiny_update_check::r#async::check("serde", "0.0.1"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(update.latest, "1.0.219");
+    });
+}
@@ -0,0 +1,166 @@
+//! Following Cargo's source replacement of crates.io.
+//!
+//! Kept in its own test binary because it sets `CARGO_HOME`, which would
+//! otherwise leak into concurrently running tests.
+
+mod common;
+
+use common::{MockRegistry, parse_head};
+use std::fs;
+use std::path::Path;
+use tiny_update_check::UpdateChecker;
+
+/// Serve the sparse index fixtures.
+fn fixture_index() -> MockRegistry {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/index");
+    MockRegistry::serve_with(move |path| {
+        fs::read(root.join(path.trim_start_matches('/')))
+            .map_or_else(|_| (404, Vec::new()), |body| (200, body))
+    })
+}
+
+/// Write a `config.toml` in `dir` replacing crates.io with `index`.
+fn replace_crates_io(dir: &Path, index: &str) {
+    fs::create_dir_all(dir).unwrap();
+    fs::write(
+        dir.join("config.toml"),
+        format!(
+            "[source.crates-io]\nreplace-with = \"mirror\"\n\n\
+             [registries.mirror]\nindex = \"sparse+{index}/\"\n"
+        ),
+    )
+    .unwrap();
+}
+
+fn checker(dir: &Path) -> UpdateChecker {
+    UpdateChecker::new("serde", "1.0.0")
+        .cache_dir(None)
+        .respect_cargo_config(true)
+        .project_dir(dir)
+}
+
+#[test]
+fn source_replacement_redirects_crates_io() {
+    let home_index = fixture_index();
+    let project_index = fixture_index();
+    let home = tempfile::tempdir().unwrap();
+    let root = tempfile::tempdir().unwrap();
+    let nested = root.path().join("crates/app");
+    fs::create_dir_all(&nested).unwrap();
+
+    temp_env::with_var("CARGO_HOME", Some(home.path()), || {
+        // No replacement anywhere: crates.io is queried.
+        let planned = checker(&nested).dry_run().unwrap();
+        assert_eq!(planned[0].url, "https://crates.io/api/v1/crates/serde");
+
+        replace_crates_io(home.path(), home_index.url());
+        let update = checker(&nested).check().unwrap().unwrap();
+        assert_eq!(update.latest, "1.0.218");
+        let (_, path, _) = parse_head(&home_index.requests()[0]);
+        assert_eq!(path, "/se/rd/serde");
+
+        // A project's config overrides the one in CARGO_HOME.
+        replace_crates_io(&root.path().join(".cargo"), project_index.url());
+        assert!(checker(&nested).check().unwrap().is_some());
+        assert_eq!(home_index.requests().len(), 1);
+        assert_eq!(project_index.requests().len(), 1);
+
+        // Off by default, and a custom registry isn't crates.io.
+        let planned = UpdateChecker::new("serde", "1.0.0")
+            .project_dir(&nested)
+            .dry_run()
+            .unwrap();
+        assert_eq!(planned[0].url, "https://crates.io/api/v1/crates/serde");
+        let planned = checker(&nested)
+            .registry_url("https://registry.example.com")
+            .dry_run()
+            .unwrap();
+        assert_eq!(
+            planned[0].url,
+            "https://registry.example.com/api/v1/crates/serde"
+        );
+    });
+}
+
+#[test]
+fn unusable_replacements_fall_back_to_crates_io() {
+    let home = tempfile::tempdir().unwrap();
+    let root = tempfile::tempdir().unwrap();
+    fs::create_dir_all(root.path().join(".cargo")).unwrap();
+    fs::write(
+        root.path().join(".cargo/config"),
+        "[source.crates-io]\nreplace-with = \"vendored\"\n\
+         [source.vendored]\ndirectory = \"vendor\"\n",
+    )
+    .unwrap();
+
+    temp_env::with_var("CARGO_HOME", Some(home.path()), || {
+        let planned = checker(root.path()).dry_run().unwrap();
+        assert_eq!(planned[0].url, "https://crates.io/api/v1/crates/serde");
+    });
+}
+
+#[test]
+fn cargo_credentials_authenticate_a_named_registry() {
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let home = tempfile::tempdir().unwrap();
+    let checker = |name: &str| {
+        UpdateChecker::new("tool", "1.0.0")
+            .cache_dir(None)
+            .registry_url(registry.url())
+            .registry_name(name)
+            .use_cargo_credentials(true)
+    };
+    let sent_token = || {
+        let (_, _, headers) = parse_head(registry.requests().last().unwrap());
+        headers
+            .into_iter()
+            .find(|(name, _)| name == "authorization")
+            .map(|(_, value)| value)
+    };
+
+    temp_env::with_var("CARGO_HOME", Some(home.path()), || {
+        // No credentials file: the check runs without a token.
+        assert!(checker("kellnr").check().unwrap().is_some());
+        assert_eq!(sent_token(), None);
+
+        fs::write(
+            home.path().join("credentials.toml"),
+            "[registries.kellnr]\ntoken = \"from-toml\"\n",
+        )
+        .unwrap();
+        checker("kellnr").check().unwrap();
+        assert_eq!(sent_token().as_deref(), Some("from-toml"));
+        checker("other").check().unwrap();
+        assert_eq!(sent_token(), None);
+        checker("kellnr")
+            .use_cargo_credentials(false)
+            .check()
+            .unwrap();
+        assert_eq!(sent_token(), None);
+
+        // The legacy file wins when both exist, as it does for Cargo.
+        fs::write(
+            home.path().join("credentials"),
+            "[registries]\nkellnr = { token = \"from-legacy\" }\n",
+        )
+        .unwrap();
+        checker("kellnr").check().unwrap();
+        assert_eq!(sent_token().as_deref(), Some("from-legacy"));
+        checker("kellnr").auth_token("explicit").check().unwrap();
+        assert_eq!(sent_token().as_deref(), Some("explicit"));
+
+        // Nothing is sent to crates.io.
+        let planned = UpdateChecker::new("tool", "1.0.0")
+            .registry_name("kellnr")
+            .use_cargo_credentials(true)
+            .dry_run()
+            .unwrap();
+        assert!(
+            planned[0]
+                .headers
+                .iter()
+                .all(|(name, _)| !name.eq_ignore_ascii_case("authorization"))
+        );
+    });
+}
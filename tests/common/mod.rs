@@ -0,0 +1,295 @@
+//! Shared helpers for integration tests.
+
+#![allow(dead_code)]
+
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tiny_update_check::PlannedRequest;
+
+/// A minimal HTTP server on a loopback port that answers every request with
+/// the same canned response, for exercising the real transports offline.
+pub struct MockRegistry {
+    url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockRegistry {
+    /// Serve `body` with a `200 OK` status.
+    pub fn serve(body: impl Into<Vec<u8>>) -> Self {
+        Self::serve_status(200, body)
+    }
+
+    /// Serve `body` with the given status code.
+    pub fn serve_status(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        let body = body.into();
+        Self::serve_with(move |_| (status, body.clone()))
+    }
+
+    /// Serve a crates.io API response for each `(crate name, newest version)`
+    /// pair, and `404 Not Found` for any other crate.
+    pub fn crates(crates: &[(&str, &str)]) -> Self {
+        let crates: Vec<(String, String)> = crates
+            .iter()
+            .map(|(name, version)| {
+                let body =
+                    format!(r#"{{"crate":{{"name":"{name}","newest_version":"{version}"}}}}"#);
+                (format!("/api/v1/crates/{name}"), body)
+            })
+            .collect();
+        Self::serve_with(move |path| {
+            crates.iter().find(|(p, _)| p == path).map_or_else(
+                || (404, br#"{"errors":[{"detail":"Not Found"}]}"#.to_vec()),
+                |(_, body)| (200, body.clone().into_bytes()),
+            )
+        })
+    }
+
+    /// Answer the first `failures` requests with `503 Service Unavailable`,
+    /// then serve `body`.
+    pub fn flaky(failures: usize, body: impl Into<Vec<u8>>) -> Self {
+        let body = body.into();
+        let served = AtomicUsize::new(0);
+        Self::serve_with(move |_| {
+            if served.fetch_add(1, Ordering::SeqCst) < failures {
+                (503, Vec::new())
+            } else {
+                (200, body.clone())
+            }
+        })
+    }
+
+    /// Answer each request with the status and body `respond` returns for its path.
+    pub fn serve_with(respond: impl Fn(&str) -> (u16, Vec<u8>) + Send + 'static) -> Self {
+        Self::serve_with_headers(move |path| {
+            let (status, body) = respond(path);
+            (status, Vec::new(), body)
+        })
+    }
+
+    /// Answer each request with the status, extra response headers and body
+    /// `respond` returns for its path.
+    pub fn serve_with_headers(
+        respond: impl Fn(&str) -> (u16, Vec<(&'static str, String)>, Vec<u8>) + Send + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let seen = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut reader = BufReader::new(&stream);
+                let mut head = String::new();
+                loop {
+                    let mut line = String::new();
+                    match reader.read_line(&mut line) {
+                        Ok(0) | Err(_) => break,
+                        Ok(_) if line == "\r\n" => break,
+                        Ok(_) => head.push_str(&line),
+                    }
+                }
+                let path = head.split(' ').nth(1).unwrap_or("/").to_string();
+                seen.lock().unwrap().push(head);
+
+                let (status, headers, body) = respond(&path);
+                let extra = headers
+                    .iter()
+                    .fold(String::new(), |mut extra, (name, value)| {
+                        let _ = write!(extra, "{name}: {value}\r\n");
+                        extra
+                    });
+                let header = format!(
+                    "HTTP/1.1 {status} Mock\r\nContent-Type: application/json\r\n\
+                     {extra}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = stream.write_all(header.as_bytes());
+                let _ = stream.write_all(&body);
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// The base URL to pass to `registry_url`.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The request line and headers of every request received so far.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// A loopback server that answers every request with `200 OK` and a body of
+/// `prefix` followed by spaces without end, for checking that oversized
+/// responses are abandoned. Returns its base URL and a count of the body
+/// bytes sent, which stops growing once the client hangs up.
+pub fn serve_endless(prefix: &'static [u8]) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let sent = Arc::new(AtomicUsize::new(0));
+
+    let counter = Arc::clone(&sent);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(&stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|n| n > 0) && line != "\r\n" {
+                line.clear();
+            }
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n");
+            let _ = stream.write_all(prefix);
+            let chunk = [b' '; 8192];
+            while stream.write_all(&chunk).is_ok() {
+                counter.fetch_add(chunk.len(), Ordering::SeqCst);
+            }
+        }
+    });
+
+    (url, sent)
+}
+
+/// A loopback HTTPS server with a certificate for `localhost` signed by the
+/// test CA in `tests/fixtures/tls`, answering every request with `200 OK`
+/// and `body`. Returns its base URL.
+pub fn serve_tls(body: impl Into<Vec<u8>>) -> String {
+    serve_tls_versions(body, rustls::DEFAULT_VERSIONS)
+}
+
+/// Like [`serve_tls`], negotiating only `versions` of TLS.
+pub fn serve_tls_versions(
+    body: impl Into<Vec<u8>>,
+    versions: &[&'static rustls::SupportedProtocolVersion],
+) -> String {
+    serve_tls_as(
+        body,
+        versions,
+        include_bytes!("../fixtures/tls/server.pem"),
+        include_bytes!("../fixtures/tls/server.key"),
+    )
+}
+
+/// Like [`serve_tls`], with a self-signed certificate for `localhost`.
+pub fn serve_self_signed(body: impl Into<Vec<u8>>) -> String {
+    serve_tls_as(
+        body,
+        rustls::DEFAULT_VERSIONS,
+        include_bytes!("../fixtures/tls/self-signed.pem"),
+        include_bytes!("../fixtures/tls/self-signed.key"),
+    )
+}
+
+/// Like [`serve_tls`], with a certificate for `crates.io` and
+/// `index.crates.io` signed by the CA in `fixtures/tls/hosts-ca.pem`.
+/// Returns its address, to resolve those hosts to.
+pub fn serve_crates_io(body: impl Into<Vec<u8>>) -> SocketAddr {
+    let url = serve_tls_as(
+        body,
+        rustls::DEFAULT_VERSIONS,
+        include_bytes!("../fixtures/tls/crates-io.pem"),
+        include_bytes!("../fixtures/tls/crates-io.key"),
+    );
+    let port = url.rsplit(':').next().unwrap().parse().unwrap();
+    SocketAddr::from(([127, 0, 0, 1], port))
+}
+
+/// A loopback HTTPS server negotiating `versions` of TLS with the PEM
+/// certificate `cert` and key `key`, answering every request with `200 OK`
+/// and `body`. Returns its base URL.
+fn serve_tls_as(
+    body: impl Into<Vec<u8>>,
+    versions: &[&'static rustls::SupportedProtocolVersion],
+    cert: &[u8],
+    key: &[u8],
+) -> String {
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+    let certs = CertificateDer::pem_slice_iter(cert)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let key = PrivateKeyDer::from_pem_slice(key).unwrap();
+    let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_protocol_versions(versions)
+    .unwrap()
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .unwrap();
+    let config = Arc::new(config);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "https://localhost:{}",
+        listener.local_addr().unwrap().port()
+    );
+    let body = body.into();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let Ok(connection) = rustls::ServerConnection::new(Arc::clone(&config)) else {
+                continue;
+            };
+            let mut stream = rustls::StreamOwned::new(connection, stream);
+            let mut reader = BufReader::new(&mut stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|n| n > 0) && line != "\r\n" {
+                line.clear();
+            }
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(header.as_bytes());
+            let _ = stream.write_all(&body);
+            stream.conn.send_close_notify();
+            let _ = stream.flush();
+        }
+    });
+
+    url
+}
+
+/// The method, path and headers of a request head from
+/// [`MockRegistry::requests`], with header names lowercased and sorted so
+/// heads from different transports compare equal.
+pub fn parse_head(head: &str) -> (String, String, Vec<(String, String)>) {
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap().split(' ');
+    let method = request_line.next().unwrap().to_string();
+    let path = request_line.next().unwrap().to_string();
+    let mut headers: Vec<(String, String)> = lines
+        .map(|line| {
+            let (name, value) = line.split_once(':').unwrap();
+            (name.to_ascii_lowercase(), value.trim().to_string())
+        })
+        .collect();
+    headers.sort();
+    (method, path, headers)
+}
+
+/// A planned request in the form [`parse_head`] returns, for a registry at
+/// `base`.
+pub fn planned_head(
+    request: PlannedRequest,
+    base: &str,
+) -> (String, String, Vec<(String, String)>) {
+    let path = request.url.strip_prefix(base).unwrap().to_string();
+    let mut headers: Vec<(String, String)> = request
+        .headers
+        .into_iter()
+        .map(|(name, value)| (name.to_ascii_lowercase(), value))
+        .collect();
+    headers.sort();
+    (request.method.to_string(), path, headers)
+}
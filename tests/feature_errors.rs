@@ -0,0 +1,95 @@
+//! The errors a downstream build reports for unusable feature combinations.
+//!
+//! Each case checks the crate with `cargo check` in a separate target
+//! directory, so a misconfiguration must fail with the crate's own message
+//! rather than errors from deep inside a transport. Building the
+//! dependencies for each combination takes a few minutes, so these run in CI
+//! with:
+//!
+//! ```sh
+//! cargo test --test feature_errors -- --ignored
+//! ```
+
+use std::process::{Command, Output};
+
+const NO_BACKEND: &str = "tiny-update-check needs an HTTP backend";
+
+fn cargo_check(features: &[&str]) -> Output {
+    let mut command = Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()));
+    command
+        .args(["check", "--lib", "--quiet", "--no-default-features"])
+        .arg("--manifest-path")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+        .env(
+            "CARGO_TARGET_DIR",
+            concat!(env!("CARGO_TARGET_TMPDIR"), "/feature-errors"),
+        )
+        .env_remove("RUSTFLAGS");
+    if !features.is_empty() {
+        command.arg("--features").arg(features.join(","));
+    }
+    command.output().unwrap()
+}
+
+/// Assert that a build with `features` fails with only the backend message.
+fn assert_no_backend(features: &[&str]) {
+    let output = cargo_check(features);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "{features:?} should not build");
+    let errors: Vec<&str> = stderr
+        .lines()
+        .filter(|line| line.starts_with("error"))
+        .collect();
+    assert!(
+        errors.first().is_some_and(|e| e.contains(NO_BACKEND)),
+        "{features:?}:\n{stderr}"
+    );
+    // The only other error is cargo's summary of the failed build.
+    assert_eq!(errors.len(), 2, "{features:?}:\n{stderr}");
+    assert!(!stderr.contains("warning"), "{features:?}:\n{stderr}");
+}
+
+#[test]
+#[ignore = "builds the crate with other features; run with --ignored"]
+fn misconfigured_features_report_one_clear_error() {
+    assert_no_backend(&[]);
+    assert_no_backend(&["do-not-track", "response-body"]);
+    assert_no_backend(&["async"]);
+}
+
+/// Run the tests in `tests/tls.rs` matching `filter` with `features`.
+fn cargo_test(features: &[&str], filter: &str) -> Output {
+    Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()))
+        .args(["test", "--quiet", "--no-default-features", "--test", "tls"])
+        .arg("--manifest-path")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+        .arg("--features")
+        .arg(features.join(","))
+        .arg(filter)
+        .env(
+            "CARGO_TARGET_DIR",
+            concat!(env!("CARGO_TARGET_TMPDIR"), "/feature-errors"),
+        )
+        .env_remove("RUSTFLAGS")
+        .output()
+        .unwrap()
+}
+
+#[test]
+#[ignore = "builds the crate with other features; run with --ignored"]
+fn each_transport_combination_builds_and_picks_its_provider() {
+    for features in [
+        &["native-tls"][..],
+        &["rustls"],
+        &["native-tls", "rustls"],
+        &["native-tls", "async"],
+        &["rustls", "async"],
+    ] {
+        let output = cargo_test(features, "the_provider");
+        assert!(
+            output.status.success(),
+            "{features:?}:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
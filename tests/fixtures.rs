@@ -0,0 +1,180 @@
+//! Tooling for the registry responses captured under `tests/fixtures/`.
+//!
+//! Captured fixtures are refreshed from the live API by an ignored test:
+//!
+//! ```sh
+//! TINY_UPDATE_CHECK_REFRESH_FIXTURES=1 cargo test --features response-body \
+//!     --test fixtures -- --ignored
+//! ```
+//!
+//! Responses are committed as the refresh writes them: scrubbed of fields that
+//! change without a release (download counts and timestamps) and serialized
+//! compactly with sorted keys, so a refresh only shows real changes. The
+//! small hand-written fixtures cover response shapes the live API rarely
+//! serves.
+
+mod common;
+
+use common::MockRegistry;
+use serde_json::Value;
+use tiny_update_check::{SuggestStrategy, UpdateChecker, VersionPolicy};
+
+/// Fixtures captured from the live API, as `(crate name, file name)`.
+const CAPTURED: &[(&str, &str)] = &[("serde", "serde_response.json")];
+
+/// Fields replaced with zero.
+const COUNTERS: &[&str] = &["downloads", "recent_downloads"];
+
+/// Fields replaced with the Unix epoch.
+const TIMESTAMPS: &[&str] = &["created_at", "updated_at", "time"];
+
+/// Replace volatile fields and serialize `body` compactly with sorted keys.
+fn scrub(body: &str) -> String {
+    let mut json: Value = serde_json::from_str(body).expect("fixture should be valid JSON");
+    scrub_value(&mut json);
+    serde_json::to_string(&json).expect("JSON should serialize")
+}
+
+fn scrub_value(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                if COUNTERS.contains(&key.as_str()) && field.is_number() {
+                    *field = Value::from(0);
+                } else if TIMESTAMPS.contains(&key.as_str()) && field.is_string() {
+                    *field = Value::from("1970-01-01T00:00:00Z");
+                } else {
+                    scrub_value(field);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(scrub_value),
+        _ => {}
+    }
+}
+
+fn fixture_path(file: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(file)
+}
+
+#[cfg(feature = "response-body")]
+#[test]
+#[ignore = "fetches live responses; set TINY_UPDATE_CHECK_REFRESH_FIXTURES=1 to run"]
+fn refresh_fixtures() {
+    use tiny_update_check::CheckMode;
+
+    if std::env::var_os("TINY_UPDATE_CHECK_REFRESH_FIXTURES").is_none() {
+        eprintln!("TINY_UPDATE_CHECK_REFRESH_FIXTURES is not set; leaving fixtures alone");
+        return;
+    }
+    for &(name, file) in CAPTURED {
+        let body = UpdateChecker::new(name, "0.0.0")
+            .cache_dir(None)
+            .mode(CheckMode::Forced)
+            .check_detailed()
+            .unwrap_or_else(|e| panic!("fetching {name} failed: {e}"))
+            .and_then(|update| update.response_body)
+            .unwrap_or_else(|| panic!("no response body for {name}"));
+        std::fs::write(fixture_path(file), scrub(&body)).unwrap();
+    }
+}
+
+/// Check `name` at `current` against a fixture `body`, returning the
+/// suggested and safest versions.
+fn check_fixture(
+    name: &str,
+    current: &str,
+    body: Vec<u8>,
+    policy: VersionPolicy,
+) -> Option<(String, Option<String>)> {
+    let registry = MockRegistry::serve(body);
+    UpdateChecker::new(name, current)
+        .registry_url(registry.url())
+        .cache_dir(None)
+        .version_policy(policy)
+        .suggest(SuggestStrategy::SafestFirst)
+        .check_detailed()
+        .unwrap()
+        .map(|update| (update.latest, update.safe_latest))
+}
+
+#[test]
+fn captured_fixtures_parse() {
+    for &(name, file) in CAPTURED {
+        let body = std::fs::read(fixture_path(file)).unwrap();
+        let (latest, _) = check_fixture(name, "0.0.1", body, VersionPolicy::Newest).unwrap();
+        assert_eq!(latest, "1.0.228", "{file}");
+    }
+}
+
+#[test]
+fn captured_fixtures_are_scrubbed() {
+    for &(_, file) in CAPTURED {
+        let body = std::fs::read_to_string(fixture_path(file)).unwrap();
+        assert!(body == scrub(&body), "{file} needs scrubbing");
+    }
+}
+
+#[test]
+fn edge_case_fixtures_parse() {
+    let cases = [
+        // Every release yanked: `newest_version` still names one, and there
+        // is no unyanked release to suggest as the safest.
+        ("yanked_only.json", VersionPolicy::Newest, Some("1.2.0")),
+        ("yanked_only.json", VersionPolicy::MaxStable, Some("1.2.0")),
+        ("yanked_only.json", VersionPolicy::Max, None),
+        // Only prereleases, which a stable current version never moves to.
+        ("prerelease_only.json", VersionPolicy::Newest, None),
+        ("prerelease_only.json", VersionPolicy::MaxStable, None),
+        ("prerelease_only.json", VersionPolicy::Max, None),
+        // No `max_version`, `max_stable_version` or `yanked`: every policy
+        // falls back to `newest_version`.
+        ("missing_fields.json", VersionPolicy::Newest, Some("1.4.0")),
+        (
+            "missing_fields.json",
+            VersionPolicy::MaxStable,
+            Some("1.4.0"),
+        ),
+        ("missing_fields.json", VersionPolicy::Max, Some("1.4.0")),
+    ];
+    for (file, policy, expected) in cases {
+        let body = std::fs::read(fixture_path(file)).unwrap();
+        let update = check_fixture("tool", "0.1.0", body, policy);
+        assert_eq!(
+            update,
+            expected.map(|latest| (latest.to_string(), None)),
+            "{file} with {policy:?}"
+        );
+    }
+}
+
+#[test]
+fn scrub_replaces_volatile_fields() {
+    let body = r#"{"crate":{"downloads":123,"updated_at":"2025-01-01T00:00:00Z","name":"x"},
+        "versions":[{"num":"1.0.0","downloads":7,"audit_actions":[{"time":"2025-01-01"}]}]}"#;
+    let scrubbed: Value = serde_json::from_str(&scrub(body)).unwrap();
+
+    assert_eq!(scrubbed["crate"]["downloads"], 0);
+    assert_eq!(scrubbed["crate"]["updated_at"], "1970-01-01T00:00:00Z");
+    assert_eq!(scrubbed["crate"]["name"], "x");
+    assert_eq!(scrubbed["versions"][0]["num"], "1.0.0");
+    assert_eq!(scrubbed["versions"][0]["downloads"], 0);
+    assert_eq!(
+        scrubbed["versions"][0]["audit_actions"][0]["time"],
+        "1970-01-01T00:00:00Z"
+    );
+}
+
+#[test]
+fn scrub_is_idempotent_and_ignores_other_types() {
+    let body = r#"{"b":{"downloads":null,"updated_at":5},"a":[1,2]}"#;
+    let once = scrub(body);
+    assert_eq!(scrub(&once), once);
+
+    let scrubbed: Value = serde_json::from_str(&once).unwrap();
+    assert_eq!(scrubbed["b"]["downloads"], Value::Null);
+    assert_eq!(scrubbed["b"]["updated_at"], 5);
+    assert!(once.find("\"a\"") < once.find("\"b\""), "keys are sorted");
+}
@@ -1,33 +1,1957 @@
 //! Integration tests for tiny-update-check
 
-use std::time::Duration;
-use tiny_update_check::UpdateChecker;
+mod common;
+
+use common::{MockRegistry, parse_head, planned_head};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+use tiny_update_check::{
+    CheckMode, CheckStats, CratesIoSource, DistManifestSource, Error, ErrorKind, GitHubSource,
+    GitLabSource, GitTagsSource, HomebrewSource, HttpFailure, JsonSource, ManifestSource,
+    NpmSource, OciSource, RaceWinner, SourceContext, SuggestStrategy, TextSource, UpdateChecker,
+    VersionPolicy, VersionSource, WingetSource,
+};
 
 #[test]
-fn test_checker_configuration() {
-    let checker = UpdateChecker::new("serde", "1.0.0")
-        .cache_duration(Duration::from_secs(3600))
-        .timeout(Duration::from_secs(10))
+fn bom_prefixed_response_parses() {
+    let registry = MockRegistry::serve(include_bytes!("fixtures/bom_prefixed.json").as_slice());
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None);
+
+    let update = checker.check().unwrap().unwrap();
+    assert_eq!(update.latest, "2.0.0");
+    assert!(registry.requests()[0].starts_with("GET /api/v1/crates/test "));
+}
+
+#[test]
+fn responses_for_another_crate_are_refused() {
+    let registry = MockRegistry::serve(include_bytes!("fixtures/other_crate.json").as_slice());
+    let err = UpdateChecker::new("serde", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Parse);
+    assert!(
+        err.to_string()
+            .contains("response describes crate 'serde_json', not 'serde'"),
+        "{err}"
+    );
+}
+
+#[test]
+fn crate_names_match_ignoring_case_and_separators() {
+    let registry = MockRegistry::serve(include_bytes!("fixtures/underscored_name.json").as_slice());
+    let checker = UpdateChecker::new("my-tool", "1.0.0")
+        .registry_url(registry.url())
         .cache_dir(None);
+    assert_eq!(checker.check().unwrap().unwrap().latest, "2.0.0");
+}
+
+#[test]
+fn invalid_utf8_response_reports_offset() {
+    let registry = MockRegistry::serve(b"{\"crate\":\xff}".as_slice());
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None);
+
+    match checker.check() {
+        Err(Error::ParseError(msg)) => {
+            assert_eq!(msg, "response is not valid UTF-8 at byte 9");
+        }
+        other => panic!("expected a UTF-8 parse error, got {other:?}"),
+    }
+}
+
+fn renamed_checker(registry: &MockRegistry) -> UpdateChecker {
+    UpdateChecker::new("oldtool", "1.4.0")
+        .successor_crate("newtool")
+        .registry_url(registry.url())
+        .cache_dir(None)
+}
+
+#[test]
+fn newer_successor_crate_is_reported_as_rename() {
+    let registry = MockRegistry::crates(&[("oldtool", "1.5.0"), ("newtool", "2.0.0")]);
+
+    let update = renamed_checker(&registry)
+        .check_detailed()
+        .unwrap()
+        .unwrap();
+    assert_eq!(update.latest, "2.0.0");
+    assert_eq!(update.renamed_to.as_deref(), Some("newtool"));
+    assert_eq!(
+        update.summary(),
+        "This tool is now published as newtool: 1.4.0 -> 2.0.0"
+    );
+}
+
+#[test]
+fn successor_crate_falls_back_to_original_when_unavailable() {
+    let registry = MockRegistry::crates(&[("oldtool", "1.5.0")]);
+
+    let update = renamed_checker(&registry)
+        .check_detailed()
+        .unwrap()
+        .unwrap();
+    assert_eq!(update.latest, "1.5.0");
+    assert_eq!(update.renamed_to, None);
+    assert_eq!(update.summary(), "Update available: 1.4.0 -> 1.5.0");
+}
+
+#[test]
+fn successor_crate_not_newer_is_ignored() {
+    let registry = MockRegistry::crates(&[("oldtool", "1.4.0"), ("newtool", "0.1.0")]);
+
+    assert!(renamed_checker(&registry).check().unwrap().is_none());
+    assert_eq!(registry.requests().len(), 2);
+}
+
+#[test]
+fn stats_count_cache_and_network_outcomes() {
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let dir = tempfile::tempdir().unwrap();
+    let stats = Arc::new(CheckStats::new());
+    let cached = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()))
+        .stats(Arc::clone(&stats));
+
+    // Cold cache: a miss served by the registry, then two hits.
+    for _ in 0..3 {
+        assert_eq!(cached.check().unwrap().unwrap().latest, "2.0.0");
+    }
+    // Caching disabled: every check goes to the registry.
+    cached.cache_duration(Duration::ZERO).check().unwrap();
+    // A crate the registry doesn't know: a miss and a failed request.
+    let missing = UpdateChecker::new("missing", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None)
+        .stats(Arc::clone(&stats));
+    assert!(missing.check().is_err());
+
+    let snapshot = stats.snapshot();
+    assert_eq!(snapshot.cache_hits, 2);
+    assert_eq!(snapshot.cache_misses, 3);
+    assert_eq!(snapshot.network_successes, 2);
+    assert_eq!(snapshot.network_failures, 1);
+    assert!(snapshot.network_time > Duration::ZERO);
+    assert_eq!(registry.requests().len(), 3);
+}
+
+#[test]
+fn safest_first_suggests_compatible_release() {
+    let registry =
+        MockRegistry::serve(include_bytes!("fixtures/versions_spanning.json").as_slice());
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "2.3.1")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()))
+        .suggest(SuggestStrategy::SafestFirst);
+
+    for _ in 0..2 {
+        let update = checker.check_detailed().unwrap().unwrap();
+        assert_eq!(update.latest, "3.1.0");
+        assert_eq!(update.safe_latest.as_deref(), Some("2.3.9"));
+    }
+    // The second check was served from the cache, version list included.
+    assert_eq!(registry.requests().len(), 1);
+}
+
+#[test]
+fn version_policy_changes_the_reported_update() {
+    let registry =
+        MockRegistry::serve(include_bytes!("fixtures/prerelease_newest.json").as_slice());
+    let checker = || {
+        UpdateChecker::new("tool", "1.9.0")
+            .registry_url(registry.url())
+            .cache_dir(None)
+    };
+    let latest = |checker: UpdateChecker| checker.check().unwrap().map(|update| update.latest);
+
+    // The newest publish is a pre-release, so only the highest stable
+    // version is worth reporting by default.
+    assert_eq!(latest(checker()).as_deref(), Some("1.9.3"));
+    assert_eq!(
+        latest(checker().version_policy(VersionPolicy::Newest)),
+        None
+    );
+    assert_eq!(
+        latest(checker().include_prerelease(true)).as_deref(),
+        Some("2.0.0-rc.1")
+    );
+    assert_eq!(
+        latest(
+            checker()
+                .include_prerelease(true)
+                .version_policy(VersionPolicy::MaxStable)
+        )
+        .as_deref(),
+        Some("1.9.3")
+    );
+}
+
+#[test]
+fn http_failures_report_specific_kinds() {
+    for (status, kind) in [
+        (401, ErrorKind::Unauthorized),
+        (403, ErrorKind::Unauthorized),
+        (404, ErrorKind::NotFound),
+        (429, ErrorKind::RateLimited),
+        (500, ErrorKind::ServerError),
+        (503, ErrorKind::ServerError),
+        (418, ErrorKind::Http),
+    ] {
+        let registry = MockRegistry::serve_status(status, "");
+        let checker = UpdateChecker::new("test", "1.0.0")
+            .registry_url(registry.url())
+            .cache_dir(None);
+        let err = checker.check().unwrap_err();
+        assert_eq!(err.kind(), kind, "status {status}");
+        assert_eq!(err.status(), Some(status));
+    }
+}
+
+#[test]
+fn slow_registry_reports_timeout() {
+    let registry = MockRegistry::serve_with(|_| {
+        std::thread::sleep(Duration::from_secs(3));
+        (200, Vec::new())
+    });
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .timeout(Duration::from_secs(1))
+        .cache_dir(None);
+    assert_eq!(checker.check().unwrap_err().kind(), ErrorKind::Timeout);
+}
+
+#[test]
+fn oversized_response_reports_too_large() {
+    let registry = MockRegistry::serve(vec![b' '; 64 * 1024]);
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .max_memory(16 * 1024)
+        .cache_dir(None);
+    assert_eq!(checker.check().unwrap_err().kind(), ErrorKind::TooLarge);
+}
+
+#[test]
+fn endless_responses_are_abandoned_at_the_limit() {
+    use common::serve_endless;
+
+    let (url, sent) = serve_endless(b"<html>");
+    let started = std::time::Instant::now();
+    let err = UpdateChecker::new("test", "1.0.0")
+        .registry_url(&url)
+        .max_memory(16 * 1024 * 1024)
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TooLarge);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    // Only the limit and what the socket buffers held were ever sent.
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(sent.load(Ordering::SeqCst) < 32 * 1024 * 1024);
+
+    // A message is cut short rather than read in full.
+    let (message_url, sent) = serve_endless(b"Please upgrade.");
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let update = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .message_url(message_url)
+        .cache_dir(None)
+        .check_detailed()
+        .unwrap()
+        .unwrap();
+    assert_eq!(update.message.as_deref(), Some("Please upgrade."));
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(sent.load(Ordering::SeqCst) < 32 * 1024 * 1024);
+}
+
+#[test]
+fn ephemeral_checks_leave_no_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let registry = MockRegistry::serve(include_bytes!("fixtures/serde_response.json").as_slice());
+    let checker = UpdateChecker::new("serde", "1.0.100")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().join("cache")))
+        .state_dir(Some(dir.path().join("state")))
+        .daily_request_budget(2)
+        .suggest(SuggestStrategy::SafestFirst)
+        .ephemeral(true);
+
+    let clone = checker.clone();
+
+    let first = checker.check_detailed().unwrap().unwrap();
+    // Served from the in-memory cache, which clones share.
+    let second = clone.check_detailed().unwrap().unwrap();
+    assert_eq!(first.latest, second.latest);
+    assert_eq!(first.safe_latest, second.safe_latest);
+    assert_eq!(registry.requests().len(), 1);
+
+    // Forced checks spend the in-memory budget until it runs out.
+    let forced = checker.mode(CheckMode::Forced);
+    assert!(forced.check().unwrap().is_some());
+    assert!(forced.check().unwrap().is_none());
+    assert_eq!(registry.requests().len(), 2);
+
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn enrichments_share_a_single_request() {
+    let registry = MockRegistry::serve(include_bytes!("fixtures/serde_response.json").as_slice());
+    let checker = UpdateChecker::new("serde", "1.0.100")
+        .registry_url(registry.url())
+        .cache_dir(None)
+        .suggest(SuggestStrategy::SafestFirst)
+        .max_binary_age(Duration::from_secs(1))
+        .build_timestamp(std::time::UNIX_EPOCH)
+        .stats(Arc::new(CheckStats::new()));
+
+    let update = checker.check_detailed().unwrap().unwrap();
+    assert_eq!(update.latest, "1.0.228");
+    assert_eq!(update.safe_latest.as_deref(), Some("1.0.228"));
+    assert!(update.age_notice.is_some());
+    assert_eq!(registry.requests().len(), 1);
+}
+
+#[test]
+fn registry_updated_at_survives_the_cache() {
+    let registry = MockRegistry::serve(include_bytes!("fixtures/serde_response.json").as_slice());
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("serde", "1.0.100")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()));
+
+    for _ in 0..2 {
+        let update = checker.check_detailed().unwrap().unwrap();
+        assert_eq!(
+            update.registry_updated_at.as_deref(),
+            Some("1970-01-01T00:00:00Z")
+        );
+    }
+    assert_eq!(registry.requests().len(), 1);
+}
+
+#[test]
+fn empty_cache_file_heals_on_next_check() {
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let dir = tempfile::tempdir().unwrap();
+    // What a crash between writing and flushing the cache can leave behind.
+    let cache = dir.path().join("tool-update-check");
+    std::fs::write(&cache, "").unwrap();
+
+    for durable in [false, true] {
+        let checker = UpdateChecker::new("tool", "1.0.0")
+            .registry_url(registry.url())
+            .cache_dir(Some(dir.path().to_path_buf()))
+            .durable_cache(durable);
+        assert_eq!(checker.check().unwrap().unwrap().latest, "2.0.0");
+        let state = tiny_update_check::read_cached_state(dir.path(), "tool").unwrap();
+        assert_eq!(state.unwrap().latest, "2.0.0");
+        std::fs::write(&cache, "").unwrap();
+    }
+    assert_eq!(registry.requests().len(), 2);
+}
+
+#[test]
+fn cache_keys_keep_checkers_for_one_crate_apart() {
+    let public = MockRegistry::crates(&[("plugin", "2.0.0")]);
+    let private = MockRegistry::crates(&[("plugin", "3.0.0")]);
+    let cache = tempfile::tempdir().unwrap();
+    let state = tempfile::tempdir().unwrap();
+    let checker = |registry: &MockRegistry, key: &str| {
+        UpdateChecker::new("plugin", "1.0.0")
+            .registry_url(registry.url())
+            .cache_dir(Some(cache.path().to_path_buf()))
+            .state_dir(Some(state.path().to_path_buf()))
+            .daily_request_budget(5)
+            .cache_key(key)
+    };
+    let public_checker = checker(&public, "my-app.public.plugin");
+    let private_checker = checker(&private, "my-app.private.plugin");
+
+    for _ in 0..2 {
+        assert_eq!(public_checker.check().unwrap().unwrap().latest, "2.0.0");
+        assert_eq!(private_checker.check().unwrap().unwrap().latest, "3.0.0");
+    }
+    assert_eq!(public.requests().len(), 1);
+    assert_eq!(private.requests().len(), 1);
+
+    let cache = cache.path();
+    assert!(cache.join("my-app.public.plugin").exists());
+    assert!(cache.join("my-app.private.plugin").exists());
+    assert!(!cache.join("plugin-update-check").exists());
+    let state = state.path();
+    assert!(state.join("my-app.public.plugin.budget").exists());
+    assert!(state.join("my-app.private.plugin.budget").exists());
+}
+
+#[test]
+fn parse_failures_capture_the_response() {
+    let body = format!("{{\"crate\": {}", "x".repeat(100 * 1024));
+    let registry = MockRegistry::serve(body.clone());
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()));
+
+    // Off by default.
+    assert_eq!(checker.check().unwrap_err().kind(), ErrorKind::Parse);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+    let err = checker.capture_failed_bodies(true).check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Parse);
+    let capture = std::fs::read_dir(dir.path())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let name = capture.file_name().unwrap().to_string_lossy().into_owned();
+    assert!(
+        name.starts_with("test-update-check.parse-failure-"),
+        "{name}"
+    );
+    assert!(
+        err.to_string().contains(&*capture.to_string_lossy()),
+        "{err}"
+    );
+    assert_eq!(
+        std::fs::read(&capture).unwrap(),
+        body.as_bytes()[..64 * 1024]
+    );
+}
+
+#[test]
+fn seeded_cache_answers_first_check() {
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().join("not-yet-created")));
+    let hour = Duration::from_secs(60 * 60);
+
+    checker
+        .seed_cache("1.2.0", SystemTime::now() - hour)
+        .unwrap();
+    assert_eq!(checker.check().unwrap().unwrap().latest, "1.2.0");
+    assert!(registry.requests().is_empty());
+
+    // A stale seed is refetched, and the fetch supersedes it.
+    let checker = checker.cache_duration(hour / 2);
+    assert_eq!(checker.check().unwrap().unwrap().latest, "2.0.0");
+    assert_eq!(registry.requests().len(), 1);
+
+    // Seeding never replaces fresher data.
+    checker
+        .seed_cache("1.2.0", SystemTime::now() - hour / 4)
+        .unwrap();
+    assert_eq!(checker.check().unwrap().unwrap().latest, "2.0.0");
+    assert_eq!(registry.requests().len(), 1);
+}
+
+#[test]
+fn seed_cache_validates_before_writing() {
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0").cache_dir(Some(dir.path().to_path_buf()));
+    let now = SystemTime::now();
+
+    let err = checker.seed_cache("not a version", now).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Version);
+    let err = checker.clone().cache_key("a/b").seed_cache("1.0.0", now);
+    assert_eq!(err.unwrap_err().kind(), ErrorKind::Config);
+    let err = checker.cache_dir(None).seed_cache("1.0.0", now);
+    assert_eq!(err.unwrap_err().kind(), ErrorKind::Cache);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn ephemeral_checkers_seed_in_memory() {
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None)
+        .ephemeral(true);
+
+    checker.seed_cache("1.2.0", SystemTime::now()).unwrap();
+    assert_eq!(checker.check().unwrap().unwrap().latest, "1.2.0");
+    assert!(registry.requests().is_empty());
+}
+
+#[test]
+fn cleared_caches_are_refetched() {
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let dir = tempfile::tempdir().unwrap();
+    let state = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()))
+        .state_dir(Some(state.path().to_path_buf()))
+        .suggest(SuggestStrategy::SafestFirst);
+
+    assert_eq!(checker.check().unwrap().unwrap().latest, "2.0.0");
+    assert_eq!(checker.check().unwrap().unwrap().latest, "2.0.0");
+    assert_eq!(registry.requests().len(), 1);
+    let other = dir.path().join("unrelated");
+    std::fs::write(&other, "keep").unwrap();
+
+    checker.clear_cache().unwrap();
+    let left: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(left, [other]);
+    // State is kept.
+    assert_eq!(std::fs::read_dir(state.path()).unwrap().count(), 1);
+    // Clearing twice is fine.
+    checker.clear_cache().unwrap();
+
+    assert_eq!(checker.check().unwrap().unwrap().latest, "2.0.0");
+    assert_eq!(registry.requests().len(), 2);
+}
+
+#[test]
+fn clearing_refuses_names_that_leave_the_cache_dir() {
+    let root = tempfile::tempdir().unwrap();
+    let dir = root.path().join("cache");
+    std::fs::create_dir(&dir).unwrap();
+    let outside = root.path().join("outside");
+    std::fs::write(&outside, "keep").unwrap();
+    let checker = |name: &str| UpdateChecker::new(name, "1.0.0").cache_dir(Some(dir.clone()));
+
+    for name in ["../outside", "cache/../../outside", "/tmp/outside", ""] {
+        let err = checker(name).clear_cache().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidCrateName, "{name:?}");
+    }
+    for key in ["../outside", "..", ".", "a/b"] {
+        let err = checker("test").cache_key(key).clear_cache().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Config, "{key:?}");
+    }
+    assert!(outside.exists());
+
+    // Nothing to clear.
+    checker("test").cache_dir(None).clear_cache().unwrap();
+}
+
+#[test]
+fn ephemeral_checkers_clear_in_memory() {
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None)
+        .ephemeral(true);
+
+    checker.seed_cache("1.2.0", SystemTime::now()).unwrap();
+    checker.clear_cache().unwrap();
+    assert_eq!(checker.check().unwrap().unwrap().latest, "2.0.0");
+    assert_eq!(registry.requests().len(), 1);
+}
+
+#[test]
+fn successive_runs_report_version_transitions() {
+    let state = tempfile::tempdir().unwrap();
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let run = |version: &str| {
+        UpdateChecker::new("test", version)
+            .registry_url(registry.url())
+            .cache_dir(None)
+            .state_dir(Some(state.path().to_path_buf()))
+    };
+
+    // First run: nothing recorded yet.
+    let first = run("1.0.0");
+    assert_eq!(first.previous_run_version(), None);
+    assert_eq!(first.just_upgraded(), None);
+    assert!(first.check().unwrap().is_some());
+
+    // Upgrade, reported for the whole run even after it is recorded.
+    let upgraded = run("1.1.0");
+    assert!(upgraded.check().unwrap().is_some());
+    let upgrade = upgraded.just_upgraded().unwrap();
+    assert_eq!(
+        (upgrade.previous.as_str(), upgrade.current.as_str()),
+        ("1.0.0", "1.1.0")
+    );
+    assert!(!upgrade.downgraded);
+
+    // Same version again: no transition. Recorded whatever the outcome.
+    let same = run("1.1.0").mode(CheckMode::Disabled);
+    assert_eq!(same.previous_run_version().as_deref(), Some("1.1.0"));
+    assert_eq!(same.just_upgraded(), None);
+    assert!(same.check().unwrap().is_none());
+
+    // Downgrade, flagged as such.
+    let downgraded = run("1.0.5");
+    let change = downgraded.just_upgraded().unwrap();
+    assert_eq!(change.previous, "1.1.0");
+    assert!(change.downgraded);
+    downgraded.check().unwrap();
+    assert_eq!(run("1.0.5").just_upgraded(), None);
+
+    // A garbled record reads as a first run.
+    std::fs::write(state.path().join("test-update-check.last-run"), "\0\0 junk").unwrap();
+    assert_eq!(run("1.0.5").previous_run_version(), None);
+    std::fs::write(
+        state.path().join("test-update-check.last-run"),
+        "not-a-version",
+    )
+    .unwrap();
+    assert_eq!(run("1.0.5").just_upgraded(), None);
+}
+
+#[test]
+fn dry_run_matches_the_requests_sent() {
+    // The successor is missing, so the check falls back to the crate itself.
+    let registry = MockRegistry::crates(&[("oldtool", "1.5.0")]);
+    let checker = UpdateChecker::new("oldtool", "1.4.0")
+        .successor_crate("newtool")
+        .registry_url(registry.url())
+        .cache_dir(None);
+
+    let planned: Vec<_> = checker
+        .dry_run()
+        .unwrap()
+        .into_iter()
+        .map(|request| planned_head(request, registry.url()))
+        .collect();
+    assert!(registry.requests().is_empty());
+
+    assert!(checker.check().unwrap().is_some());
+    let sent: Vec<_> = registry
+        .requests()
+        .iter()
+        .map(|head| parse_head(head))
+        .collect();
+    assert_eq!(planned, sent);
+    assert_eq!(sent.len(), 2);
+}
+
+/// Serve the sparse index files under `tests/fixtures/index/`.
+fn fixture_index() -> MockRegistry {
+    let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/index");
+    MockRegistry::serve_with(move |path| {
+        std::fs::read(root.join(path.trim_start_matches('/')))
+            .map_or_else(|_| (404, Vec::new()), |body| (200, body))
+    })
+}
+
+#[test]
+fn sparse_index_lookups_use_cargo_prefixes() {
+    let index = fixture_index();
+    // syn's highest version is a pre-release, which is not reported by
+    // default, just as with the web API.
+    for (name, latest, path) in [
+        ("a", Some("0.2.0"), "/1/a"),
+        ("io", Some("1.2.0"), "/2/io"),
+        ("syn", None, "/3/s/syn"),
+        ("serde", Some("1.0.218"), "/se/rd/serde"),
+    ] {
+        let update = UpdateChecker::new(name, "0.0.1")
+            .use_sparse_index(true)
+            .sparse_index_url(index.url())
+            .cache_dir(None)
+            .check()
+            .unwrap();
+        assert_eq!(update.map(|u| u.latest).as_deref(), latest, "{name}");
+        let requests = index.requests();
+        let (_, requested, _) = parse_head(requests.last().unwrap());
+        assert_eq!(requested, path);
+    }
+}
+
+/// Serve `tag` as the latest release of `octo/tool`, and `404 Not Found` for
+/// anything else.
+fn github_releases(tag: &str) -> MockRegistry {
+    let body = format!(r#"{{"tag_name":"{tag}","published_at":"2025-06-01T12:00:00Z"}}"#);
+    MockRegistry::serve_with(move |path| {
+        if path == "/repos/octo/tool/releases/latest" {
+            (200, body.clone().into_bytes())
+        } else {
+            (404, br#"{"message":"Not Found"}"#.to_vec())
+        }
+    })
+}
+
+#[test]
+fn raced_registry_takes_the_first_success() {
+    let slow = |respond: fn(&str) -> (u16, Vec<u8>)| {
+        MockRegistry::serve_with(move |path| {
+            std::thread::sleep(Duration::from_millis(300));
+            respond(path)
+        })
+    };
+    let checker = |github: &MockRegistry, registry: &MockRegistry| {
+        UpdateChecker::new("tool", "1.0.0")
+            .github_releases(GitHubSource::new("octo", "tool").api_url(github.url()))
+            .registry_url(registry.url())
+            .race_registry(true)
+            .cache_dir(None)
+    };
+
+    // The faster answer wins.
+    let github = github_releases("v2.1.0");
+    let registry = slow(|_| (200, br#"{"crate":{"newest_version":"2.0.0"}}"#.to_vec()));
+    let update = checker(&github, &registry)
+        .check_detailed()
+        .unwrap()
+        .unwrap();
+    assert_eq!(update.latest, "2.1.0");
+    assert_eq!(update.race_winner, Some(RaceWinner::ReleaseSource));
+    assert_eq!(update.registry_url, None);
+
+    // A fast failure doesn't beat a slower success.
+    let github = github_releases("v2.1.0");
+    let registry = MockRegistry::serve_status(500, "down");
+    let update = checker(&github, &registry)
+        .check_detailed()
+        .unwrap()
+        .unwrap();
+    assert_eq!(update.race_winner, Some(RaceWinner::ReleaseSource));
+    let github = slow(|_| (404, br#"{"message":"Not Found"}"#.to_vec()));
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let update = checker(&github, &registry)
+        .check_detailed()
+        .unwrap()
+        .unwrap();
+    assert_eq!(update.latest, "2.0.0");
+    assert_eq!(update.race_winner, Some(RaceWinner::Registry));
+    assert_eq!(update.registry_url.as_deref(), Some(registry.url()));
+
+    // Once both fail, the release source's error names every URL tried.
+    let registry = MockRegistry::serve_status(503, "down");
+    let err = checker(&github, &registry).check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    let message = err.to_string();
+    assert!(message.contains("no source answered"), "{message}");
+    assert!(message.contains(github.url()), "{message}");
+    assert!(message.contains(registry.url()), "{message}");
+}
+
+#[test]
+fn github_releases_are_an_alternative_source() {
+    let github = github_releases("v2.1.0");
+    let checker = UpdateChecker::new("tool", "2.0.0")
+        .github_releases(GitHubSource::new("octo", "tool").api_url(github.url()))
+        .cache_dir(None);
+
+    let planned: Vec<_> = checker
+        .dry_run()
+        .unwrap()
+        .into_iter()
+        .map(|request| planned_head(request, github.url()))
+        .collect();
+    let update = checker.check_detailed().unwrap().unwrap();
+    assert_eq!(update.latest, "2.1.0");
+    assert_eq!(
+        update.registry_updated_at.as_deref(),
+        Some("2025-06-01T12:00:00Z")
+    );
+
+    let sent: Vec<_> = github
+        .requests()
+        .iter()
+        .map(|head| parse_head(head))
+        .collect();
+    assert_eq!(planned, sent);
+    let (_, _, headers) = &sent[0];
+    assert!(headers.contains(&(
+        "accept".to_string(),
+        "application/vnd.github+json".to_string()
+    )));
+}
+
+#[test]
+fn missing_github_release_reports_not_found() {
+    let github = github_releases("v2.1.0");
+    let err = UpdateChecker::new("tool", "2.0.0")
+        .github_releases(GitHubSource::new("octo", "gone").api_url(github.url()))
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains("octo/gone"), "{err}");
+}
+
+#[test]
+fn github_token_is_sent_but_never_shown() {
+    let github = github_releases("v2.1.0");
+    let checker = UpdateChecker::new("tool", "2.0.0")
+        .github_releases(
+            GitHubSource::new("octo", "tool")
+                .api_url(github.url())
+                .token("ghp_s3cret"),
+        )
+        .cache_dir(None);
+
+    let plan = checker.dry_run().unwrap();
+    assert!(checker.check().unwrap().is_some());
+    let (_, _, headers) = parse_head(&github.requests()[0]);
+    assert!(headers.contains(&("authorization".to_string(), "Bearer ghp_s3cret".to_string())));
 
-    // Just verify it builds - actual network tests would be flaky
-    assert!(checker.check().is_ok() || checker.check().is_err());
+    for debug in [format!("{checker:?}"), format!("{plan:?}")] {
+        assert!(!debug.contains("ghp_s3cret"), "{debug}");
+    }
 }
 
 #[test]
-fn test_check_detailed() {
+fn github_releases_feed_needs_no_api() {
+    let feeds = MockRegistry::serve_with(|path| match path {
+        "/BurntSushi/ripgrep/releases.atom" => {
+            (200, include_bytes!("fixtures/atom/ripgrep.atom").to_vec())
+        }
+        "/octo/beta/releases.atom" => (
+            200,
+            include_bytes!("fixtures/atom/prerelease.atom").to_vec(),
+        ),
+        "/octo/empty/releases.atom" => (
+            200,
+            include_bytes!("fixtures/atom/no-releases.atom").to_vec(),
+        ),
+        _ => (404, b"Not Found".to_vec()),
+    });
+    let checker = |repo: &str, current: &str| {
+        let (owner, repo) = repo.split_once('/').unwrap();
+        UpdateChecker::new("tool", current)
+            .github_releases(
+                GitHubSource::new(owner, repo)
+                    .releases_feed(true)
+                    .web_url(feeds.url())
+                    .token("ghp_s3cret"),
+            )
+            .cache_dir(None)
+    };
+
+    let ripgrep = checker("BurntSushi/ripgrep", "14.1.1");
+    let plan = ripgrep.dry_run().unwrap();
+    let update = ripgrep.check_detailed().unwrap().unwrap();
+    assert_eq!(update.latest, "15.1.0");
+    assert_eq!(
+        update.registry_updated_at.as_deref(),
+        Some("2025-10-22T13:35:19Z")
+    );
+    let sent = parse_head(&feeds.requests()[0]);
+    assert_eq!(planned_head(plan[0].clone(), feeds.url()), sent);
+    let (_, _, headers) = sent;
+    assert!(headers.contains(&("accept".to_string(), "application/atom+xml".to_string())));
+    assert!(!headers.iter().any(|(name, _)| name == "authorization"));
+
+    // A pre-release at the top of the feed follows the pre-release policy.
+    assert!(checker("octo/beta", "1.4.2").check().unwrap().is_none());
+    let beta = checker("octo/beta", "1.4.2")
+        .include_prerelease(true)
+        .check();
+    assert_eq!(beta.unwrap().unwrap().latest, "2.0.0-beta.1");
+
+    let err = checker("octo/empty", "1.0.0").check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains("octo/empty"), "{err}");
+}
+
+#[test]
+fn gitlab_releases_are_an_alternative_source() {
+    let gitlab = MockRegistry::serve_with(|path| match path {
+        "/api/v4/projects/tools%2Fmy-tool/releases?per_page=1&order_by=released_at" => (
+            200,
+            br#"[{"tag_name":"v2.1.0","released_at":"2025-06-01T12:00:00Z"}]"#.to_vec(),
+        ),
+        "/api/v4/projects/tools%2Fno-releases/releases?per_page=1&order_by=released_at" => {
+            (200, b"[]".to_vec())
+        }
+        _ => (404, br#"{"message":"404 Project Not Found"}"#.to_vec()),
+    });
+    let checker = UpdateChecker::new("my-tool", "2.0.0")
+        .gitlab_releases(GitLabSource::new(gitlab.url(), "tools/my-tool").token("glpat-s3cret"))
+        .cache_dir(None);
+
+    let plan = checker.dry_run().unwrap();
+    let update = checker.check_detailed().unwrap().unwrap();
+    assert_eq!(update.latest, "2.1.0");
+    assert_eq!(
+        update.registry_updated_at.as_deref(),
+        Some("2025-06-01T12:00:00Z")
+    );
+    assert!(!format!("{checker:?} {plan:?}").contains("glpat-s3cret"));
+
+    let planned: Vec<_> = plan
+        .into_iter()
+        .map(|request| planned_head(request, gitlab.url()))
+        .collect();
+    let sent: Vec<_> = gitlab
+        .requests()
+        .iter()
+        .map(|head| parse_head(head))
+        .collect();
+    assert_eq!(planned, sent);
+    let (_, _, headers) = &sent[0];
+    assert!(headers.contains(&("private-token".to_string(), "glpat-s3cret".to_string())));
+
+    for project in ["tools/no-releases", "tools/gone"] {
+        let err = UpdateChecker::new("my-tool", "2.0.0")
+            .gitlab_releases(GitLabSource::new(gitlab.url(), project))
+            .cache_dir(None)
+            .check()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound, "{project}");
+        assert!(err.to_string().contains(project), "{err}");
+    }
+}
+
+#[test]
+fn text_source_reads_a_version_file() {
+    let mirror = MockRegistry::serve_with(|path| match path {
+        "/tool/latest.txt" => (200, b"v1.4.0\n".to_vec()),
+        "/tool/index.html" => (200, b"<html><body>Mirror index</body></html>".repeat(10)),
+        _ => (404, b"Not Found".to_vec()),
+    });
+    let checker = |path: &str| {
+        UpdateChecker::new("tool", "1.3.0")
+            .text_source(TextSource::new(format!("{}{path}", mirror.url())))
+            .cache_dir(None)
+    };
+
+    let tool = checker("/tool/latest.txt");
+    let plan = tool.dry_run().unwrap();
+    assert_eq!(tool.check().unwrap().unwrap().latest, "1.4.0");
+    assert_eq!(
+        planned_head(plan[0].clone(), mirror.url()),
+        parse_head(&mirror.requests()[0])
+    );
+
+    let err = checker("/tool/index.html").check().unwrap_err();
+    assert!(matches!(err, Error::ParseError(_)), "{err}");
+    let err = checker("/tool/latest.txt")
+        .text_source(
+            TextSource::new(format!("{}/tool/latest.txt", mirror.url())).strip_v_prefix(false),
+        )
+        .check()
+        .unwrap_err();
+    assert!(matches!(err, Error::ParseError(_)), "{err}");
+    let err = checker("/gone.txt").check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains("/gone.txt"), "{err}");
+}
+
+#[test]
+fn json_source_reads_a_nested_version() {
+    let status = MockRegistry::serve(r#"{"app":{"name":"tool","releases":[{"latest":"1.4.2"}]}}"#);
+    let checker = |pointer: &str| {
+        UpdateChecker::new("tool", "1.3.0")
+            .json_source(JsonSource::new(format!("{}/status", status.url()), pointer))
+            .cache_dir(None)
+    };
+
+    let tool = checker("/app/releases/0/latest");
+    let plan = tool.dry_run().unwrap();
+    assert_eq!(tool.check().unwrap().unwrap().latest, "1.4.2");
+    assert_eq!(
+        planned_head(plan[0].clone(), status.url()),
+        parse_head(&status.requests()[0])
+    );
+
+    let err = checker("/app/latest").check().unwrap_err();
+    assert!(matches!(err, Error::ParseError(_)), "{err}");
+    let err = checker("/app").check().unwrap_err();
+    assert!(matches!(err, Error::ParseError(_)), "{err}");
+    let err = checker("app/latest").check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Config);
+}
+
+#[test]
+fn manifest_source_reads_package_version() {
+    let forge = MockRegistry::serve_with(|path| match path {
+        "/me/tool/main/Cargo.toml" => (
+            200,
+            include_bytes!("fixtures/manifest/simple.toml").to_vec(),
+        ),
+        "/me/tool/main/crates/core/Cargo.toml" => (
+            200,
+            include_bytes!("fixtures/manifest/member.toml").to_vec(),
+        ),
+        _ => (404, b"404: Not Found".to_vec()),
+    });
+    let checker = |path: &str| {
+        UpdateChecker::new("tool", "15.0.0")
+            .manifest_source(ManifestSource::new(format!("{}{path}", forge.url())))
+            .cache_dir(None)
+    };
+
+    let tool = checker("/me/tool/main/Cargo.toml");
+    let plan = tool.dry_run().unwrap();
+    assert_eq!(tool.check().unwrap().unwrap().latest, "15.1.0");
+    assert_eq!(
+        planned_head(plan[0].clone(), forge.url()),
+        parse_head(&forge.requests()[0])
+    );
+
+    let err = checker("/me/tool/main/crates/core/Cargo.toml")
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Parse);
+    assert!(err.to_string().contains("version.workspace"), "{err}");
+    let err = checker("/me/gone/main/Cargo.toml").check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+#[test]
+fn homebrew_formula_is_cached_apart_from_the_registry() {
+    let servers = MockRegistry::serve_with(|path| match path {
+        "/api/v1/crates/ripgrep" => (200, br#"{"crate":{"newest_version":"15.1.0"}}"#.to_vec()),
+        "/api/formula/ripgrep.json" => (
+            200,
+            br#"{"name":"ripgrep","versions":{"stable":"15.0.0","head":"HEAD","bottle":true}}"#
+                .to_vec(),
+        ),
+        _ => (404, br#"{"error":"Not Found"}"#.to_vec()),
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let checker = || {
+        UpdateChecker::new("ripgrep", "14.1.1")
+            .registry_url(servers.url())
+            .cache_dir(Some(dir.path().to_path_buf()))
+    };
+    let brew =
+        |formula: &str| HomebrewSource::new(formula).api_url(format!("{}/api", servers.url()));
+
+    assert_eq!(checker().check().unwrap().unwrap().latest, "15.1.0");
+    let homebrew = checker().homebrew_formula(brew("ripgrep"));
+    let plan = homebrew.dry_run().unwrap();
+    assert_eq!(homebrew.check().unwrap().unwrap().latest, "15.0.0");
+    assert_eq!(
+        planned_head(plan[0].clone(), servers.url()),
+        parse_head(&servers.requests()[1])
+    );
+    // Each answer is now cached under its own key.
+    assert_eq!(homebrew.check().unwrap().unwrap().latest, "15.0.0");
+    assert_eq!(checker().check().unwrap().unwrap().latest, "15.1.0");
+    assert_eq!(servers.requests().len(), 2);
+
+    let err = checker()
+        .homebrew_formula(brew("no-such-formula"))
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains("no-such-formula"), "{err}");
+}
+
+#[test]
+fn npm_package_is_cached_apart_from_the_registry() {
+    let servers = MockRegistry::serve_with(|path| match path {
+        "/api/v1/crates/tool" => (200, br#"{"crate":{"newest_version":"2.2.0"}}"#.to_vec()),
+        "/npm/@my-org%2Ftool" => (
+            200,
+            br#"{"name":"@my-org/tool","dist-tags":{"latest":"2.1.0","next":"3.0.0-rc.1"}}"#
+                .to_vec(),
+        ),
+        _ => (404, br#"{"error":"Not found"}"#.to_vec()),
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let checker = || {
+        UpdateChecker::new("tool", "2.0.0")
+            .registry_url(servers.url())
+            .cache_dir(Some(dir.path().to_path_buf()))
+    };
+    let npm =
+        |package: &str| NpmSource::new(package).registry_url(format!("{}/npm", servers.url()));
+
+    let wrapper = checker().npm_package(npm("@my-org/tool"));
+    let plan = wrapper.dry_run().unwrap();
+    assert_eq!(wrapper.check().unwrap().unwrap().latest, "2.1.0");
+    assert_eq!(
+        planned_head(plan[0].clone(), servers.url()),
+        parse_head(&servers.requests()[0])
+    );
+    assert_eq!(checker().check().unwrap().unwrap().latest, "2.2.0");
+    assert_eq!(wrapper.check().unwrap().unwrap().latest, "2.1.0");
+    assert_eq!(servers.requests().len(), 2);
+
+    let err = checker()
+        .npm_package(npm("@my-org/gone"))
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains("@my-org/gone"), "{err}");
+}
+
+#[test]
+fn winget_versions_are_compared_leniently() {
+    let github = MockRegistry::serve_with(|path| {
+        match path {
+        "/repos/microsoft/winget-pkgs/contents/manifests/m/Me/Tool" => (
+            200,
+            br#"[{"name":"1.2.0","type":"dir"},{"name":"1.2.0.5","type":"dir"},{"name":"1.3","type":"dir"},{"name":"Preview","type":"dir"}]"#
+                .to_vec(),
+        ),
+        _ => (404, br#"{"message":"Not Found"}"#.to_vec()),
+    }
+    });
+    let checker = |current: &str, package: &str| {
+        UpdateChecker::new("tool", current)
+            .winget_package(WingetSource::new(package).api_url(github.url()))
+            .cache_dir(None)
+    };
+
+    let tool = checker("1.2.0", "Me.Tool");
+    let plan = tool.dry_run().unwrap();
+    let update = tool.check().unwrap().unwrap();
+    assert_eq!(update.latest, "1.3");
+    assert_eq!(
+        planned_head(plan[0].clone(), github.url()),
+        parse_head(&github.requests()[0])
+    );
+    assert!(checker("1.3.0", "Me.Tool").check().unwrap().is_none());
+
+    let err = checker("1.2.0", "Me.Gone").check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains("Me.Gone"), "{err}");
+    let err = checker("1.2.0", "tool").check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Config);
+}
+
+/// A test double answering with a fixed version, counting its calls.
+struct Counting {
+    version: &'static str,
+    calls: Arc<AtomicUsize>,
+}
+
+impl VersionSource for Counting {
+    fn latest(&self, _context: &SourceContext<'_>) -> Result<String, Error> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        match self.version {
+            "" => Err(Error::HttpError(HttpFailure::new("mirror unreachable"))),
+            version => Ok(version.to_string()),
+        }
+    }
+
+    fn id(&self) -> String {
+        format!("counting:{}", self.version)
+    }
+}
+
+#[test]
+fn version_sources_get_caching_and_comparison() {
+    let dir = tempfile::tempdir().unwrap();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let checker = |version: &'static str| {
+        UpdateChecker::new("tool", "1.0.0")
+            .with_source(Counting {
+                version,
+                calls: Arc::clone(&calls),
+            })
+            .cache_dir(Some(dir.path().to_path_buf()))
+    };
+
+    assert!(checker("1.1.0").dry_run().unwrap().is_empty());
+    assert_eq!(checker("1.1.0").check().unwrap().unwrap().latest, "1.1.0");
+    assert_eq!(checker("1.1.0").check().unwrap().unwrap().latest, "1.1.0");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    // Pre-releases are filtered above the source, and each source is
+    // cached under its own key.
+    assert!(checker("2.0.0-rc.1").check().unwrap().is_none());
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    let err = checker("").check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Http);
+
+    let registry = MockRegistry::crates(&[("tool", "1.2.0")]);
+    let crates_io = UpdateChecker::new("tool", "1.0.0")
+        .with_source(CratesIoSource::new("tool").registry_url(registry.url()))
+        .cache_dir(None);
+    assert_eq!(crates_io.check().unwrap().unwrap().latest, "1.2.0");
+}
+
+/// A source that reads the version from a URL, sending its request with
+/// the checker's client.
+struct Fetched(String);
+
+impl VersionSource for Fetched {
+    fn latest(&self, context: &SourceContext<'_>) -> Result<String, Error> {
+        Ok(context.get(&self.0)?.trim().to_string())
+    }
+
+    fn id(&self) -> String {
+        self.0.clone()
+    }
+}
+
+#[test]
+fn version_sources_fetch_with_the_checkers_settings() {
+    let registry = MockRegistry::crates(&[("tool", "1.2.0")]);
+    let text = MockRegistry::serve("1.3.0\n");
+    let url = format!("{}/latest.txt", text.url());
+    let configured = |checker: UpdateChecker| {
+        checker
+            .app_user_agent("my-tool", "1.0.0", None)
+            .header("X-Team", "tools")
+            .cache_dir(None)
+    };
+
+    let crates_io = UpdateChecker::new("tool", "1.0.0")
+        .with_source(CratesIoSource::new("tool").registry_url(registry.url()));
+    assert_eq!(
+        configured(crates_io).check().unwrap().unwrap().latest,
+        "1.2.0"
+    );
+    let fetched = UpdateChecker::new("tool", "1.0.0").with_source(Fetched(url.clone()));
+    assert_eq!(
+        configured(fetched).check().unwrap().unwrap().latest,
+        "1.3.0"
+    );
+    // Built-in sources are version sources too.
+    let built_in = UpdateChecker::new("tool", "1.0.0").with_source(TextSource::new(url));
+    assert_eq!(
+        configured(built_in).check().unwrap().unwrap().latest,
+        "1.3.0"
+    );
+
+    assert_eq!(text.requests().len(), 2);
+    for request in registry.requests().iter().chain(&text.requests()) {
+        let (_, _, headers) = parse_head(request);
+        assert!(headers.contains(&("x-team".to_string(), "tools".to_string())));
+        let (_, agent) = headers
+            .iter()
+            .find(|(name, _)| name == "user-agent")
+            .unwrap();
+        assert!(agent.starts_with("my-tool/1.0.0"), "{agent}");
+    }
+
+    // Requests are refused where the checker's would be.
+    let insecure = UpdateChecker::new("tool", "1.0.0")
+        .with_source(Fetched("http://example.com/latest.txt".to_string()))
+        .cache_dir(None);
+    assert_eq!(insecure.check().unwrap_err().kind(), ErrorKind::Config);
+}
+
+#[test]
+fn fallback_registries_are_tried_in_order() {
+    let down = MockRegistry::serve_status(503, "Service Unavailable");
+    let gone = MockRegistry::serve_status(404, r#"{"errors":[{"detail":"Not Found"}]}"#);
+    let mirror = MockRegistry::crates(&[("tool", "1.2.0")]);
+    // Nothing listens on port 1.
+    let closed = "http://127.0.0.1:1".to_string();
+    let checker = |fallbacks: Vec<String>| {
+        UpdateChecker::new("tool", "1.0.0")
+            .registry_url(down.url())
+            .fallback_registries(fallbacks)
+            .timeout(Duration::from_secs(5))
+            .cache_dir(None)
+    };
+
+    let update = checker(vec![closed.clone(), mirror.url().to_string()])
+        .check_detailed()
+        .unwrap()
+        .unwrap();
+    assert_eq!(update.latest, "1.2.0");
+    assert_eq!(update.registry_url.as_deref(), Some(mirror.url()));
+
+    // Only an unavailable registry is skipped.
+    let err = checker(vec![gone.url().to_string(), mirror.url().to_string()])
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    let err = checker(vec![closed.clone()]).check().unwrap_err();
+    let message = err.to_string();
+    assert!(
+        message.contains(down.url()) && message.contains("503"),
+        "{message}"
+    );
+    assert!(message.contains(&closed), "{message}");
+}
+
+#[test]
+fn fallback_registries_share_the_total_deadline() {
+    let slow = MockRegistry::serve_with(|_| {
+        std::thread::sleep(Duration::from_secs(3));
+        (200, br#"{"crate":{"newest_version":"9.9.9"}}"#.to_vec())
+    });
+    let mirror = MockRegistry::crates(&[("tool", "1.2.0")]);
+    let started = std::time::Instant::now();
+    let err = UpdateChecker::new("tool", "1.0.0")
+        .registry_url(slow.url())
+        .fallback_registries(vec![mirror.url().to_string()])
+        .timeout(Duration::from_secs(1))
+        .total_deadline(Duration::from_secs(1))
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert!(started.elapsed() < Duration::from_secs(3), "{err}");
+    assert!(
+        err.to_string()
+            .contains(&format!("deadline passed before {}", mirror.url())),
+        "{err}"
+    );
+    assert!(mirror.requests().is_empty());
+}
+
+#[test]
+fn dist_manifest_lists_release_artifacts() {
+    let releases = MockRegistry::serve_with(|path| match path {
+        "/releases/latest/download/dist-manifest.json" => (
+            200,
+            include_bytes!("fixtures/dist/schema-0.22.json").to_vec(),
+        ),
+        _ => (404, b"Not Found".to_vec()),
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("axolotlsay", "0.14.0")
+        .dist_manifest(DistManifestSource::new(format!(
+            "{}/releases/latest/download/dist-manifest.json",
+            releases.url()
+        )))
+        .cache_dir(Some(dir.path().to_path_buf()));
+
+    let fetched = checker.check_detailed().unwrap().unwrap();
+    assert_eq!(fetched.latest, "0.14.2");
+    let linux = fetched
+        .artifacts
+        .iter()
+        .find(|artifact| {
+            artifact.kind.as_deref() == Some("executable-zip")
+                && artifact.target_triples == ["x86_64-unknown-linux-gnu"]
+        })
+        .unwrap();
+    assert_eq!(linux.name, "axolotlsay-x86_64-unknown-linux-gnu.tar.xz");
+
+    // The artifacts outlive the response in the cache.
+    let cached = checker.check_detailed().unwrap().unwrap();
+    assert_eq!(cached.artifacts, fetched.artifacts);
+    assert_eq!(releases.requests().len(), 1);
+}
+
+#[test]
+fn oci_tags_follow_the_token_challenge_and_pages() {
+    let registry_url = Arc::new(std::sync::OnceLock::<String>::new());
+    let challenged = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let realm = Arc::clone(&registry_url);
+    let registry = MockRegistry::serve_with_headers(move |path| match path {
+        "/v2/me/tool/tags/list" if !challenged.swap(true, Ordering::SeqCst) => {
+            let challenge = format!(
+                r#"Bearer realm="{}/token",service="mock",scope="repository:me/tool:pull""#,
+                realm.get().unwrap()
+            );
+            (401, vec![("WWW-Authenticate", challenge)], b"{}".to_vec())
+        }
+        "/token?service=mock&scope=repository%3Ame%2Ftool%3Apull" => {
+            (200, vec![], br#"{"token":"anonymous"}"#.to_vec())
+        }
+        "/v2/me/tool/tags/list" => (
+            200,
+            vec![(
+                "Link",
+                r#"</v2/me/tool/tags/list?last=sha-1a2b3c&n=3>; rel="next""#.to_string(),
+            )],
+            br#"{"name":"me/tool","tags":["1.0.0","latest","sha-1a2b3c"]}"#.to_vec(),
+        ),
+        "/v2/me/tool/tags/list?last=sha-1a2b3c&n=3" => (
+            200,
+            vec![],
+            br#"{"name":"me/tool","tags":["v1.2.0","1.1.0","nightly"]}"#.to_vec(),
+        ),
+        _ => (
+            404,
+            vec![],
+            br#"{"errors":[{"code":"NAME_UNKNOWN"}]}"#.to_vec(),
+        ),
+    });
+    registry_url.set(registry.url().to_string()).unwrap();
+    let checker = |repository: &str| {
+        UpdateChecker::new("tool", "1.0.0")
+            .oci_tags(OciSource::new(registry.url(), repository))
+            .cache_dir(None)
+    };
+
+    let tool = checker("me/tool");
+    let plan = tool.dry_run().unwrap();
+    assert_eq!(tool.check().unwrap().unwrap().latest, "1.2.0");
+    let requests = registry.requests();
+    assert_eq!(requests.len(), 4);
+    assert_eq!(
+        planned_head(plan[0].clone(), registry.url()),
+        parse_head(&requests[0])
+    );
+    for page in &requests[2..] {
+        let (_, _, headers) = parse_head(page);
+        assert!(headers.contains(&("authorization".to_string(), "Bearer anonymous".to_string())));
+    }
+
+    let err = checker("me/gone").check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains("me/gone"), "{err}");
+    let err = checker("Me/Tool").check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Config);
+}
+
+/// `git ls-remote` output, as served to `git` over the dumb HTTP protocol.
+const TOOL_REFS: &str = "\
+1111111111111111111111111111111111111111\trefs/tags/v1.9.0
+2222222222222222222222222222222222222222\trefs/tags/v2.0.0
+3333333333333333333333333333333333333333\trefs/tags/v2.0.0^{}
+4444444444444444444444444444444444444444\trefs/tags/nightly
+5555555555555555555555555555555555555555\trefs/tags/plugin-v3.1.0
+";
+
+#[test]
+fn git_tags_are_an_alternative_source() {
+    let forge = MockRegistry::serve_with(|path| match path {
+        "/me/tool.git/info/refs?service=git-upload-pack" => (200, TOOL_REFS.into()),
+        "/api/v1/repos/me/tool/tags" => (
+            200,
+            br#"[{"name":"v2.0.0"},{"name":"v2.1.0-rc.1"},{"name":"latest"}]"#.to_vec(),
+        ),
+        _ => (404, b"Not Found".to_vec()),
+    });
+    let checker = |source: GitTagsSource| {
+        UpdateChecker::new("tool", "1.9.0")
+            .git_tags(source)
+            .cache_dir(None)
+    };
+
+    let endpoint = checker(GitTagsSource::endpoint(format!(
+        "{}/api/v1/repos/me/tool/tags",
+        forge.url()
+    )))
+    .include_prerelease(true);
+    let plan = endpoint.dry_run().unwrap();
+    assert_eq!(endpoint.check().unwrap().unwrap().latest, "2.1.0-rc.1");
+    assert_eq!(
+        planned_head(plan[0].clone(), forge.url()),
+        parse_head(&forge.requests()[0])
+    );
+
+    // Rejected before running git, which would accept it as an option.
+    let err = checker(GitTagsSource::remote("--upload-pack=touch pwned"))
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Config);
+
+    if std::process::Command::new("git")
+        .arg("--version")
+        .output()
+        .is_err()
+    {
+        eprintln!("git not on PATH; skipping ls-remote");
+        return;
+    }
+    let remote = format!("{}/me/tool.git", forge.url());
+    let tool = checker(GitTagsSource::remote(&remote));
+    assert!(tool.dry_run().unwrap().is_empty());
+    assert_eq!(tool.check().unwrap().unwrap().latest, "2.0.0");
+    let plugin = checker(GitTagsSource::remote(&remote).tag_prefix("plugin-v"));
+    assert_eq!(plugin.check().unwrap().unwrap().latest, "3.1.0");
+
+    let err = checker(GitTagsSource::remote(&remote).tag_prefix("docs-v"))
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains("'docs-v'"), "{err}");
+    let err = checker(GitTagsSource::remote(format!("{}/gone.git", forge.url())))
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound, "{err}");
+}
+
+#[test]
+fn exhausted_github_rate_limit_reports_reset_time() {
+    let github = MockRegistry::serve_with_headers(|_| {
+        let headers = vec![
+            ("X-RateLimit-Remaining", "0".to_string()),
+            ("X-RateLimit-Reset", "1760000000".to_string()),
+        ];
+        (
+            403,
+            headers,
+            br#"{"message":"API rate limit exceeded"}"#.to_vec(),
+        )
+    });
+    let err = UpdateChecker::new("tool", "2.0.0")
+        .github_releases(
+            GitHubSource::new("octo", "tool")
+                .api_url(github.url())
+                .token("ghp_s3cret"),
+        )
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::RateLimited);
+    assert_eq!(
+        err.rate_limit_reset(),
+        Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_760_000_000))
+    );
+    assert!(err.to_string().contains("1760000000"), "{err}");
+    assert!(!format!("{err} {err:?}").contains("ghp_s3cret"));
+
+    // A 403 with quota left is refused access.
+    let forbidden = MockRegistry::serve_with_headers(|_| {
+        let headers = vec![("X-RateLimit-Remaining", "59".to_string())];
+        (403, headers, b"{}".to_vec())
+    });
+    let err = UpdateChecker::new("tool", "2.0.0")
+        .github_releases(GitHubSource::new("octo", "tool").api_url(forbidden.url()))
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unauthorized);
+    assert_eq!(err.rate_limit_reset(), None);
+}
+
+#[test]
+fn transient_failures_are_retried() {
+    let body = br#"{"crate":{"newest_version":"2.0.0"}}"#;
+    let checker = |registry: &MockRegistry, retries| {
+        UpdateChecker::new("tool", "1.0.0")
+            .registry_url(registry.url())
+            .retries(retries)
+            .total_deadline(Duration::from_secs(5))
+            .cache_dir(None)
+    };
+
+    let registry = MockRegistry::flaky(2, body.to_vec());
+    let update = checker(&registry, 2).check().unwrap().unwrap();
+    assert_eq!(update.latest, "2.0.0");
+    assert_eq!(registry.requests().len(), 3);
+
+    let registry = MockRegistry::flaky(2, body.to_vec());
+    let err = checker(&registry, 1).check().unwrap_err();
+    assert_eq!(err.attempts(), Some(2));
+    assert!(
+        err.to_string().contains("gave up after 2 attempts"),
+        "{err}"
+    );
+    assert_eq!(registry.requests().len(), 2);
+
+    // Without retries, the error is unchanged.
+    let registry = MockRegistry::flaky(1, body.to_vec());
+    let err = checker(&registry, 0).check().unwrap_err();
+    assert_eq!(err.attempts(), None);
+
+    // Neither a 4xx status nor an unparsable answer is retried.
+    for registry in [
+        MockRegistry::serve_status(404, ""),
+        MockRegistry::serve_status(429, ""),
+        MockRegistry::serve("not json"),
+    ] {
+        let err = checker(&registry, 3).check().unwrap_err();
+        assert_eq!(err.attempts(), None, "{err}");
+        assert_eq!(registry.requests().len(), 1);
+    }
+}
+
+#[test]
+fn retries_stop_at_the_deadline() {
+    let registry = MockRegistry::serve_status(503, "");
+    let started = std::time::Instant::now();
+    let err = UpdateChecker::new("tool", "1.0.0")
+        .registry_url(registry.url())
+        .retries(10)
+        .total_deadline(Duration::from_millis(400))
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert!(started.elapsed() < Duration::from_millis(800));
+    let attempts = registry.requests().len();
+    assert!((2..10).contains(&attempts), "{attempts}");
+    assert_eq!(err.attempts(), u32::try_from(attempts).ok());
+}
+
+#[test]
+fn auth_tokens_are_sent_verbatim_to_the_registry() {
+    let token = "cio_s3cret-token";
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let checker = UpdateChecker::new("tool", "1.0.0")
+        .registry_url(registry.url())
+        .auth_token(token)
+        .cache_dir(None);
+    assert!(checker.check().unwrap().is_some());
+    let (_, _, headers) = parse_head(&registry.requests()[0]);
+    assert!(headers.contains(&("authorization".to_string(), token.to_string())));
+    assert!(!format!("{checker:?}").contains(token));
+
+    for status in [401, 403] {
+        let registry = MockRegistry::serve_status(status, "");
+        let err = UpdateChecker::new("tool", "1.0.0")
+            .registry_url(registry.url())
+            .auth_token(token)
+            .cache_dir(None)
+            .check()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unauthorized, "{status}");
+        assert!(!format!("{err} {err:?}").contains(token), "{err}");
+    }
+}
+
+#[test]
+fn basic_auth_is_encoded_and_redacted() {
+    let password = "p@ss:wörd/+=";
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let checker = UpdateChecker::new("tool", "1.0.0")
+        .registry_url(registry.url())
+        .basic_auth("deploy", password)
+        .cache_dir(None);
+    assert!(checker.check().unwrap().is_some());
+    let (_, _, headers) = parse_head(&registry.requests()[0]);
+    assert!(headers.contains(&(
+        "authorization".to_string(),
+        "Basic ZGVwbG95OnBAc3M6d8O2cmQvKz0=".to_string()
+    )));
+    assert!(!format!("{checker:?}").contains("ZGVwbG95"));
+
+    let refused = MockRegistry::serve_status(401, "");
+    let err = UpdateChecker::new("tool", "1.0.0")
+        .registry_url(refused.url())
+        .basic_auth("deploy", password)
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unauthorized);
+    let message = err.to_string();
+    assert!(
+        message.contains("registry 127.0.0.1 requires valid credentials"),
+        "{message}"
+    );
+    assert!(
+        !format!("{message} {err:?}").contains("ZGVwbG95"),
+        "{message}"
+    );
+    assert!(!message.contains("deploy"), "{message}");
+}
+
+#[test]
+fn app_user_agent_precedes_the_crate_identifier() {
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let checker = |contact| {
+        UpdateChecker::new("tool", "1.0.0")
+            .registry_url(registry.url())
+            .app_user_agent("mytool", "2.1.0", contact)
+            .cache_dir(None)
+    };
+    let crate_agent = concat!("tiny-update-check/", env!("CARGO_PKG_VERSION"));
+
+    checker(Some("me@example.com")).check().unwrap();
+    checker(None).check().unwrap();
+    let agents: Vec<_> = registry
+        .requests()
+        .iter()
+        .map(|request| {
+            let (_, _, headers) = parse_head(request);
+            headers
+                .into_iter()
+                .find(|(name, _)| name == "user-agent")
+                .unwrap()
+                .1
+        })
+        .collect();
+    assert_eq!(
+        agents,
+        [
+            format!("mytool/2.1.0 (me@example.com) {crate_agent}"),
+            format!("mytool/2.1.0 {crate_agent}"),
+        ]
+    );
+    let planned = checker(None).dry_run().unwrap();
+    assert!(
+        planned[0]
+            .headers
+            .contains(&("User-Agent", format!("mytool/2.1.0 {crate_agent}")))
+    );
+
+    for contact in ["me@example.com\r\nX-Injected: 1", "José"] {
+        let err = checker(Some(contact)).check().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Config, "{contact}");
+    }
+    assert_eq!(registry.requests().len(), 2);
+}
+
+#[test]
+fn custom_headers_are_sent_with_every_request() {
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let checker = || {
+        UpdateChecker::new("tool", "1.0.0")
+            .registry_url(registry.url())
+            .header("X-Org-Team", "platform")
+            .header("X-Audit-Id", "42")
+            .cache_dir(None)
+    };
+    assert!(checker().check().unwrap().is_some());
+    let (_, _, headers) = parse_head(&registry.requests()[0]);
+    for header in [("x-audit-id", "42"), ("x-org-team", "platform")] {
+        assert!(
+            headers.contains(&(header.0.to_string(), header.1.to_string())),
+            "{headers:?}"
+        );
+    }
+
+    let planned = checker().dry_run().unwrap();
+    assert_eq!(
+        planned[0].custom_headers,
+        [
+            ("X-Org-Team".to_string(), "platform".to_string()),
+            ("X-Audit-Id".to_string(), "42".to_string()),
+        ]
+    );
+
+    // Replacing the crate's own `User-Agent` or `Authorization` needs an
+    // explicit opt-in, and illegal headers are refused before any request.
+    for (name, value) in [
+        ("user-agent", "bot/1.0"),
+        ("Authorization", "Bearer s3cret"),
+        ("X Bad", "1"),
+        ("X-Split", "a\r\nX-Injected: 1"),
+        ("Host", "example.com"),
+    ] {
+        let err = checker().header(name, value).check().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Config, "{name}");
+        assert!(!err.to_string().contains("s3cret"), "{err}");
+    }
+    assert_eq!(registry.requests().len(), 1);
+
+    let checker = checker()
+        .header("Authorization", "Bearer s3cret")
+        .header("User-Agent", "bot/1.0")
+        .allow_header_overrides(true)
+        .auth_token("ignored");
+    assert!(!format!("{checker:?}").contains("s3cret"));
+    checker.check().unwrap();
+    let (_, _, headers) = parse_head(&registry.requests()[1]);
+    let sent = |name: &str| {
+        headers
+            .iter()
+            .filter(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(sent("authorization"), ["Bearer s3cret"]);
+    assert_eq!(sent("user-agent"), ["bot/1.0"]);
+}
+
+#[test]
+fn rate_limits_are_waited_out_across_runs() {
+    use tiny_update_check::{CheckOutcome, NoUpdateReason};
+
+    let dir = tempfile::tempdir().unwrap();
+    let retry_after = Arc::new(std::sync::Mutex::new(Some("120".to_string())));
+    let header = Arc::clone(&retry_after);
+    let registry = MockRegistry::serve_with_headers(move |_| {
+        let headers = header
+            .lock()
+            .unwrap()
+            .clone()
+            .map(|value| vec![("Retry-After", value)])
+            .unwrap_or_default();
+        (429, headers, Vec::new())
+    });
+    let checker = || {
+        UpdateChecker::new("tool", "1.0.0")
+            .registry_url(registry.url())
+            .cache_dir(Some(dir.path().to_path_buf()))
+    };
+    let backoff = dir.path().join("tool-update-check.retry-after");
+    let recorded = || -> u64 { std::fs::read_to_string(&backoff).unwrap().parse().unwrap() };
+    let now = || {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    };
+
+    let err = checker().check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::RateLimited);
+    assert_eq!(err.status(), Some(429));
+    let until = err.rate_limit_reset().unwrap();
+    assert!(until > SystemTime::now() + Duration::from_secs(100));
+    assert!(recorded().abs_diff(now() + 120) <= 2);
+
+    // The next run doesn't ask again, with nothing cached to answer from.
+    match checker().check_outcome() {
+        CheckOutcome::NoUpdate(NoUpdateReason::RateLimited { until: waiting }) => {
+            assert!(waiting.duration_since(until).unwrap_or_default() <= Duration::from_secs(1));
+        }
+        outcome => panic!("{outcome:?}"),
+    }
+    assert!(checker().check().unwrap().is_none());
+
+    // A cache, however old, answers while the wait lasts, even when forced.
+    let week_ago = SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60);
+    checker().seed_cache("2.0.0", week_ago).unwrap();
+    let update = checker().mode(CheckMode::Forced).check().unwrap().unwrap();
+    assert_eq!(update.latest, "2.0.0");
+    assert_eq!(registry.requests().len(), 1);
+
+    // Once the time passes, the registry is asked again. An HTTP date is
+    // honored, but never beyond a day; without a header, the wait is an hour.
+    std::fs::write(&backoff, (now() - 1).to_string()).unwrap();
+    *retry_after.lock().unwrap() = Some("Fri, 31 Dec 9999 23:59:59 GMT".to_string());
+    assert_eq!(
+        checker().check().unwrap_err().kind(),
+        ErrorKind::RateLimited
+    );
+    assert!(recorded().abs_diff(now() + 24 * 60 * 60) <= 2);
+
+    std::fs::write(&backoff, "0").unwrap();
+    *retry_after.lock().unwrap() = None;
+    assert!(checker().check().is_err());
+    assert!(recorded().abs_diff(now() + 60 * 60) <= 2);
+    assert_eq!(registry.requests().len(), 3);
+}
+
+#[cfg(all(feature = "gzip", feature = "rustls"))]
+#[test]
+fn gzip_responses_are_decoded() {
+    let registry = MockRegistry::serve_with_headers(|_| {
+        (
+            200,
+            vec![("Content-Encoding", "gzip".to_string())],
+            include_bytes!("fixtures/serde_response.json.gz").to_vec(),
+        )
+    });
     let checker = UpdateChecker::new("serde", "1.0.0")
-        .cache_duration(Duration::from_secs(3600))
-        .timeout(Duration::from_secs(10))
+        .registry_url(registry.url())
         .cache_dir(None);
+    assert_eq!(checker.check().unwrap().unwrap().latest, "1.0.228");
+    let (_, _, headers) = parse_head(&registry.requests()[0]);
+    assert!(headers.contains(&("accept-encoding".to_string(), "gzip".to_string())));
+
+    // A body that isn't the gzip it claims to be fails clearly.
+    let liar = MockRegistry::serve_with_headers(|_| {
+        (
+            200,
+            vec![("Content-Encoding", "gzip".to_string())],
+            br#"{"crate":{"newest_version":"2.0.0"}}"#.to_vec(),
+        )
+    });
+    let err = UpdateChecker::new("serde", "1.0.0")
+        .registry_url(liar.url())
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Http);
+    assert!(err.to_string().contains("couldn't be decoded"), "{err}");
+}
+
+#[test]
+fn server_errors_fall_back_to_a_stale_cache() {
+    use tiny_update_check::CheckEvent;
+
+    let dir = tempfile::tempdir().unwrap();
+    let registry = MockRegistry::serve_status(503, "Service Unavailable");
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let seen = Arc::clone(&events);
+    let checker = UpdateChecker::new("tool", "1.0.0")
+        .registry_url(registry.url())
+        .cache_duration(Duration::from_secs(60 * 60))
+        .cache_dir(Some(dir.path().to_path_buf()))
+        .on_event(move |event| seen.lock().unwrap().push(event.clone()));
+
+    // Nothing cached: the server error stands.
+    let err = checker.check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ServerError);
+
+    let week_ago = SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60);
+    checker.seed_cache("1.2.0", week_ago).unwrap();
+    let update = checker.check_detailed().unwrap().unwrap();
+    assert_eq!(update.latest, "1.2.0");
+    assert!(update.stale);
+    assert!(events.lock().unwrap().contains(&CheckEvent::StaleCacheHit {
+        version: "1.2.0".to_string(),
+        status: Some(503),
+    }));
+    assert_eq!(registry.requests().len(), 2);
+
+    // The stale answer is still compared: nothing newer is no update.
+    assert!(
+        UpdateChecker::new("tool", "1.2.0")
+            .registry_url(registry.url())
+            .cache_dir(Some(dir.path().to_path_buf()))
+            .check()
+            .unwrap()
+            .is_none()
+    );
+
+    // Strict checks fail, as do other errors.
+    let err = checker
+        .clone()
+        .stale_on_server_error(false)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ServerError);
+    let gone = MockRegistry::serve_status(404, "");
+    let err = checker.registry_url(gone.url()).check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+/// A registry answering with `2.0.0` and the given `Cache-Control` header.
+fn cache_controlled(cache_control: &'static str) -> MockRegistry {
+    MockRegistry::serve_with_headers(move |_| {
+        (
+            200,
+            vec![("Cache-Control", cache_control.to_string())],
+            br#"{"crate":{"newest_version":"2.0.0"}}"#.to_vec(),
+        )
+    })
+}
+
+#[test]
+fn a_shorter_server_max_age_expires_the_cache_early() {
+    let registry = cache_controlled("public, max-age=0");
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()));
+
+    // Ignored unless asked for.
+    for _ in 0..2 {
+        assert_eq!(checker.check().unwrap().unwrap().latest, "2.0.0");
+    }
+    assert_eq!(registry.requests().len(), 1);
+
+    let honoring = checker.honor_cache_control(true);
+    for _ in 0..2 {
+        assert_eq!(honoring.check().unwrap().unwrap().latest, "2.0.0");
+    }
+    assert_eq!(registry.requests().len(), 3);
+
+    // The same holds for checks that keep their cache in memory.
+    let ephemeral = honoring.ephemeral(true);
+    for _ in 0..2 {
+        ephemeral.check().unwrap();
+    }
+    assert_eq!(registry.requests().len(), 5);
+}
+
+#[test]
+fn a_longer_server_max_age_does_not_extend_the_cache() {
+    let registry = cache_controlled("max-age=86400");
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()))
+        .cache_duration(Duration::from_secs(1))
+        .honor_cache_control(true);
 
-    assert!(checker.check_detailed().is_ok() || checker.check_detailed().is_err());
+    checker.check().unwrap();
+    checker.check().unwrap();
+    assert_eq!(registry.requests().len(), 1);
+    std::thread::sleep(Duration::from_millis(1100));
+    checker.check().unwrap();
+    assert_eq!(registry.requests().len(), 2);
 }
 
 #[test]
-fn test_convenience_function() {
-    // Test the convenience function compiles
-    let result = tiny_update_check::check("serde", "1.0.0");
-    // Either succeeds or fails - we just test the API works
-    assert!(result.is_ok() || result.is_err());
+fn an_unusable_cache_control_falls_back_to_the_cache_duration() {
+    for cache_control in ["no-cache", "max-age=soon", "max-age=-1"] {
+        let registry = cache_controlled(cache_control);
+        let dir = tempfile::tempdir().unwrap();
+        let checker = UpdateChecker::new("test", "1.0.0")
+            .registry_url(registry.url())
+            .cache_dir(Some(dir.path().to_path_buf()))
+            .honor_cache_control(true);
+        for _ in 0..2 {
+            checker.check().unwrap();
+        }
+        assert_eq!(registry.requests().len(), 1, "{cache_control}");
+    }
 }
@@ -0,0 +1,57 @@
+//! The memory a check uses on the largest captured response.
+//!
+//! Measured from the process's peak resident set size rather than a counting
+//! allocator, since the crate forbids the `unsafe` a custom allocator needs.
+//! This is the only test in its binary, so nothing else moves the peak while
+//! it runs.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::MockRegistry;
+use tiny_update_check::{ErrorKind, SuggestStrategy, UpdateChecker};
+
+/// The committed bound on how far a check of the `serde` fixture may raise
+/// the peak. A regression that parses the response more than once, or holds
+/// extra copies of it, shows up here.
+const PEAK_BOUND: usize = 12 * 1024 * 1024;
+
+/// The process's peak resident set size in bytes, from `/proc/self/status`.
+fn peak_rss() -> usize {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap();
+    let kib: usize = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|rest| rest.trim().strip_suffix("kB"))
+        .and_then(|kib| kib.trim().parse().ok())
+        .expect("VmHWM should be reported");
+    kib * 1024
+}
+
+#[test]
+fn largest_fixture_stays_within_committed_peak() {
+    let fixture = include_bytes!("fixtures/serde_response.json");
+    let registry = MockRegistry::serve(fixture.as_slice());
+    let checker = UpdateChecker::new("serde", "1.0.100")
+        .registry_url(registry.url())
+        .cache_dir(None)
+        .suggest(SuggestStrategy::SafestFirst);
+
+    let before = peak_rss();
+    let update = checker.clone().max_memory(PEAK_BOUND).check_detailed();
+    let growth = peak_rss().saturating_sub(before);
+
+    assert!(update.unwrap().is_some());
+    assert!(
+        growth <= PEAK_BOUND,
+        "check raised the peak by {growth} bytes"
+    );
+
+    // The body may take a sixteenth of the limit; one byte short fails.
+    let err = checker
+        .max_memory(fixture.len() * 16 - 1)
+        .check_detailed()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TooLarge);
+}
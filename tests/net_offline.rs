@@ -0,0 +1,260 @@
+//! Offline checks, which stay off the network: explicitly offline ones, ones
+//! run while `CARGO_NET_OFFLINE` or `TINY_UPDATE_CHECK_OFFLINE` is set, and
+//! ones whose offline probe finds no network.
+//!
+//! Kept in its own test binary because it sets those variables; checks read
+//! them as they run.
+
+mod common;
+
+use common::MockRegistry;
+use std::time::{Duration, SystemTime};
+use tiny_update_check::{CheckOutcome, NoUpdateReason, UpdateChecker};
+
+const NET_OFFLINE_VAR: &str = "CARGO_NET_OFFLINE";
+const OFFLINE_VAR: &str = "TINY_UPDATE_CHECK_OFFLINE";
+
+/// A registry with `test` at 2.0.0.
+fn registry() -> MockRegistry {
+    MockRegistry::crates(&[("test", "2.0.0")])
+}
+
+/// A checker for `test` 1.0.0 against `registry`, caching in `dir`.
+fn checker(registry: &MockRegistry, dir: &tempfile::TempDir) -> UpdateChecker {
+    UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()))
+}
+
+#[test]
+fn offline_checks_answer_from_the_cache_however_old() {
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let checker = checker(&registry, &dir);
+    let week = Duration::from_secs(7 * 24 * 60 * 60);
+    checker
+        .seed_cache("1.5.0", SystemTime::now() - week)
+        .unwrap();
+
+    for value in ["true", "1", "TRUE"] {
+        temp_env::with_var(NET_OFFLINE_VAR, Some(value), || {
+            assert_eq!(checker.check().unwrap().unwrap().latest, "1.5.0", "{value}");
+        });
+    }
+    assert!(registry.requests().is_empty());
+}
+
+#[test]
+fn offline_checks_without_a_cache_find_no_update() {
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let checker = checker(&registry, &dir);
+
+    temp_env::with_var(NET_OFFLINE_VAR, Some("true"), || {
+        assert_eq!(checker.check().unwrap(), None);
+        assert!(matches!(
+            checker.check_outcome(),
+            CheckOutcome::NoUpdate(NoUpdateReason::Offline)
+        ));
+        // Nothing listens on port 1, but nothing is asked either.
+        let unreachable = UpdateChecker::new("test", "1.0.0")
+            .registry_url("http://127.0.0.1:1")
+            .cache_dir(None);
+        assert_eq!(unreachable.check().unwrap(), None);
+    });
+    assert!(registry.requests().is_empty());
+}
+
+#[test]
+fn other_values_leave_checks_online() {
+    let registry = registry();
+    for value in [None, Some("false"), Some("0"), Some("")] {
+        let dir = tempfile::tempdir().unwrap();
+        let checker = checker(&registry, &dir);
+        temp_env::with_var(NET_OFFLINE_VAR, value, || {
+            assert_eq!(
+                checker.check().unwrap().unwrap().latest,
+                "2.0.0",
+                "{value:?}"
+            );
+        });
+    }
+    assert_eq!(registry.requests().len(), 4);
+}
+
+#[test]
+fn the_variable_can_be_ignored() {
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let checker = checker(&registry, &dir).ignore_cargo_net_offline(true);
+
+    temp_env::with_var(NET_OFFLINE_VAR, Some("true"), || {
+        assert_eq!(checker.check().unwrap().unwrap().latest, "2.0.0");
+    });
+    assert_eq!(registry.requests().len(), 1);
+}
+
+#[test]
+fn offline_checks_compare_against_a_fresh_cache() {
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let checker = checker(&registry, &dir).offline(true);
+    checker.seed_cache("1.5.0", SystemTime::now()).unwrap();
+
+    let update = checker.check_detailed().unwrap().unwrap();
+    assert_eq!(update.latest, "1.5.0");
+    assert!(update.offline);
+    assert!(!update.stale);
+    assert!(registry.requests().is_empty());
+}
+
+#[test]
+fn offline_checks_compare_against_a_stale_cache() {
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let checker = checker(&registry, &dir).offline(true);
+    let year = Duration::from_secs(365 * 24 * 60 * 60);
+    checker
+        .seed_cache("1.5.0", SystemTime::now() - year)
+        .unwrap();
+
+    match checker.check_outcome() {
+        CheckOutcome::Update(update) => {
+            assert_eq!(update.latest, "1.5.0");
+            assert!(update.offline);
+        }
+        outcome => panic!("expected an update, got {outcome:?}"),
+    }
+    assert!(registry.requests().is_empty());
+}
+
+#[test]
+fn offline_checks_without_a_cache_conclude_offline() {
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let checker = checker(&registry, &dir).offline(true);
+
+    assert_eq!(checker.check().unwrap(), None);
+    assert!(matches!(
+        checker.check_outcome(),
+        CheckOutcome::NoUpdate(NoUpdateReason::Offline)
+    ));
+    assert!(registry.requests().is_empty());
+}
+
+#[test]
+fn online_answers_are_not_marked_offline() {
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let checker = checker(&registry, &dir);
+
+    assert!(!checker.check_detailed().unwrap().unwrap().offline);
+    // The same answer, now cached.
+    assert!(!checker.check_detailed().unwrap().unwrap().offline);
+    assert_eq!(registry.requests().len(), 1);
+}
+
+#[test]
+fn the_environment_can_take_checks_offline() {
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let checker = checker(&registry, &dir).ignore_cargo_net_offline(true);
+
+    temp_env::with_var(OFFLINE_VAR, Some("1"), || {
+        assert_eq!(checker.check().unwrap(), None);
+    });
+    assert!(registry.requests().is_empty());
+
+    temp_env::with_var(OFFLINE_VAR, Some("0"), || {
+        assert_eq!(checker.check().unwrap().unwrap().latest, "2.0.0");
+    });
+    assert_eq!(registry.requests().len(), 1);
+}
+
+#[cfg(feature = "async")]
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_checks_stay_offline_unless_told_otherwise() {
+    use tiny_update_check::r#async::UpdateChecker;
+
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()));
+
+    temp_env::with_var(NET_OFFLINE_VAR, Some("1"), || {
+        assert_eq!(block_on(checker.check()).unwrap(), None);
+        assert!(registry.requests().is_empty());
+
+        let online = checker.clone().ignore_cargo_net_offline(true);
+        assert_eq!(block_on(online.check()).unwrap().unwrap().latest, "2.0.0");
+        // The answer is cached now, and served offline however old.
+        let stale = checker.clone().cache_duration(Duration::from_nanos(1));
+        assert_eq!(block_on(stale.check()).unwrap().unwrap().latest, "2.0.0");
+    });
+    assert_eq!(registry.requests().len(), 1);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_failed_offline_probes_fall_back_to_the_cache() {
+    use tiny_update_check::r#async::UpdateChecker;
+
+    let proxies = ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"];
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url("https://registry.invalid")
+        .cache_dir(Some(dir.path().to_path_buf()))
+        .fast_offline_probe(true);
+
+    temp_env::with_vars_unset(proxies, || {
+        assert!(matches!(
+            block_on(checker.check_outcome()),
+            CheckOutcome::NoUpdate(NoUpdateReason::Offline)
+        ));
+
+        let week = Duration::from_secs(7 * 24 * 60 * 60);
+        checker
+            .seed_cache("2.0.0", SystemTime::now() - week)
+            .unwrap();
+        let update = block_on(checker.check_detailed()).unwrap().unwrap();
+        assert_eq!(update.latest, "2.0.0");
+        assert!(update.stale);
+    });
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_offline_checks_answer_from_the_cache_however_old() {
+    use tiny_update_check::r#async::UpdateChecker;
+
+    let registry = registry();
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()))
+        .offline(true);
+
+    assert!(matches!(
+        block_on(checker.check_outcome()),
+        CheckOutcome::NoUpdate(NoUpdateReason::Offline)
+    ));
+
+    let week = Duration::from_secs(7 * 24 * 60 * 60);
+    checker
+        .seed_cache("1.5.0", SystemTime::now() - week)
+        .unwrap();
+    let update = block_on(checker.check_detailed()).unwrap().unwrap();
+    assert_eq!(update.latest, "1.5.0");
+    assert!(update.offline);
+    assert!(registry.requests().is_empty());
+}
@@ -0,0 +1,106 @@
+//! Registry credentials from a `.netrc` file.
+//!
+//! Kept in its own test binary because it sets `NETRC`, which would
+//! otherwise leak into concurrently running tests.
+
+mod common;
+
+use common::{MockRegistry, parse_head};
+use std::fs;
+use std::path::Path;
+use tiny_update_check::{ErrorKind, UpdateChecker};
+
+/// Write `netrc` to `path`, readable only by its owner.
+fn write_netrc(path: &Path, netrc: &str) {
+    fs::write(path, netrc).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600)).unwrap();
+    }
+}
+
+#[test]
+fn netrc_credentials_authenticate_the_registry_host() {
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("netrc");
+    let checker = || {
+        UpdateChecker::new("tool", "1.0.0")
+            .registry_url(registry.url())
+            .use_netrc(true)
+            .cache_dir(None)
+    };
+    let sent_token = || {
+        let (_, _, headers) = parse_head(registry.requests().last().unwrap());
+        headers
+            .into_iter()
+            .find(|(name, _)| name == "authorization")
+            .map(|(_, value)| value)
+    };
+
+    temp_env::with_var("NETRC", Some(&path), || {
+        // No file: the check runs without credentials.
+        assert!(checker().check().unwrap().is_some());
+        assert_eq!(sent_token(), None);
+
+        write_netrc(
+            &path,
+            "machine example.com login other password x\n\
+             machine 127.0.0.1\n  login deploy\n  password p@ss:w0rd\n",
+        );
+        checker().check().unwrap();
+        assert_eq!(
+            sent_token().as_deref(),
+            Some("Basic ZGVwbG95OnBAc3M6dzByZA==")
+        );
+        checker().use_netrc(false).check().unwrap();
+        assert_eq!(sent_token(), None);
+        checker().auth_token("explicit").check().unwrap();
+        assert_eq!(sent_token().as_deref(), Some("explicit"));
+
+        // `default` covers a host without its own entry.
+        write_netrc(&path, "default login anonymous password guest");
+        checker().check().unwrap();
+        assert_eq!(sent_token().as_deref(), Some("Basic YW5vbnltb3VzOmd1ZXN0"));
+
+        // crates.io is never sent credentials.
+        let planned = UpdateChecker::new("tool", "1.0.0")
+            .use_netrc(true)
+            .dry_run()
+            .unwrap();
+        assert!(
+            planned[0]
+                .headers
+                .iter()
+                .all(|(name, _)| !name.eq_ignore_ascii_case("authorization"))
+        );
+    });
+}
+
+#[cfg(unix)]
+#[test]
+fn world_readable_netrc_is_refused() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("netrc");
+    fs::write(&path, "machine 127.0.0.1 login deploy password s3cret").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+    temp_env::with_var("NETRC", Some(&path), || {
+        let err = UpdateChecker::new("tool", "1.0.0")
+            .registry_url(registry.url())
+            .use_netrc(true)
+            .cache_dir(None)
+            .check()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Config);
+        let message = err.to_string();
+        assert!(message.contains("readable by every user"), "{message}");
+        assert!(message.contains("chmod 600"), "{message}");
+        assert!(!message.contains("s3cret"), "{message}");
+        assert!(registry.requests().is_empty());
+    });
+}
@@ -0,0 +1,162 @@
+//! Repository-local policy from `.tiny-update-check.toml`.
+//!
+//! Kept in its own test binary because it sets `CI` and `DO_NOT_TRACK`,
+//! which would otherwise leak into concurrently running tests.
+
+mod common;
+
+use common::MockRegistry;
+use std::fs;
+use std::path::Path;
+use tiny_update_check::{CheckMode, CheckOutcome, ErrorKind, NoUpdateReason, UpdateChecker};
+
+/// A checker on 1.0.0 against a registry whose latest is 2.0.0, applying
+/// the project policy found from `dir`.
+fn checker(registry: &MockRegistry, dir: &Path) -> UpdateChecker {
+    UpdateChecker::new("tool", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(None)
+        .project_config(true)
+        .project_dir(dir)
+}
+
+/// Run `f` outside CI with `DO_NOT_TRACK` unset, unless `env` says otherwise.
+fn with_env<R>(env: &[(&str, &str)], f: impl FnOnce() -> R) -> R {
+    let value = |key| env.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+    temp_env::with_vars(
+        [("CI", value("CI")), ("DO_NOT_TRACK", value("DO_NOT_TRACK"))],
+        f,
+    )
+}
+
+fn reason(outcome: CheckOutcome) -> NoUpdateReason {
+    match outcome {
+        CheckOutcome::NoUpdate(reason) => reason,
+        other => panic!("expected no update, got {other:?}"),
+    }
+}
+
+#[test]
+fn nearest_project_file_applies() {
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let root = tempfile::tempdir().unwrap();
+    let nested = root.path().join("crates/app/src");
+    fs::create_dir_all(&nested).unwrap();
+
+    // No file anywhere: the builder's settings stand.
+    with_env(&[], || {
+        assert!(checker(&registry, &nested).check().unwrap().is_some());
+    });
+
+    let root_file = root.path().join(".tiny-update-check.toml");
+    fs::write(&root_file, "# Stay on 1.x\nversion_req = \"1.x\"\n").unwrap();
+    let outcome = with_env(&[], || checker(&registry, &nested).check_outcome());
+    assert_eq!(
+        reason(outcome),
+        NoUpdateReason::OutsideVersionReq {
+            latest: "2.0.0".to_string(),
+            path: root_file,
+        }
+    );
+
+    // A file nearer the starting directory wins over one further up.
+    let app_file = root.path().join("crates/app/.tiny-update-check.toml");
+    fs::write(&app_file, "version_req = \"<3\"  # 2.x is fine here\n").unwrap();
+    with_env(&[], || {
+        assert!(checker(&registry, &nested).check().unwrap().is_some());
+    });
+
+    // Discovery is opt-in.
+    fs::write(&app_file, "disable = true\n").unwrap();
+    with_env(&[], || {
+        let update = checker(&registry, &nested).project_config(false).check();
+        assert!(update.unwrap().is_some());
+    });
+}
+
+#[test]
+fn project_file_overrides_builder_but_not_do_not_track() {
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join(".tiny-update-check.toml");
+
+    // The project disables checks even when the builder forces them.
+    fs::write(&file, "disable = true\n").unwrap();
+    let outcome = with_env(&[], || {
+        checker(&registry, dir.path())
+            .mode(CheckMode::Forced)
+            .check_outcome()
+    });
+    assert_eq!(
+        reason(outcome),
+        NoUpdateReason::ProjectDisabled { path: file.clone() }
+    );
+    assert!(registry.requests().is_empty());
+
+    // The project can't re-enable checks the environment opts out of.
+    fs::write(&file, "disable = false\n").unwrap();
+    if cfg!(feature = "do-not-track") {
+        let outcome = with_env(&[("DO_NOT_TRACK", "1")], || {
+            checker(&registry, dir.path()).check_outcome()
+        });
+        assert_eq!(reason(outcome), NoUpdateReason::DoNotTrack);
+    }
+
+    // A custom file name is looked for instead of the default.
+    let outcome = with_env(&[], || {
+        fs::write(dir.path().join("policy.toml"), "disable = true\n").unwrap();
+        checker(&registry, dir.path())
+            .project_config_file("policy.toml")
+            .check_outcome()
+    });
+    assert!(matches!(
+        reason(outcome),
+        NoUpdateReason::ProjectDisabled { .. }
+    ));
+}
+
+#[test]
+fn outside_ci_policy_follows_the_ci_variable() {
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join(".tiny-update-check.toml");
+    fs::write(&file, "notify_policy = \"outside-ci\"\n").unwrap();
+
+    for ci in ["true", "1"] {
+        let outcome = with_env(&[("CI", ci)], || {
+            checker(&registry, dir.path()).check_outcome()
+        });
+        assert_eq!(
+            reason(outcome),
+            NoUpdateReason::ProjectDisabled { path: file.clone() }
+        );
+    }
+    for ci in ["", "false"] {
+        let update = with_env(&[("CI", ci)], || checker(&registry, dir.path()).check());
+        assert!(update.unwrap().is_some(), "CI={ci:?}");
+    }
+}
+
+#[test]
+fn malformed_project_file_names_path_and_key() {
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join(".tiny-update-check.toml");
+
+    for (contents, expected) in [
+        ("version_req = \"not a req\"\n", "key 'version_req'"),
+        ("disable = \"yes\"\n", "key 'disable'"),
+        ("notify_policy = \"sometimes\"\n", "key 'notify_policy'"),
+        ("disabled = true\n", "key 'disabled': unknown key"),
+        ("disable = true\ndisable = false\n", "set more than once"),
+        ("[policy]\n", "line 1"),
+    ] {
+        fs::write(&file, contents).unwrap();
+        let err = with_env(&[], || checker(&registry, dir.path()).check()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Config, "{contents:?}");
+        let message = err.to_string();
+        assert!(message.contains(&file.display().to_string()), "{message}");
+        assert!(message.contains(expected), "{message}");
+    }
+    assert!(registry.requests().is_empty());
+}
@@ -0,0 +1,84 @@
+//! Registry tokens read from `TINY_UPDATE_CHECK_TOKEN` variables.
+//!
+//! Kept in its own test binary because it sets those variables, which
+//! would otherwise leak into concurrently running tests.
+
+mod common;
+
+use common::{MockRegistry, parse_head};
+use tiny_update_check::UpdateChecker;
+
+/// The `Authorization` header of the last request `registry` received.
+fn sent_token(registry: &MockRegistry) -> Option<String> {
+    let requests = registry.requests();
+    let (_, _, headers) = parse_head(requests.last().unwrap());
+    headers
+        .into_iter()
+        .find(|(name, _)| name == "authorization")
+        .map(|(_, value)| value)
+}
+
+/// The variable naming `registry`'s token, from its `127.0.0.1:<port>`.
+fn registry_variable(registry: &MockRegistry) -> String {
+    let host = registry.url().trim_start_matches("http://");
+    format!("TINY_UPDATE_CHECK_TOKEN_{}", host.replace(['.', ':'], "_"))
+}
+
+#[test]
+fn environment_tokens_follow_precedence() {
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+    let checker = || {
+        UpdateChecker::new("tool", "1.0.0")
+            .registry_url(registry.url())
+            .cache_dir(None)
+    };
+    let check = |checker: UpdateChecker| {
+        checker.check().unwrap();
+        sent_token(&registry)
+    };
+    let variable = registry_variable(&registry);
+    let with_env = |generic: Option<&str>, specific: Option<&str>, f: &dyn Fn()| {
+        temp_env::with_vars(
+            [
+                ("TINY_UPDATE_CHECK_TOKEN", generic),
+                (variable.as_str(), specific),
+            ],
+            f,
+        );
+    };
+
+    with_env(None, None, &|| assert_eq!(check(checker()), None));
+    with_env(Some("generic"), None, &|| {
+        assert_eq!(check(checker()).as_deref(), Some("generic"));
+    });
+    with_env(Some("generic"), Some("specific"), &|| {
+        assert_eq!(check(checker()).as_deref(), Some("specific"));
+        // An explicit token always wins.
+        let explicit = checker().auth_token("explicit");
+        assert_eq!(check(explicit).as_deref(), Some("explicit"));
+    });
+    // Empty variables are unset.
+    with_env(Some("generic"), Some(""), &|| {
+        assert_eq!(check(checker()).as_deref(), Some("generic"));
+    });
+    with_env(Some(""), None, &|| assert_eq!(check(checker()), None));
+}
+
+#[test]
+fn crates_io_gets_no_environment_token() {
+    temp_env::with_var("TINY_UPDATE_CHECK_TOKEN", Some("generic"), || {
+        for checker in [
+            UpdateChecker::new("tool", "1.0.0"),
+            UpdateChecker::new("tool", "1.0.0").use_sparse_index(true),
+        ] {
+            let planned = checker.dry_run().unwrap();
+            assert!(
+                planned[0]
+                    .headers
+                    .iter()
+                    .all(|(name, _)| !name.eq_ignore_ascii_case("authorization")),
+                "{planned:?}"
+            );
+        }
+    });
+}
@@ -0,0 +1,104 @@
+//! Host overrides: requests for `crates.io` answered by a loopback HTTPS
+//! server with a certificate for it, signed by the CA in
+//! `tests/fixtures/tls/hosts-ca.pem`.
+
+mod common;
+
+#[cfg(any(feature = "rustls", feature = "async"))]
+use common::MockRegistry;
+use tiny_update_check::UpdateChecker;
+
+#[cfg(any(feature = "rustls", feature = "async"))]
+const HOSTS_CA: &[u8] = include_bytes!("fixtures/tls/hosts-ca.pem");
+#[cfg(any(feature = "rustls", feature = "async"))]
+const BODY: &str = r#"{"crate":{"name":"test","newest_version":"2.0.0"}}"#;
+#[cfg(any(feature = "rustls", feature = "async"))]
+const INDEX: &str =
+    r#"{"name":"test","vers":"3.0.0","deps":[],"cksum":"","features":{},"yanked":false}"#;
+
+#[cfg(feature = "rustls")]
+#[test]
+fn overridden_hosts_are_answered_locally() {
+    let registry = common::serve_crates_io(BODY);
+    let index = common::serve_crates_io(INDEX);
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .resolve("crates.io", registry)
+        .resolve("index.crates.io", index)
+        .add_root_certificate(HOSTS_CA)
+        .use_env_certificates(false)
+        .fast_offline_probe(true)
+        .cache_dir(None);
+
+    let update = checker.check().unwrap().unwrap();
+    assert_eq!(update.latest, "2.0.0");
+    let planned = checker.dry_run().unwrap();
+    assert_eq!(planned[0].url, "https://crates.io/api/v1/crates/test");
+
+    let update = checker.use_sparse_index(true).check().unwrap().unwrap();
+    assert_eq!(update.latest, "3.0.0");
+}
+
+#[cfg(feature = "rustls")]
+#[test]
+fn other_hosts_are_resolved_as_usual() {
+    let registry = MockRegistry::crates(&[("test", "2.0.0")]);
+    let url = registry.url().replace("127.0.0.1", "localhost");
+    let update = UpdateChecker::new("test", "1.0.0")
+        .registry_url(url)
+        .resolve("crates.io", "127.0.0.1:9".parse().unwrap())
+        .cache_dir(None)
+        .check()
+        .unwrap()
+        .unwrap();
+    assert_eq!(update.latest, "2.0.0");
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_overridden_hosts_are_answered_locally() {
+    use tiny_update_check::r#async::UpdateChecker;
+
+    let registry = common::serve_crates_io(BODY);
+    let index = common::serve_crates_io(INDEX);
+    let checker = UpdateChecker::new("test", "1.0.0")
+        .resolve("crates.io", registry)
+        .resolve("index.crates.io", index)
+        .add_root_certificate(HOSTS_CA)
+        .use_env_certificates(false)
+        .cache_dir(None);
+
+    let update = checker.clone().check().await.unwrap().unwrap();
+    assert_eq!(update.latest, "2.0.0");
+    let update = checker
+        .use_sparse_index(true)
+        .check()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(update.latest, "3.0.0");
+
+    let other = MockRegistry::crates(&[("test", "4.0.0")]);
+    let update = UpdateChecker::new("test", "1.0.0")
+        .registry_url(other.url().replace("127.0.0.1", "localhost"))
+        .resolve("crates.io", registry)
+        .cache_dir(None)
+        .check()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(update.latest, "4.0.0");
+}
+
+#[cfg(not(feature = "rustls"))]
+#[test]
+fn native_tls_refuses_host_overrides() {
+    use tiny_update_check::ErrorKind;
+
+    let err = UpdateChecker::new("test", "1.0.0")
+        .resolve("crates.io", "127.0.0.1:443".parse().unwrap())
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Config);
+    assert!(err.to_string().contains("host overrides"), "{err}");
+}
@@ -0,0 +1,79 @@
+//! The process-wide client pool of the `shared-pool` feature.
+//!
+//! Kept in its own test binary because the pool and its counters are global
+//! to the process, and because it sets proxy environment variables.
+
+#![cfg(all(feature = "shared-pool", any(feature = "rustls", feature = "async")))]
+
+mod common;
+
+use common::MockRegistry;
+use std::time::Duration;
+use tiny_update_check::{PoolStats, shared_pool_stats};
+
+/// The hits and misses since `before`.
+fn since(before: PoolStats) -> (u64, u64) {
+    let now = shared_pool_stats();
+    (now.hits - before.hits, now.misses - before.misses)
+}
+
+#[test]
+fn checkers_share_clients_only_with_identical_settings() {
+    let registry = MockRegistry::crates(&[("tool", "2.0.0")]);
+
+    #[cfg(feature = "rustls")]
+    {
+        use tiny_update_check::UpdateChecker;
+
+        let checker = |timeout| {
+            UpdateChecker::new("tool", "1.0.0")
+                .registry_url(registry.url())
+                .cache_dir(None)
+                .timeout(timeout)
+        };
+        let check = |checker: UpdateChecker| assert!(checker.check().unwrap().is_some());
+        temp_env::with_vars_unset(["HTTPS_PROXY", "https_proxy", "NO_PROXY"], || {
+            let before = shared_pool_stats();
+            check(checker(Duration::from_secs(5)));
+            check(checker(Duration::from_secs(5)));
+            assert_eq!(since(before), (1, 1));
+
+            // Any connection setting, or the proxy environment, keys another client.
+            check(checker(Duration::from_secs(7)));
+            check(checker(Duration::from_secs(5)).proxy_loopback(true));
+            temp_env::with_var("NO_PROXY", Some("example.com"), || {
+                check(checker(Duration::from_secs(5)));
+            });
+            assert_eq!(since(before), (1, 4));
+
+            check(checker(Duration::from_secs(7)));
+            assert_eq!(since(before), (2, 4));
+        });
+    }
+
+    #[cfg(feature = "async")]
+    {
+        use tiny_update_check::r#async::UpdateChecker;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let checker = |timeout| {
+            UpdateChecker::new("tool", "1.0.0")
+                .registry_url(registry.url())
+                .cache_dir(None)
+                .timeout(timeout)
+        };
+        runtime.block_on(async {
+            let before = shared_pool_stats();
+            for timeout in [3, 3, 4, 3] {
+                let update = checker(Duration::from_secs(timeout)).check().await;
+                assert!(update.unwrap().is_some());
+            }
+            assert_eq!(since(before), (2, 2));
+        });
+    }
+
+    assert!(shared_pool_stats().clients >= 2);
+}
@@ -0,0 +1,129 @@
+//! Consolidated summaries of several checks.
+//!
+//! Kept in its own test binary because it sets `CI`, which would otherwise
+//! leak into concurrently running tests.
+
+use std::sync::Arc;
+use std::thread;
+use tiny_update_check::{
+    CheckOutcome, DetailedUpdateInfo, Error, HttpFailure, NoUpdateReason, Subject, Summary,
+    SummaryStyle, UpdateInfo,
+};
+
+fn update(current: &str, latest: &str) -> CheckOutcome {
+    CheckOutcome::Update(DetailedUpdateInfo::from(UpdateInfo {
+        current: current.to_string(),
+        latest: latest.to_string(),
+    }))
+}
+
+fn plugin(name: &str) -> Subject {
+    Subject::Plugin(name.to_string())
+}
+
+/// The self-update, two plugin updates, and outcomes that aren't updates.
+fn entries() -> Vec<(Subject, CheckOutcome)> {
+    vec![
+        (plugin("lint"), update("0.9.0", "1.0.0")),
+        (
+            plugin("docs"),
+            CheckOutcome::NoUpdate(NoUpdateReason::UpToDate),
+        ),
+        (Subject::SelfUpdate, update("2.3.0", "2.4.1")),
+        (
+            plugin("bench"),
+            CheckOutcome::Failed(Error::HttpError(HttpFailure::new("timed out"))),
+        ),
+        (plugin("fmt"), update("0.3.0", "0.4.0")),
+    ]
+}
+
+const EXPECTED: &str = "\
+Update available: 2.3.0 -> 2.4.1
+2 plugin updates available:
+  fmt 0.3.0 -> 0.4.0
+  lint 0.9.0 -> 1.0.0
+";
+
+#[test]
+fn rendering_is_independent_of_push_order() {
+    let orders: [&[usize]; 4] = [
+        &[0, 1, 2, 3, 4],
+        &[4, 3, 2, 1, 0],
+        &[2, 0, 4, 1, 3],
+        &[1, 3, 4, 0, 2],
+    ];
+    for order in orders {
+        let mut entries: Vec<_> = entries().into_iter().map(Some).collect();
+        let summary = Summary::new();
+        for &index in order {
+            let (subject, outcome) = entries[index].take().unwrap();
+            summary.push(subject, outcome);
+        }
+        assert_eq!(
+            summary.render(SummaryStyle::new()).as_deref(),
+            Some(EXPECTED),
+            "{order:?}"
+        );
+    }
+}
+
+#[test]
+fn background_threads_can_populate_a_summary() {
+    let summary = Arc::new(Summary::new());
+    let handles: Vec<_> = entries()
+        .into_iter()
+        .map(|(subject, outcome)| {
+            let summary = Arc::clone(&summary);
+            thread::spawn(move || summary.push(subject, outcome))
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(
+        summary.render(SummaryStyle::new()).as_deref(),
+        Some(EXPECTED)
+    );
+}
+
+#[test]
+fn only_updates_are_rendered() {
+    let summary = Summary::new();
+    assert_eq!(summary.render(SummaryStyle::new()), None);
+
+    summary.push(
+        Subject::SelfUpdate,
+        CheckOutcome::NoUpdate(NoUpdateReason::UpToDate),
+    );
+    summary.push(plugin("fmt"), update("0.3.0", "0.4.0"));
+    assert_eq!(
+        summary.render(SummaryStyle::new()).as_deref(),
+        Some("1 plugin update available:\n  fmt 0.3.0 -> 0.4.0\n")
+    );
+
+    // A later outcome for the same subject replaces the earlier one.
+    summary.push(
+        plugin("fmt"),
+        CheckOutcome::NoUpdate(NoUpdateReason::UpToDate),
+    );
+    assert_eq!(summary.render(SummaryStyle::new()), None);
+}
+
+#[test]
+fn suppression_applies_to_the_whole_block() {
+    let summary = Summary::new();
+    for (subject, outcome) in entries() {
+        summary.push(subject, outcome);
+    }
+    assert_eq!(summary.render(SummaryStyle::new().quiet(true)), None);
+
+    let outside_ci = SummaryStyle::new().outside_ci(true);
+    temp_env::with_var("CI", Some("true"), || {
+        assert_eq!(summary.render(outside_ci), None);
+        assert!(summary.render(SummaryStyle::new()).is_some());
+    });
+    temp_env::with_var("CI", None::<&str>, || {
+        assert_eq!(summary.render(outside_ci).as_deref(), Some(EXPECTED));
+    });
+}
@@ -0,0 +1,493 @@
+//! Root certificates added to the checker or named by `SSL_CERT_FILE` and
+//! `SSL_CERT_DIR`, minimum TLS versions, certificate pins, accepting
+//! invalid certificates and the choice of TLS provider, against a loopback HTTPS registry whose certificate
+//! is signed by the test CA in `tests/fixtures/tls`, or self-signed.
+//!
+//! Kept in its own test binary because it sets those variables; tests that
+//! don't set them ignore them.
+
+mod common;
+
+#[cfg(any(feature = "rustls", feature = "async"))]
+use std::path::{Path, PathBuf};
+use tiny_update_check::{ErrorKind, Spki256, TlsProvider, TlsVersion, UpdateChecker};
+
+const CA_PEM: &[u8] = include_bytes!("fixtures/tls/ca.pem");
+#[cfg(any(feature = "rustls", feature = "async"))]
+const CA_DER: &[u8] = include_bytes!("fixtures/tls/ca.der");
+/// The hashes of the test CA's key and the server's.
+const CA_KEY: &str = "sha256//eWbHKcSO/CeOjgXTiIiOrYKvfCdiPYKGe+eV81QY34w=";
+#[cfg(feature = "rustls")]
+const SERVER_KEY: &str = "sha256//gdZ7X6EO/2HnWj3U30zd0qk4TcmMKSSqkpVOvsp9fjU=";
+/// A key in neither certificate.
+#[cfg(feature = "rustls")]
+const OTHER_KEY: &str = "sha256//47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=";
+#[cfg(any(feature = "rustls", feature = "async"))]
+const BODY: &str = r#"{"crate":{"name":"test","newest_version":"2.0.0"}}"#;
+
+#[cfg(feature = "rustls")]
+#[test]
+fn added_root_certificates_are_trusted() {
+    let registry = common::serve_tls(BODY);
+    let checker = || {
+        UpdateChecker::new("test", "1.0.0")
+            .registry_url(&registry)
+            .use_env_certificates(false)
+            .cache_dir(None)
+    };
+
+    let err = checker().check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Http, "{err}");
+
+    for ca in [CA_PEM, CA_DER] {
+        let update = checker().add_root_certificate(ca).check().unwrap();
+        assert_eq!(update.unwrap().latest, "2.0.0");
+    }
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_added_root_certificates_are_trusted() {
+    use tiny_update_check::r#async::UpdateChecker;
+
+    let registry = common::serve_tls(BODY);
+    let checker = || {
+        UpdateChecker::new("test", "1.0.0")
+            .registry_url(&registry)
+            .use_env_certificates(false)
+            .cache_dir(None)
+    };
+
+    let err = checker().check().await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Http, "{err}");
+
+    for ca in [CA_PEM, CA_DER] {
+        let update = checker().add_root_certificate(ca).check().await.unwrap();
+        assert_eq!(update.unwrap().latest, "2.0.0");
+    }
+}
+
+#[test]
+fn invalid_root_certificates_are_config_errors() {
+    let bundle = [CA_PEM, b"-----BEGIN CERTIFICATE-----\nMIIB"].concat();
+    let err = UpdateChecker::new("test", "1.0.0")
+        .add_root_certificate(bundle)
+        .use_env_certificates(false)
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Config);
+    #[cfg(feature = "rustls")]
+    assert!(
+        err.to_string()
+            .contains("root certificate 1 has no `-----END CERTIFICATE-----` after certificate 2"),
+        "{err}"
+    );
+}
+
+#[cfg(not(feature = "rustls"))]
+#[test]
+fn native_tls_refuses_root_certificates() {
+    let err = UpdateChecker::new("test", "1.0.0")
+        .add_root_certificate(CA_PEM)
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Config);
+    assert!(err.to_string().contains("`rustls` feature"), "{err}");
+}
+
+/// A bundle of the test CA, twice over, and a directory holding it beside a
+/// file that isn't a certificate, in `dir`.
+#[cfg(any(feature = "rustls", feature = "async"))]
+fn environment_bundles(dir: &Path) -> (PathBuf, PathBuf) {
+    let bundle = dir.join("bundle.pem");
+    std::fs::write(&bundle, [CA_PEM, CA_PEM].concat()).unwrap();
+    let certs = dir.join("certs");
+    std::fs::create_dir(&certs).unwrap();
+    std::fs::write(certs.join("ca.pem"), CA_PEM).unwrap();
+    std::fs::write(certs.join("README"), "Certificates for the test CA.").unwrap();
+    (bundle, certs)
+}
+
+#[cfg(feature = "rustls")]
+#[test]
+fn environment_certificates_are_trusted() {
+    let registry = common::serve_tls(BODY);
+    let dir = tempfile::tempdir().unwrap();
+    let (bundle, certs) = environment_bundles(dir.path());
+    let checker = || {
+        UpdateChecker::new("test", "1.0.0")
+            .registry_url(&registry)
+            .cache_dir(None)
+    };
+
+    for (file, dirs) in [(Some(&bundle), None), (None, Some(&certs))] {
+        temp_env::with_vars([("SSL_CERT_FILE", file), ("SSL_CERT_DIR", dirs)], || {
+            let update = checker().check().unwrap();
+            assert_eq!(update.unwrap().latest, "2.0.0");
+
+            let err = checker().use_env_certificates(false).check().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Http, "{err}");
+        });
+    }
+}
+
+#[cfg(feature = "rustls")]
+#[test]
+fn unusable_environment_certificates_are_named() {
+    let dir = tempfile::tempdir().unwrap();
+    let malformed = dir.path().join("malformed.pem");
+    std::fs::write(&malformed, &CA_PEM[..CA_PEM.len() - 40]).unwrap();
+    let missing = dir.path().join("missing");
+    let checker = || UpdateChecker::new("test", "1.0.0").cache_dir(None);
+
+    for (var, path, problem) in [
+        ("SSL_CERT_FILE", &missing, "can't be read"),
+        (
+            "SSL_CERT_FILE",
+            &malformed,
+            "has no `-----END CERTIFICATE-----`",
+        ),
+        ("SSL_CERT_DIR", &missing, "can't be read"),
+    ] {
+        temp_env::with_vars([(var, Some(path))], || {
+            let err = checker().check().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Config, "{err}");
+            let message = err.to_string();
+            let named = format!("`{}` from `{var}` {problem}", path.display());
+            assert!(message.contains(&named), "{message}");
+        });
+    }
+
+    // Unused, the variables can't fail a check.
+    let registry = common::serve_tls(BODY);
+    temp_env::with_vars([("SSL_CERT_FILE", Some(&missing))], || {
+        let update = checker()
+            .registry_url(&registry)
+            .use_env_certificates(false)
+            .add_root_certificate(CA_PEM)
+            .check()
+            .unwrap();
+        assert_eq!(update.unwrap().latest, "2.0.0");
+    });
+}
+
+#[cfg(feature = "async")]
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_environment_certificates_are_trusted() {
+    use tiny_update_check::r#async::UpdateChecker;
+
+    let registry = common::serve_tls(BODY);
+    let dir = tempfile::tempdir().unwrap();
+    let (bundle, certs) = environment_bundles(dir.path());
+    let checker = || {
+        UpdateChecker::new("test", "1.0.0")
+            .registry_url(&registry)
+            .cache_dir(None)
+    };
+
+    for (file, dirs) in [(Some(&bundle), None), (None, Some(&certs))] {
+        temp_env::with_vars([("SSL_CERT_FILE", file), ("SSL_CERT_DIR", dirs)], || {
+            let update = block_on(checker().check()).unwrap();
+            assert_eq!(update.unwrap().latest, "2.0.0");
+
+            let err = block_on(checker().use_env_certificates(false).check()).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Http, "{err}");
+        });
+    }
+
+    let missing = dir.path().join("missing");
+    temp_env::with_vars([("SSL_CERT_DIR", Some(&missing))], || {
+        let err = block_on(checker().check()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Config, "{err}");
+        assert!(err.to_string().contains("from `SSL_CERT_DIR`"), "{err}");
+    });
+}
+
+#[cfg(feature = "rustls")]
+#[test]
+fn tls_1_3_minimum_refuses_a_tls_1_2_server() {
+    let registry = common::serve_tls_versions(BODY, &[&rustls::version::TLS12]);
+    let checker = |version| {
+        UpdateChecker::new("test", "1.0.0")
+            .registry_url(&registry)
+            .add_root_certificate(CA_PEM)
+            .use_env_certificates(false)
+            .min_tls_version(version)
+            .cache_dir(None)
+    };
+
+    let update = checker(TlsVersion::Tls1_2).check().unwrap();
+    assert_eq!(update.unwrap().latest, "2.0.0");
+    let err = checker(TlsVersion::Tls1_3).check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Http, "{err}");
+    assert!(err.to_string().contains("ProtocolVersion"), "{err}");
+
+    let registry = common::serve_tls(BODY);
+    let update = UpdateChecker::new("test", "1.0.0")
+        .registry_url(&registry)
+        .add_root_certificate(CA_PEM)
+        .use_env_certificates(false)
+        .min_tls_version(TlsVersion::Tls1_3)
+        .cache_dir(None)
+        .check()
+        .unwrap();
+    assert_eq!(update.unwrap().latest, "2.0.0");
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_tls_1_3_minimum_refuses_a_tls_1_2_server() {
+    use tiny_update_check::r#async::UpdateChecker;
+
+    let registry = common::serve_tls_versions(BODY, &[&rustls::version::TLS12]);
+    let checker = |version| {
+        UpdateChecker::new("test", "1.0.0")
+            .registry_url(&registry)
+            .add_root_certificate(CA_PEM)
+            .use_env_certificates(false)
+            .min_tls_version(version)
+            .cache_dir(None)
+    };
+
+    let update = checker(TlsVersion::Tls1_2).check().await.unwrap();
+    assert_eq!(update.unwrap().latest, "2.0.0");
+    let err = checker(TlsVersion::Tls1_3).check().await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Http, "{err}");
+}
+
+#[cfg(not(feature = "rustls"))]
+#[test]
+fn native_tls_refuses_a_minimum_tls_version() {
+    let err = UpdateChecker::new("test", "1.0.0")
+        .min_tls_version(TlsVersion::Tls1_2)
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Config);
+    assert!(err.to_string().contains("minimum TLS version"), "{err}");
+}
+
+#[cfg(feature = "rustls")]
+fn pins(keys: &[&str]) -> Vec<Spki256> {
+    keys.iter()
+        .map(|key| Spki256::from_base64(key).unwrap())
+        .collect()
+}
+
+#[cfg(feature = "rustls")]
+#[test]
+fn pinned_keys_are_required_in_the_chain() {
+    let registry = common::serve_tls(BODY);
+    let checker = |keys: &[&str]| {
+        UpdateChecker::new("test", "1.0.0")
+            .registry_url(&registry)
+            .add_root_certificate(CA_PEM)
+            .use_env_certificates(false)
+            .pin_certificates(pins(keys))
+            .cache_dir(None)
+    };
+
+    // The server's key, the CA's, and either among other pins match.
+    for keys in [
+        &[SERVER_KEY][..],
+        &[CA_KEY],
+        &[OTHER_KEY, SERVER_KEY],
+        &[OTHER_KEY, CA_KEY],
+    ] {
+        let update = checker(keys).check().unwrap();
+        assert_eq!(update.unwrap().latest, "2.0.0", "{keys:?}");
+    }
+
+    let err = checker(&[OTHER_KEY]).check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PinMismatch, "{err}");
+    let message = err.to_string();
+    assert!(message.contains(SERVER_KEY), "{message}");
+    assert!(message.contains(CA_KEY), "{message}");
+    assert!(!message.contains(OTHER_KEY), "{message}");
+
+    // Pinned connections keep the minimum version.
+    let tls_1_2 = common::serve_tls_versions(BODY, &[&rustls::version::TLS12]);
+    let err = checker(&[CA_KEY])
+        .registry_url(&tls_1_2)
+        .min_tls_version(TlsVersion::Tls1_3)
+        .check()
+        .unwrap_err();
+    assert!(err.to_string().contains("ProtocolVersion"), "{err}");
+
+    // Pins don't relax verification.
+    let err = UpdateChecker::new("test", "1.0.0")
+        .registry_url(&registry)
+        .use_env_certificates(false)
+        .pin_certificates(pins(&[SERVER_KEY]))
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Http, "{err}");
+}
+
+#[cfg(all(feature = "async", feature = "rustls"))]
+#[tokio::test]
+async fn async_pinned_keys_are_required_in_the_chain() {
+    use tiny_update_check::r#async::UpdateChecker;
+
+    let registry = common::serve_tls(BODY);
+    let checker = |keys: &[&str]| {
+        UpdateChecker::new("test", "1.0.0")
+            .registry_url(&registry)
+            .add_root_certificate(CA_PEM)
+            .use_env_certificates(false)
+            .pin_certificates(pins(keys))
+            .cache_dir(None)
+    };
+
+    let update = checker(&[OTHER_KEY, CA_KEY]).check().await.unwrap();
+    assert_eq!(update.unwrap().latest, "2.0.0");
+
+    let err = checker(&[OTHER_KEY]).check().await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PinMismatch, "{err}");
+    assert!(err.to_string().contains(SERVER_KEY), "{err}");
+}
+
+#[cfg(not(feature = "rustls"))]
+#[test]
+fn native_tls_refuses_certificate_pins() {
+    let err = UpdateChecker::new("test", "1.0.0")
+        .pin_certificates(vec![Spki256::from_base64(CA_KEY).unwrap()])
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Config);
+    assert!(err.to_string().contains("certificate pins"), "{err}");
+}
+
+#[cfg(feature = "rustls")]
+#[test]
+fn self_signed_certificates_are_refused() {
+    let registry = common::serve_self_signed(BODY);
+    let err = UpdateChecker::new("test", "1.0.0")
+        .registry_url(&registry)
+        .add_root_certificate(CA_PEM)
+        .use_env_certificates(false)
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Http, "{err}");
+}
+
+#[cfg(all(feature = "dangerous-config", feature = "rustls"))]
+#[test]
+fn invalid_certificates_are_accepted_when_asked() {
+    let registry = common::serve_self_signed(BODY);
+    let checker = |accept| {
+        UpdateChecker::new("test", "1.0.0")
+            .registry_url(&registry)
+            .use_env_certificates(false)
+            .danger_accept_invalid_certs(accept)
+            .cache_dir(None)
+    };
+
+    assert!(checker(false).check().is_err());
+    let accepting = checker(true);
+    assert!(
+        format!("{accepting:?}").contains("danger_accept_invalid_certs: true"),
+        "{accepting:?}"
+    );
+    let update = accepting.check().unwrap();
+    assert_eq!(update.unwrap().latest, "2.0.0");
+
+    let err = checker(true)
+        .pin_certificates(pins(&[CA_KEY]))
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Config, "{err}");
+}
+
+#[cfg(all(feature = "dangerous-config", feature = "async"))]
+#[tokio::test]
+async fn async_invalid_certificates_are_accepted_when_asked() {
+    use tiny_update_check::r#async::UpdateChecker;
+
+    let registry = common::serve_self_signed(BODY);
+    let checker = |accept| {
+        UpdateChecker::new("test", "1.0.0")
+            .registry_url(&registry)
+            .use_env_certificates(false)
+            .danger_accept_invalid_certs(accept)
+            .cache_dir(None)
+    };
+
+    assert!(checker(false).check().await.is_err());
+    let accepting = checker(true);
+    assert!(
+        format!("{accepting:?}").contains("danger_accept_invalid_certs: true"),
+        "{accepting:?}"
+    );
+    let update = accepting.check().await.unwrap();
+    assert_eq!(update.unwrap().latest, "2.0.0");
+}
+
+#[cfg(all(feature = "dangerous-config", not(feature = "rustls")))]
+#[test]
+fn native_tls_refuses_to_accept_invalid_certificates() {
+    let err = UpdateChecker::new("test", "1.0.0")
+        .danger_accept_invalid_certs(true)
+        .cache_dir(None)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Config);
+    assert!(err.to_string().contains("invalid certificates"), "{err}");
+}
+
+#[test]
+fn the_provider_follows_the_features() {
+    let provider = UpdateChecker::new("test", "1.0.0").tls_provider_in_use();
+    if cfg!(feature = "rustls") {
+        assert_eq!(provider, TlsProvider::Rustls);
+    } else {
+        assert_eq!(provider, TlsProvider::NativeTls);
+    }
+}
+
+#[cfg(all(feature = "native-tls", feature = "rustls"))]
+#[test]
+fn the_provider_can_be_chosen_at_runtime() {
+    let registry = common::MockRegistry::serve(BODY);
+    let checker = |provider| {
+        UpdateChecker::new("test", "1.0.0")
+            .registry_url(registry.url())
+            .use_env_certificates(false)
+            .tls_provider(provider)
+            .cache_dir(None)
+    };
+
+    // ureq asks for any media type; minreq sends only the headers it is given.
+    for (provider, accepts) in [(TlsProvider::NativeTls, false), (TlsProvider::Rustls, true)] {
+        let checker = checker(provider);
+        assert_eq!(checker.tls_provider_in_use(), provider);
+        let update = checker.check().unwrap();
+        assert_eq!(update.unwrap().latest, "2.0.0");
+        let request = registry.requests().pop().unwrap().to_ascii_lowercase();
+        assert_eq!(
+            request.contains("accept: */*"),
+            accepts,
+            "{provider}: {request}"
+        );
+    }
+
+    let err = checker(TlsProvider::NativeTls)
+        .add_root_certificate(CA_PEM)
+        .check()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Config, "{err}");
+}
@@ -5,12 +5,17 @@
 //! - ASCII alphanumeric, `-`, or `_` only
 //! - Must start with an alphabetic character
 //! - Maximum 64 characters
+//!
+//! Lenient validation drops the rule on the first character.
+
+mod common;
 
-use tiny_update_check::{Error, UpdateChecker};
+use common::MockRegistry;
+use tiny_update_check::{Error, ErrorKind, UpdateChecker};
 
 /// Helper to check if an error is `InvalidCrateName`
-const fn is_invalid_crate_name(err: &Error) -> bool {
-    matches!(err, Error::InvalidCrateName(_))
+fn is_invalid_crate_name(err: &Error) -> bool {
+    err.kind() == ErrorKind::InvalidCrateName
 }
 
 #[test]
@@ -98,66 +103,67 @@ fn rejects_crate_name_too_long() {
     );
 }
 
+/// Assert that a check for `name` gets as far as the registry, a mock one
+/// serving it at 1.0.0.
+fn assert_accepted(name: &str) {
+    let registry = MockRegistry::crates(&[(name, "1.0.0")]);
+    let update = UpdateChecker::new(name, "0.0.1")
+        .registry_url(registry.url())
+        .cache_dir(None)
+        .check()
+        .unwrap_or_else(|err| panic!("{name} should be accepted: {err}"));
+    assert_eq!(update.unwrap().latest, "1.0.0");
+}
+
 #[test]
 fn accepts_valid_simple_name() {
-    // This test will make a real HTTP request, so we just verify
-    // it doesn't fail with InvalidCrateName
-    let checker = UpdateChecker::new("serde", "0.0.1");
-    let result = checker.check();
-    // Should either succeed or fail with HTTP/Parse error, not InvalidCrateName
-    if let Err(err) = &result {
-        assert!(
-            !is_invalid_crate_name(err),
-            "Should not be InvalidCrateName: {err}"
-        );
-    }
+    assert_accepted("serde");
 }
 
 #[test]
 fn accepts_valid_name_with_hyphens() {
-    let checker = UpdateChecker::new("my-cool-crate", "0.0.1");
-    let result = checker.check();
-    if let Err(err) = &result {
-        assert!(
-            !is_invalid_crate_name(err),
-            "Should not be InvalidCrateName: {err}"
-        );
-    }
+    assert_accepted("my-cool-crate");
 }
 
 #[test]
 fn accepts_valid_name_with_underscores() {
-    let checker = UpdateChecker::new("my_cool_crate", "0.0.1");
-    let result = checker.check();
-    if let Err(err) = &result {
-        assert!(
-            !is_invalid_crate_name(err),
-            "Should not be InvalidCrateName: {err}"
-        );
-    }
+    assert_accepted("my_cool_crate");
 }
 
 #[test]
 fn accepts_valid_name_with_numbers() {
-    let checker = UpdateChecker::new("crate2", "0.0.1");
-    let result = checker.check();
-    if let Err(err) = &result {
-        assert!(
-            !is_invalid_crate_name(err),
-            "Should not be InvalidCrateName: {err}"
-        );
-    }
+    assert_accepted("crate2");
 }
 
 #[test]
 fn accepts_max_length_name() {
-    let max_name = "a".repeat(64);
-    let checker = UpdateChecker::new(max_name, "0.0.1");
-    let result = checker.check();
-    if let Err(err) = &result {
-        assert!(
-            !is_invalid_crate_name(err),
-            "Should not be InvalidCrateName: {err}"
-        );
+    assert_accepted(&"a".repeat(64));
+}
+
+#[test]
+fn lenient_validation_accepts_grandfathered_names() {
+    let registry = MockRegistry::crates(&[("0x-util", "2.0.0")]);
+    let dir = tempfile::tempdir().unwrap();
+    let checker = UpdateChecker::new("0x-util", "1.0.0")
+        .registry_url(registry.url())
+        .cache_dir(Some(dir.path().to_path_buf()));
+
+    assert!(is_invalid_crate_name(&checker.check().unwrap_err()));
+    assert!(registry.requests().is_empty());
+
+    let checker = checker.lenient_name_validation(true);
+    assert_eq!(checker.check().unwrap().unwrap().latest, "2.0.0");
+    assert!(dir.path().join("0x-util-update-check").exists());
+}
+
+#[test]
+fn lenient_validation_still_rejects_unsafe_names() {
+    for name in ["0x/../etc", "0x.util", "0x util", ""] {
+        let err = UpdateChecker::new(name, "1.0.0")
+            .lenient_name_validation(true)
+            .cache_dir(None)
+            .check()
+            .unwrap_err();
+        assert!(is_invalid_crate_name(&err), "{name:?}: {err}");
     }
 }